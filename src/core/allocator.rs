@@ -0,0 +1,200 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// 缓冲区规格(size class)，申请的容量向上取整到最近的规格
+///
+const SIZE_CLASSES: [usize; 6] = [64, 256, 1024, 4096, 16384, 65536];
+
+///
+/// 每个规格最多缓存的空闲缓冲区数量
+///
+const MAX_CACHED_PER_CLASS: usize = 256;
+
+
+///
+/// 单个EventLoop 的内存池, EventLoop 是单线程的，所以每个EventLoop 一个arena 即线程本地arena
+///
+struct PoolArena {
+    free_lists: Vec<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl PoolArena {
+    fn new() -> PoolArena {
+        let mut free_lists = Vec::with_capacity(SIZE_CLASSES.len());
+        for _i in 0..SIZE_CLASSES.len() {
+            free_lists.push(Mutex::new(Vec::new()));
+        }
+        PoolArena {
+            free_lists
+        }
+    }
+}
+
+
+///
+/// 内存池统计
+///
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct PoolStats {
+    /// 从池中直接拿到空闲缓冲区的次数
+    pub hits: usize,
+    /// 池中没有空闲缓冲区，新分配的次数
+    pub misses: usize,
+    /// 已借出尚未归还的缓冲区数量
+    pub outstanding: usize,
+    /// 归还到池中的次数
+    pub recycled: usize,
+    /// 当前池中缓存的空闲缓冲区数量
+    pub cached: usize,
+}
+
+
+///
+/// 池化的缓冲区分配器, 每个EventLoop 持有一个
+///
+/// 读路径、解码器、编码器通过它申请可复用的 `Vec<u8>`, `PooledBuffer` 被释放(drop)时自动归还
+///
+/// EventLoop 读到的数据和内置编码器的输出都以 `PooledBuffer` 在pipeline 中传递, 写出或被丢弃后归还;
+/// 直接处理字节的handler 用 `message::as_bytes` 同时兼容 `ByteBuf` 和 `PooledBuffer`
+///
+pub struct PooledByteBufAllocator {
+    arena: PoolArena,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    outstanding: AtomicUsize,
+    recycled: AtomicUsize,
+}
+
+impl PooledByteBufAllocator {
+    pub fn new() -> PooledByteBufAllocator {
+        PooledByteBufAllocator {
+            arena: PoolArena::new(),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            outstanding: AtomicUsize::new(0),
+            recycled: AtomicUsize::new(0),
+        }
+    }
+
+    ///
+    /// 申请一个容量至少为 capacity 的空缓冲区
+    ///
+    pub fn buffer(self: &Arc<Self>, capacity: usize) -> PooledBuffer {
+        self.outstanding.fetch_add(1, Ordering::Relaxed);
+        let buf = match PooledByteBufAllocator::size_class_index(capacity) {
+            Some(idx) => {
+                let cached = self.arena.free_lists[idx].lock().unwrap().pop();
+                match cached {
+                    Some(buf) => {
+                        self.hits.fetch_add(1, Ordering::Relaxed);
+                        buf
+                    }
+                    None => {
+                        self.misses.fetch_add(1, Ordering::Relaxed);
+                        Vec::with_capacity(SIZE_CLASSES[idx])
+                    }
+                }
+            }
+            // 超过最大规格的不池化
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                Vec::with_capacity(capacity)
+            }
+        };
+        PooledBuffer {
+            buf: Some(buf),
+            allocator: self.clone(),
+        }
+    }
+
+    ///
+    /// 申请一个缓冲区并拷贝 bytes
+    ///
+    pub fn buffer_from(self: &Arc<Self>, bytes: &[u8]) -> PooledBuffer {
+        let mut buf = self.buffer(bytes.len());
+        buf.extend_from_slice(bytes);
+        buf
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let cached = self.arena.free_lists.iter().map(|l| l.lock().unwrap().len()).sum();
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            outstanding: self.outstanding.load(Ordering::Relaxed),
+            recycled: self.recycled.load(Ordering::Relaxed),
+            cached,
+        }
+    }
+
+    fn recycle(&self, mut buf: Vec<u8>) {
+        self.outstanding.fetch_sub(1, Ordering::Relaxed);
+        // 按实际容量归类，容量不足最小规格或者超过最大规格的直接丢弃
+        let idx = SIZE_CLASSES.iter().rposition(|c| *c <= buf.capacity());
+        if let Some(idx) = idx {
+            let mut free_list = self.arena.free_lists[idx].lock().unwrap();
+            if free_list.len() < MAX_CACHED_PER_CLASS && buf.capacity() <= SIZE_CLASSES[SIZE_CLASSES.len() - 1] * 2 {
+                buf.clear();
+                free_list.push(buf);
+                self.recycled.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[inline]
+    fn size_class_index(capacity: usize) -> Option<usize> {
+        SIZE_CLASSES.iter().position(|c| *c >= capacity)
+    }
+}
+
+
+///
+/// 从内存池借出的缓冲区, drop 时归还给池
+///
+pub struct PooledBuffer {
+    buf: Option<Vec<u8>>,
+    allocator: Arc<PooledByteBufAllocator>,
+}
+
+impl PooledBuffer {
+    ///
+    /// 显式释放，等同于drop
+    ///
+    pub fn release(self) {}
+
+    ///
+    /// 丢弃前面已经消费的 n 个字节，剩余字节移到缓冲区头部
+    ///
+    pub fn discard_read_bytes(&mut self, n: usize) {
+        let buf = self.buf.as_mut().unwrap();
+        if n >= buf.len() {
+            buf.clear();
+        } else {
+            buf.drain(..n);
+        }
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Self::Target {
+        self.buf.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.buf.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(buf) = self.buf.take() {
+            self.allocator.recycle(buf);
+        }
+    }
+}
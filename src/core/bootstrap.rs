@@ -16,12 +16,24 @@ use mio::net::TcpListener;
 use uuid::Uuid;
 
 use crate::core::eventloop::{EventLoop, EventLoopGroup};
+use crate::core::metrics::MetricsRegistry;
+use crate::core::trace;
 use crate::errors::RettyErrorKind;
 use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
 use crate::handler::channel_handler_ctx_pipe::{ChannelInboundHandlerCtxPipe, ChannelOutboundHandlerCtxPipe};
 use crate::handler::handler::{ChannelOutboundHandler, HeadHandler, TailHandler};
 use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+use crate::handler::metrics_handler::PrometheusMetricsHandler;
 use crate::transport::channel::{Channel, ChannelOptions};
+use crate::transport::channel_id::ChannelId;
+use crate::transport::local::{self, LocalChannel, LocalServer};
+use crate::transport::socket::{self, SocketChannel};
+
+///
+/// 为每个连接创建一对入站/出站 handler pipeline
+///
+pub(crate) type PipelineFn = Arc<dyn Fn() -> (ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe) + Send + Sync>;
+
 
 struct Sessions {
     channel: Arc<Mutex<Channel>>,
@@ -45,10 +57,27 @@ pub struct Bootstrap {
     worker_group: Option<Arc<EventLoopGroup>>,
     channel_inbound_handler_pipe_fn: Option<Arc<dyn Fn() -> ChannelInboundHandlerPipe + Send + Sync + 'static>>,
     channel_outbound_handler_pipe_fn: Option<Arc<dyn Fn() -> ChannelOutboundHandlerPipe + Send + Sync + 'static>>,
+    ///
+    /// 同时初始化入站和出站pipeline, 在上面两个之后调用
+    ///
+    channel_pipeline_fn: Option<Arc<dyn Fn(&mut ChannelInboundHandlerPipe, &mut ChannelOutboundHandlerPipe) + Send + Sync + 'static>>,
     opts: HashMap<String, ChannelOptions>,
     stopped: Arc<AtomicBool>,
-    channel_container: Arc<Mutex<HashMap<Token, Arc<Mutex<Sessions>>>>>,
-
+    channel_container: Arc<Mutex<HashMap<ChannelId, Arc<Mutex<Sessions>>>>>,
+    metrics: Arc<MetricsRegistry>,
+    ///
+    /// 内置指标监听地址
+    ///
+    metrics_addr: Option<(String, u16)>,
+    metrics_bootstrap: Option<Box<Bootstrap>>,
+    ///
+    /// connect 和 connect_local 轮询使用的EventLoop
+    ///
+    next_local_loop: usize,
+    ///
+    /// 绑定的进程内地址, 设置后不再监听TCP 端口
+    ///
+    local_name: Option<String>,
 }
 
 
@@ -61,12 +90,37 @@ impl Bootstrap {
             worker_group: None,
             channel_inbound_handler_pipe_fn: None,
             channel_outbound_handler_pipe_fn: None,
+            channel_pipeline_fn: None,
             opts: HashMap::new(),
             stopped: Arc::new(AtomicBool::new(false)),
             channel_container: Arc::new(Mutex::new(HashMap::new())),
+            metrics: Arc::new(MetricsRegistry::new()),
+            metrics_addr: None,
+            metrics_bootstrap: None,
+            next_local_loop: 0,
+            local_name: None,
         }
     }
 
+    ///
+    /// 客户端只需要 worker_group 和 pipeline, 通过 connect 或 connect_local 连接
+    ///
+    pub fn new_client_bootstrap() -> Bootstrap {
+        Bootstrap::new_server_bootstrap()
+    }
+
+    pub fn metrics(&self) -> Arc<MetricsRegistry> {
+        self.metrics.clone()
+    }
+
+    ///
+    /// 启动内置的指标监听器, 以 Prometheus text 格式输出指标
+    ///
+    pub fn opt_metrics_listener(&mut self, host: &str, port: u16) -> &mut Self {
+        self.metrics_addr = Some((host.to_owned(), port));
+        self
+    }
+
 
     pub fn initialize_inbound_handler_pipeline<F>(&mut self, pipe_fn: F) -> &mut Self
         where F: Fn() -> ChannelInboundHandlerPipe + Send + Sync + 'static
@@ -82,6 +136,42 @@ impl Bootstrap {
         self
     }
 
+    ///
+    /// 同时初始化入站和出站pipeline, 用于需要成对安装的handler, 比如 CombinedChannelDuplexHandler
+    ///
+    pub fn initialize_pipeline<F>(&mut self, pipeline_fn: F) -> &mut Self
+        where F: Fn(&mut ChannelInboundHandlerPipe, &mut ChannelOutboundHandlerPipe) + Send + Sync + 'static
+    {
+        self.channel_pipeline_fn = Some(Arc::new(pipeline_fn));
+        self
+    }
+
+    ///
+    /// 合并三个初始化函数, 没有设置的pipeline 为空
+    ///
+    fn pipeline_fn(&self) -> PipelineFn {
+        if self.channel_inbound_handler_pipe_fn.is_none() && self.channel_outbound_handler_pipe_fn.is_none() && self.channel_pipeline_fn.is_none() {
+            panic!("handler pipeline is not initialized");
+        }
+        let inbound_fn = self.channel_inbound_handler_pipe_fn.clone();
+        let outbound_fn = self.channel_outbound_handler_pipe_fn.clone();
+        let pipeline_fn = self.channel_pipeline_fn.clone();
+        Arc::new(move || {
+            let mut inbound = match &inbound_fn {
+                Some(f) => f(),
+                None => ChannelInboundHandlerPipe::new(),
+            };
+            let mut outbound = match &outbound_fn {
+                Some(f) => f(),
+                None => ChannelOutboundHandlerPipe::new(),
+            };
+            if let Some(f) = &pipeline_fn {
+                f(&mut inbound, &mut outbound);
+            }
+            (inbound, outbound)
+        })
+    }
+
     // 设置 worker_group
     pub fn worker_group(&mut self, n: usize) -> &mut Self {
         self.worker_group = Some(Arc::new(EventLoopGroup::new(n)));
@@ -137,6 +227,28 @@ impl Bootstrap {
     }
 
 
+    ///
+    /// connect 的超时时间, 默认30 秒
+    ///
+    pub fn opt_connect_timeout_ms(&mut self, ms: usize) -> &mut Self {
+        self.opts.insert(
+            "connect_timeout_ms".to_owned(),
+            ChannelOptions::NUMBER(ms),
+        );
+        self
+    }
+
+    ///
+    /// 出站缓冲区的高水位, 超过后暂停读, 降到一半以下恢复, 默认1MB
+    ///
+    pub fn opt_write_buffer_high_water_mark(&mut self, bytes: usize) -> &mut Self {
+        self.opts.insert(
+            "write_buffer_high_water_mark".to_owned(),
+            ChannelOptions::NUMBER(bytes),
+        );
+        self
+    }
+
     pub fn opt_read_idle_timeout_ms(&mut self, ms: usize) -> &mut Self {
         self.opts.insert(
             "read_idle_timeout_ms".to_owned(),
@@ -152,16 +264,69 @@ impl Bootstrap {
         self
     }
 
+    ///
+    /// 绑定进程内地址, client 通过 connect_local(name) 连接, 不经过内核socket
+    ///
+    pub fn bind_local(&mut self, name: &str) -> &mut Self {
+        self.local_name = Some(name.to_owned());
+        self
+    }
+
+    ///
+    /// 连接到 bind_local 绑定的server, 返回client 端的channel
+    ///
+    pub fn connect_local(&mut self, name: &str) -> Result<LocalChannel, RettyErrorKind> {
+        let event_loop = self.next_client_loop();
+        local::connect(name, event_loop, self.pipeline_fn())
+    }
+
+    ///
+    /// 以TCP 连接到 host:port, 返回client 端的channel
+    ///
+    /// 这是同步调用: 域名解析和 connect 都在调用线程上阻塞完成, 每个解析出的地址最多等
+    /// `opt_connect_timeout_ms`, 依次尝试, 最坏情况是地址数乘以超时。不要在handler 或
+    /// EventLoop::execute 的任务里调用, 否则会卡住整个EventLoop 上的所有channel
+    ///
+    pub fn connect(&mut self, host: &str, port: u16) -> Result<SocketChannel, RettyErrorKind> {
+        let (event_loop, opts, pipeline_fn) = self.prepare_connect();
+        socket::connect(host, port, opts, event_loop, pipeline_fn)
+    }
+
+    ///
+    /// 取出一次连接需要的EventLoop、选项和pipeline, 调用方可以不持有Bootstrap 完成连接
+    ///
+    pub(crate) fn prepare_connect(&mut self) -> (Arc<EventLoop>, HashMap<String, ChannelOptions>, PipelineFn) {
+        (self.next_client_loop(), self.opts.clone(), self.pipeline_fn())
+    }
+
+    fn next_client_loop(&mut self) -> Arc<EventLoop> {
+        let work_group = self.worker_group.get_or_insert_with(|| Arc::new(EventLoopGroup::new(1))).clone();
+        work_group.event_loop_group().iter().for_each(|e| e.run());
+        let event_loop = work_group.event_loop_group()[self.next_local_loop % work_group.event_loop_group().len()].clone();
+        self.next_local_loop = self.next_local_loop.wrapping_add(1);
+        event_loop
+    }
+
     ///
     pub fn terminate(&mut self) {
         self.stopped.store(true, Ordering::Relaxed);
+        if let Some(ref name) = self.local_name {
+            local::unbind(name);
+        }
         if let Some(ref group) = &self.worker_group {
             group.event_loop_group().iter().for_each(|g| { g.shutdown(); });
         }
+        if let Some(ref mut metrics_bootstrap) = self.metrics_bootstrap {
+            metrics_bootstrap.terminate();
+        }
     }
 
 
     pub fn start(&mut self) {
+        if let Some(name) = self.local_name.clone() {
+            self.start_local(name.as_str());
+            return;
+        }
         let mut boss_group = &mut self.boss_group;
         let boss_eventloop = boss_group.next().unwrap();
         let idle_task_event_loop = boss_group.next().unwrap();
@@ -174,22 +339,28 @@ impl Bootstrap {
 
         let opts = self.opts.clone();
         let stopped = Arc::clone(&self.stopped);
+        let metrics = Arc::clone(&self.metrics);
+        work_group.event_loop_group().iter().for_each(|e| metrics.register_event_loop(e.name(), e.clone()));
+        self.start_metrics_listener();
 
-        let channel_inbound_handler_pipe_fn = &self.channel_inbound_handler_pipe_fn.as_ref().unwrap();
-        let channel_inbound_handler_pipe_fn = Arc::clone(channel_inbound_handler_pipe_fn);
-        let channel_outbound_handler_pipe_fn = &self.channel_outbound_handler_pipe_fn.as_ref().unwrap();
-        let channel_outbound_handler_pipe_fn = Arc::clone(channel_outbound_handler_pipe_fn);
+        let pipeline_fn = self.pipeline_fn();
 
 
         let channel_container = Arc::clone(&self.channel_container);
         idle_task_event_loop.excutor.spawn(move || {
-            let (s, r) = bounded::<Token>(1024);
+            let (s, r) = bounded::<ChannelId>(1024);
             let (s, r) = (s.clone(), r.clone());
             loop {
+                // 已经关闭的channel 不再检测
+                channel_container.lock().unwrap().retain(|_k, sess| {
+                    let sess = sess.lock().unwrap();
+                    let channel = sess.channel.lock().unwrap();
+                    !channel.is_closed()
+                });
                 for (k, sess) in channel_container.lock().unwrap().iter() {
                     let sess = sess.lock().unwrap();
                     let channel = sess.channel.lock().unwrap();
-                    if (Local::now().timestamp_millis() as u64).sub(channel.last_read_time_ms()) > channel.read_idle_timeout_ms() {
+                    if channel.is_read_idle() {
                         s.send(k.clone());
                     }
                 }
@@ -216,16 +387,16 @@ impl Bootstrap {
         let mut channel_container = Arc::clone(&self.channel_container);
         boss_eventloop.excutor.spawn(move || {
             let mut events = Events::with_capacity(1024);
-            let mut ch_id: usize = 1;
+            let mut next_loop: usize = 0;
 
             let mut listener = match TcpListener::bind(&sock_addr) {
                 Ok(s) => {
-                    println!("[High performance I/O framework written by Rust inspired by Netty]");
-                    println!("[Retty server is listening : {:?} : {:?}]", sock_addr.ip(), sock_addr.port());
+                    log::info!(target: trace::TARGET_BOOTSTRAP, "[High performance I/O framework written by Rust inspired by Netty]");
+                    log::info!(target: trace::TARGET_BOOTSTRAP, "[Retty server is listening : {:?} : {:?}]", sock_addr.ip(), sock_addr.port());
                     s
                 }
                 Err(e) => {
-                    println!("error : {:?}", e);
+                    log::error!(target: trace::TARGET_BOOTSTRAP, "bind {} error : {:?}", sock_addr, e);
                     panic!("server is not started:{:?}", e)
                 }
             };
@@ -237,7 +408,6 @@ impl Bootstrap {
             work_group.event_loop_group().iter().for_each(|e| e.run());
             //当服务器没有停的时候
             while !stopped.load(Ordering::Relaxed) {
-                let event_loop = work_group.event_loop_group()[ch_id % work_group.event_loop_group().len()].clone();
                 // 取出selector中的事件集合
                 match sel.poll(&mut events, Some(Duration::from_millis(200))) {
                     Ok(_) => {}
@@ -245,49 +415,99 @@ impl Bootstrap {
                         continue;
                     }
                 }
-                // 循环事件，监听accept
-                for _e in events.iter() {
+                if events.is_empty() {
+                    continue;
+                }
+                // 边缘触发, 一次事件可能对应多个连接, 一直accept 到WouldBlock
+                loop {
+                    let event_loop = work_group.event_loop_group()[next_loop % work_group.event_loop_group().len()].clone();
                     let (mut sock, addr) = match listener.accept() {
                         Ok((s, a)) => (s, a),
-                        Err(_) => {
-                            continue;
+                        Err(e) => {
+                            if e.kind() != ErrorKind::WouldBlock {
+                                log::warn!(target: trace::TARGET_BOOTSTRAP, "accept error : {:?}", e);
+                                metrics.record_accept_error();
+                            }
+                            break;
                         }
                     };
+                    metrics.record_accepted();
+                    log::debug!(target: trace::TARGET_BOOTSTRAP, "accepted connection from {}", addr);
 
-                    let channel = Channel::create(Token(ch_id),
+                    let channel_id = ChannelId::new_instance();
+                    let token = event_loop.allocate_token();
+                    let channel = Channel::create(channel_id,
+                                                  token,
                                                   opts.clone(),
                                                   event_loop.clone(),
                                                   sock.try_clone().unwrap());
 
                     let channel = Arc::new(Mutex::new(channel));
-                    let outbound_ctx_pipe = Bootstrap::create_channel_outbound_ctx_pipe(channel_outbound_handler_pipe_fn.clone(), event_loop.clone(), channel.clone());
-                    let inbound_ctx_pipe = Bootstrap::create_channel_inbound_ctx_pipe(channel_inbound_handler_pipe_fn.clone(), event_loop.clone(), channel.clone(), Arc::new(Mutex::new(outbound_ctx_pipe)));
-                    event_loop.clone().attach(ch_id, channel.clone(), inbound_ctx_pipe.clone());
+                    let (inbound_pipe, outbound_pipe) = (pipeline_fn)();
+                    let outbound_ctx_pipe = Bootstrap::create_channel_outbound_ctx_pipe(outbound_pipe, event_loop.clone(), channel.clone(), Box::new(TailHandler::new()));
+                    let inbound_ctx_pipe = Bootstrap::create_channel_inbound_ctx_pipe(inbound_pipe, event_loop.clone(), channel.clone(), Arc::new(Mutex::new(outbound_ctx_pipe)));
+                    event_loop.clone().attach(token, channel.clone(), inbound_ctx_pipe.clone());
                     let sessions = Arc::new(Mutex::new(Sessions::new(channel.clone(), Arc::new(inbound_ctx_pipe.clone()))));
-                    channel_container.lock().unwrap().insert(Token(ch_id).clone(), sessions.clone());
-                    ch_id = Bootstrap::incr_id(ch_id);
+                    channel_container.lock().unwrap().insert(channel_id, sessions.clone());
+                    next_loop = next_loop.wrapping_add(1);
                 }
             }
         });
     }
 
-    #[inline]
-    fn incr_id(cur_id: usize) -> usize {
-        if cur_id >= usize::MAX {
-            0
-        } else {
-            cur_id + 1
+    ///
+    /// 进程内server 没有监听器, 连接在 connect_local 时直接建立
+    ///
+    fn start_local(&mut self, name: &str) {
+        let work_group = match &self.worker_group {
+            None => panic!("work_group error"),
+            Some(g) => Arc::clone(g),
+        };
+        let metrics = Arc::clone(&self.metrics);
+        work_group.event_loop_group().iter().for_each(|e| metrics.register_event_loop(e.name(), e.clone()));
+        self.start_metrics_listener();
+        work_group.event_loop_group().iter().for_each(|e| e.run());
+
+        let server = LocalServer::new(work_group, self.pipeline_fn(), metrics);
+        match local::bind(name, server) {
+            Ok(_) => {
+                log::info!(target: trace::TARGET_BOOTSTRAP, "[Retty server is listening : local:{}]", name);
+            }
+            Err(e) => {
+                log::error!(target: trace::TARGET_BOOTSTRAP, "bind local:{} error : {:?}", name, e);
+                panic!("server is not started:{:?}", e)
+            }
         }
     }
 
+    ///
+    /// 指标监听器本身也是一个Retty server
+    ///
+    fn start_metrics_listener(&mut self) {
+        let (host, port) = match &self.metrics_addr {
+            Some(addr) => addr.clone(),
+            None => return,
+        };
+        let registry = self.metrics.clone();
+        let mut metrics_bootstrap = Bootstrap::new_server_bootstrap();
+        metrics_bootstrap.worker_group(1)
+            .bind(host.as_str(), port)
+            .initialize_inbound_handler_pipeline(move || {
+                let mut handler_pipe = ChannelInboundHandlerPipe::new();
+                handler_pipe.add_last(Box::new(PrometheusMetricsHandler::new(registry.clone())));
+                handler_pipe
+            })
+            .initialize_outbound_handler_pipeline(|| {
+                ChannelOutboundHandlerPipe::new()
+            }).start();
+        self.metrics_bootstrap = Some(Box::new(metrics_bootstrap));
+    }
 
     ///
     /// 创建入站处理pipeline
     ///
-    fn create_channel_inbound_ctx_pipe(in_channel_handler_pipe_fn: Arc<dyn Fn() -> ChannelInboundHandlerPipe + Send + Sync>, event_loop: Arc<EventLoop>, channel: Arc<Mutex<Channel>>, out_pipe: Arc<Mutex<ChannelOutboundHandlerCtxPipe>>) -> ChannelInboundHandlerCtxPipe
+    pub(crate) fn create_channel_inbound_ctx_pipe(mut channel_handler_pipe: ChannelInboundHandlerPipe, event_loop: Arc<EventLoop>, channel: Arc<Mutex<Channel>>, out_pipe: Arc<Mutex<ChannelOutboundHandlerCtxPipe>>) -> ChannelInboundHandlerCtxPipe
     {
-        // 创建ChannelHandlerPipe , 每一个连接创建自己的一套pipeline
-        let mut channel_handler_pipe: ChannelInboundHandlerPipe = (in_channel_handler_pipe_fn)();
         //添加头handler
         channel_handler_pipe.add_first(Box::new(HeadHandler::new()));
         // 创建ChannelHandlerCtxPipe
@@ -338,10 +558,8 @@ impl Bootstrap {
     ///
     /// 创建出站处理器pipeline
     ///
-    fn create_channel_outbound_ctx_pipe(out_channel_handler_pipe_fn: Arc<dyn Fn() -> ChannelOutboundHandlerPipe + Send + Sync>, event_loop: Arc<EventLoop>, channel: Arc<Mutex<Channel>>) -> ChannelOutboundHandlerCtxPipe
+    pub(crate) fn create_channel_outbound_ctx_pipe(mut channel_handler_pipe: ChannelOutboundHandlerPipe, event_loop: Arc<EventLoop>, channel: Arc<Mutex<Channel>>, tail_handler: Box<dyn ChannelOutboundHandler + Send + Sync>) -> ChannelOutboundHandlerCtxPipe
     {
-        // 创建ChannelHandlerPipe , 每一个连接创建自己的一套pipeline
-        let mut channel_handler_pipe: ChannelOutboundHandlerPipe = (out_channel_handler_pipe_fn)();
         // 创建ChannelHandlerCtxPipe
         let mut channel_handler_context_pipe = ChannelOutboundHandlerCtxPipe::new();
         //将handler pipeline 反序
@@ -349,7 +567,7 @@ impl Bootstrap {
         ///
         /// 添加TailHandler，追加到最后面
         ///
-        channel_handler_pipe.add_last(Box::new(TailHandler::new()));
+        channel_handler_pipe.add_last(tail_handler);
 
         for _i in 0..channel_handler_pipe.handlers.len() {
             let handler = channel_handler_pipe.handlers.remove(0);
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+///
+/// EventLoop 使用的时钟, 读空闲检测和定时任务都以它为准
+///
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+
+///
+/// 系统时钟
+///
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        chrono::Local::now().timestamp_millis() as u64
+    }
+}
+
+
+///
+/// 手动推进的时钟，用于测试
+///
+pub struct FakeClock {
+    now_ms: AtomicU64,
+}
+
+impl FakeClock {
+    pub fn new(start_ms: u64) -> FakeClock {
+        FakeClock {
+            now_ms: AtomicU64::new(start_ms),
+        }
+    }
+
+    pub fn advance_ms(&self, ms: u64) {
+        self.now_ms.fetch_add(ms, Ordering::SeqCst);
+    }
+
+    pub fn set_ms(&self, ms: u64) {
+        self.now_ms.store(ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+}
@@ -1,60 +1,229 @@
 use std::borrow::Borrow;
+use std::collections::VecDeque;
 use std::io::ErrorKind;
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
 use std::thread;
 use std::thread::Thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use bytebuf_rs::bytebuf::ByteBuf;
 use chashmap::CHashMap;
-use mio::{Events, Poll, Token};
+use mio::{Events, Poll, PollOpt, Ready, Registration, SetReadiness, Token};
 use rayon_core::ThreadPool;
 use uuid::Uuid;
 
+use crate::core::allocator::PooledByteBufAllocator;
+use crate::core::clock::{Clock, SystemClock};
+use crate::core::metrics::EventLoopMetrics;
+use crate::core::trace;
 use crate::errors::RettyErrorKind;
 use crate::handler::channel_handler_ctx_pipe::{ChannelInboundHandlerCtxPipe, ChannelOutboundHandlerCtxPipe};
 use crate::transport::channel::{Channel, InboundChannelCtx, OutboundChannelCtx};
+use crate::transport::channel_id::ChannelId;
+use crate::transport::channel_stats::EventLoopStats;
+
+///
+/// mio Token 槽位分配器, Token(0) 保留给监听器, 释放的槽位会被复用
+///
+pub(crate) struct TokenSlab {
+    next: usize,
+    free: Vec<usize>,
+}
+
+impl TokenSlab {
+    pub(crate) fn new() -> TokenSlab {
+        TokenSlab {
+            next: 1,
+            free: Vec::new(),
+        }
+    }
+
+    pub(crate) fn allocate(&mut self) -> Token {
+        match self.free.pop() {
+            Some(slot) => Token(slot),
+            None => {
+                let slot = self.next;
+                self.next += 1;
+                Token(slot)
+            }
+        }
+    }
+
+    pub(crate) fn release(&mut self, token: Token) {
+        self.free.push(token.0);
+    }
+}
+
+
+type TimerTask = Box<dyn FnOnce() + Send>;
+
+///
+/// 唤醒selector 用的Token, 不会被TokenSlab 分配到; usize::MAX 是mio 保留的, 不能注册
+///
+const WAKER_TOKEN: Token = Token(usize::MAX - 2);
+
 
 pub struct EventLoop {
+    pub(crate) name: String,
     pub(crate) excutor: Arc<ThreadPool>,
     pub(crate) selector: Arc<Poll>,
     pub(crate) channel_map: Arc<CHashMap<Token, Arc<Mutex<Channel>>>>,
     pub(crate) channel_inbound_handler_ctx_pipe_map: Arc<CHashMap<Token, ChannelInboundHandlerCtxPipe>>,
     pub(crate) stopped: Arc<AtomicBool>,
+    ///
+    /// 每个EventLoop 独占的缓冲区池
+    ///
+    pub(crate) allocator: Arc<PooledByteBufAllocator>,
+    ///
+    /// handler 通过 read() 发起的读请求, 带上ChannelId, token 被回收复用后旧的请求不会读到新的channel
+    ///
+    pub(crate) pending_reads: Arc<Mutex<Vec<(Token, ChannelId)>>>,
+    pub(crate) token_slab: Arc<Mutex<TokenSlab>>,
+    ///
+    /// 已经关闭、等待EventLoop 注销并触发 channel_inactive 的channel
+    ///
+    pub(crate) pending_closes: Arc<Mutex<Vec<(Token, ChannelId)>>>,
+    ///
+    /// 当前EventLoop 上所有channel 的累计流量
+    ///
+    pub(crate) stats: Arc<EventLoopStats>,
+    pub(crate) metrics: Arc<EventLoopMetrics>,
+    pub(crate) clock: Arc<dyn Clock>,
+    ///
+    /// schedule_delayed 提交的定时任务, (到期时间, 任务)
+    ///
+    pub(crate) timers: Arc<Mutex<Vec<(u64, TimerTask)>>>,
+    ///
+    /// 循环运行期间 execute() 提交的任务, 每轮poll 之后执行
+    ///
+    pub(crate) tasks: Arc<Mutex<VecDeque<TimerTask>>>,
+    ///
+    /// 有任务提交时唤醒阻塞在poll 上的循环
+    ///
+    _waker_registration: Registration,
+    waker: SetReadiness,
+    ///
+    /// 是否有人驱动任务队列和定时器: run() 的循环, 或者 EmbeddedChannel 手动推进
+    ///
+    pub(crate) driven: Arc<AtomicBool>,
 }
 
 
 impl EventLoop {
     pub fn new(i: usize) -> EventLoop {
+        EventLoop::new_with_clock(i, Arc::new(SystemClock))
+    }
+
+    pub fn new_with_clock(i: usize, clock: Arc<dyn Clock>) -> EventLoop {
+        let selector = Poll::new().unwrap();
+        let (waker_registration, waker) = Registration::new2();
+        selector.register(&waker_registration, WAKER_TOKEN, Ready::readable(), PollOpt::edge()).unwrap();
         EventLoop {
+            name: format!("eventloop-{}", i),
             excutor: Arc::new(rayon::ThreadPoolBuilder::new().num_threads(1).thread_name(move |_| {
                 format!("eventloop-{}", i)
             }).build().unwrap()),
-            selector: Arc::new(Poll::new().unwrap()),
+            selector: Arc::new(selector),
             channel_map: Arc::new(CHashMap::new()),
             channel_inbound_handler_ctx_pipe_map: Arc::new(CHashMap::new()),
             stopped: Arc::new(AtomicBool::new(false)),
+            allocator: Arc::new(PooledByteBufAllocator::new()),
+            pending_reads: Arc::new(Mutex::new(Vec::new())),
+            token_slab: Arc::new(Mutex::new(TokenSlab::new())),
+            pending_closes: Arc::new(Mutex::new(Vec::new())),
+            stats: Arc::new(EventLoopStats::new()),
+            metrics: Arc::new(EventLoopMetrics::new()),
+            clock,
+            timers: Arc::new(Mutex::new(Vec::new())),
+            tasks: Arc::new(Mutex::new(VecDeque::new())),
+            _waker_registration: waker_registration,
+            waker,
+            driven: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    ///
+    /// 当前EventLoop 时钟的毫秒时间戳
+    ///
+    pub fn now_ms(&self) -> u64 {
+        self.clock.now_ms()
+    }
+
+    pub fn allocator(&self) -> Arc<PooledByteBufAllocator> {
+        self.allocator.clone()
+    }
+
+    pub fn stats(&self) -> Arc<EventLoopStats> {
+        self.stats.clone()
+    }
+
+    pub fn metrics(&self) -> Arc<EventLoopMetrics> {
+        self.metrics.clone()
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    ///
+    /// 提交任务到EventLoop 的线程执行
+    ///
+    /// 循环运行时线程被poll 占着, 任务放进队列并唤醒selector, 在本轮poll 之后执行
+    ///
+    pub fn execute<F>(&self, task: F) where F: FnOnce() + Send + 'static {
+        let metrics = self.metrics.clone();
+        metrics.task_submitted();
+        let task = move || {
+            metrics.task_finished();
+            task()
+        };
+        if self.driven.load(Ordering::Relaxed) {
+            self.tasks.lock().unwrap().push_back(Box::new(task));
+            if let Err(e) = self.waker.set_readiness(Ready::readable()) {
+                log::warn!(target: trace::TARGET_EVENTLOOP, "wake up {} failed: {}", self.name, e);
+            }
+        } else {
+            self.excutor.spawn(task);
+        }
+    }
+
+    ///
+    /// 执行队列里的任务, 执行期间新提交的任务留到下一轮
+    ///
+    pub(crate) fn run_pending_tasks(&self) {
+        EventLoop::drain_tasks(&self.tasks);
+    }
+
+    fn drain_tasks(tasks: &Mutex<VecDeque<TimerTask>>) {
+        let pending: Vec<TimerTask> = tasks.lock().unwrap().drain(..).collect();
+        for task in pending {
+            task();
         }
     }
+
     pub fn shutdown(&self) {
         self.stopped.store(true, Ordering::Relaxed);
     }
 
-    pub(crate) fn attach(&self, id: usize, ch: Arc<Mutex<Channel>>, mut ctx__inbound_ctx_pipe: ChannelInboundHandlerCtxPipe) {
-        let channel = ch.clone();
-        let channel_2 = ch.clone();
-        // 一个channel注册一个selector
-        {
-            let channel = channel.lock().unwrap();
+    ///
+    /// 为新的channel 分配selector 上的槽位
+    ///
+    pub(crate) fn allocate_token(&self) -> Token {
+        self.token_slab.lock().unwrap().allocate()
+    }
+
+    pub(crate) fn attach(&self, token: Token, ch: Arc<Mutex<Channel>>, ctx__inbound_ctx_pipe: ChannelInboundHandlerCtxPipe) {
+        // 先放进map, channel_active 里关闭channel 时EventLoop 才能回收它
+        self.channel_map.insert_new(token, ch.clone());
+        self.channel_inbound_handler_ctx_pipe_map.insert_new(token, ctx__inbound_ctx_pipe.clone());
+        ctx__inbound_ctx_pipe.head_channel_active();
+        // 一个channel注册一个selector, 持有锁检查, 避免和回收channel 时的注销交错
+        let channel = ch.lock().unwrap();
+        if !channel.is_closed() {
             channel.register(&self.selector);
         }
-        {
-            ctx__inbound_ctx_pipe.head_channel_active();
-        }
-        self.channel_inbound_handler_ctx_pipe_map.insert_new(Token(id), ctx__inbound_ctx_pipe);
-        self.channel_map.insert_new(Token(id), channel_2);
     }
 
 
@@ -63,70 +232,240 @@ impl EventLoop {
         let channel_map = Arc::clone(&self.channel_map);
         let channel_inbound_ctx_pipe_map = Arc::clone(&self.channel_inbound_handler_ctx_pipe_map);
         let stopped = Arc::clone(&self.stopped);
+        let allocator = Arc::clone(&self.allocator);
+        let pending_reads = Arc::clone(&self.pending_reads);
+        let token_slab = Arc::clone(&self.token_slab);
+        let pending_closes = Arc::clone(&self.pending_closes);
+        let metrics = Arc::clone(&self.metrics);
+        let timers = Arc::clone(&self.timers);
+        let clock = Arc::clone(&self.clock);
+        let tasks = Arc::clone(&self.tasks);
+        let waker = self.waker.clone();
+        // 已经在运行
+        if self.driven.swap(true, Ordering::SeqCst) {
+            return;
+        }
 
         self.excutor.spawn(move || {
             let mut events = Events::with_capacity(1024);
             while !stopped.load(Ordering::Relaxed) {
                 selector.poll(&mut events, Some(Duration::from_millis(200))).unwrap();
+                let polled_at = Instant::now();
 
                 for e in events.iter() {
-                    let channel = match channel_map.remove(&e.token()) {
-                        Some(ch) => {
-                            let mut buf: Vec<u8> = Vec::with_capacity(65535);
-                            let ch_clone = ch.clone();
-                            let mut ch = ch.lock().unwrap();
-                            let ch_ret = match ch.read(&mut buf) {
-                                Ok(0) => {
-                                    ch.close();
-                                    None
-                                }
-                                Ok(n) => {
-                                    None
-                                }
-                                Err(e) if e.kind() == ErrorKind::WouldBlock => {
-                                    None
-                                }
-                                Err(e) => {
-                                    Some(e)
-                                }
-                            };
-                            if !ch.is_closed() {
-                                channel_map.insert_new(e.token(), ch_clone);
-                            }
-                            Some((ch.clone(), buf.clone(), ch_ret))
-                        }
-                        None => None
-                    };
-                    if let Some((ch, buf, err)) = channel {
-                        if ch.is_closed() {
-                            {
-                                let ctx_pipe = channel_inbound_ctx_pipe_map.get_mut(&e.token()).unwrap();
-                                ctx_pipe.head_channel_inactive();
-                            }
-                        }
-                        if !ch.is_closed() {
-                            if err.is_some() {
-                                let ctx_pipe = channel_inbound_ctx_pipe_map.get_mut(&e.token()).unwrap();
-                                let error: RettyErrorKind = err.unwrap().into();
-                                ctx_pipe.head_channel_exception(error);
-                            } else {
-                                let mut bytebuf = ByteBuf::new_from(&buf[..]);
-                                let ctx_pipe = channel_inbound_ctx_pipe_map.get_mut(&e.token()).unwrap();
-                                ctx_pipe.head_channel_read(&mut bytebuf);
-                            }
-
-                        }
+                    if e.token() == WAKER_TOKEN {
+                        // 边缘触发, 清掉就绪状态以便下次唤醒
+                        let _ = waker.set_readiness(Ready::empty());
+                        continue;
+                    }
+                    let readiness = e.readiness();
+                    if readiness.is_writable() {
+                        EventLoop::process_writable(e.token(), &channel_map);
+                    }
+                    if readiness.is_readable() || !readiness.is_writable() {
+                        EventLoop::process_readable(e.token(), None, &channel_map, &channel_inbound_ctx_pipe_map, &allocator, &selector, &token_slab, &metrics);
                     }
                 }
+
+                // 处理handler 主动发起的read() 请求
+                let reads: Vec<(Token, ChannelId)> = pending_reads.lock().unwrap().drain(..).collect();
+                for (token, id) in reads {
+                    EventLoop::process_readable(token, Some(id), &channel_map, &channel_inbound_ctx_pipe_map, &allocator, &selector, &token_slab, &metrics);
+                }
+                // 回收本轮关闭的channel
+                let closes: Vec<(Token, ChannelId)> = pending_closes.lock().unwrap().drain(..).collect();
+                for (token, id) in closes {
+                    EventLoop::close_channel(token, id, &channel_map, &channel_inbound_ctx_pipe_map, &selector, &token_slab, &metrics);
+                }
+                EventLoop::drain_tasks(&tasks);
+                EventLoop::fire_due_timers(&timers, clock.as_ref());
+                metrics.record_poll_latency(polled_at.elapsed());
             }
         });
     }
 
+    ///
+    /// 提交一次读请求并唤醒selector, 在本轮poll 之后执行
+    ///
+    pub(crate) fn request_read(&self, token: Token, id: ChannelId) {
+        self.pending_reads.lock().unwrap().push((token, id));
+        if let Err(e) = self.waker.set_readiness(Ready::readable()) {
+            log::warn!(target: trace::TARGET_EVENTLOOP, "wake up {} failed: {}", self.name, e);
+        }
+    }
+
+    ///
+    /// channel 关闭后请求EventLoop 注销它并触发 channel_inactive
+    ///
+    pub(crate) fn request_close(&self, token: Token, id: ChannelId) {
+        self.pending_closes.lock().unwrap().push((token, id));
+        if let Err(e) = self.waker.set_readiness(Ready::readable()) {
+            log::warn!(target: trace::TARGET_EVENTLOOP, "wake up {} failed: {}", self.name, e);
+        }
+    }
 
+
+    fn process_writable(token: Token, channel_map: &CHashMap<Token, Arc<Mutex<Channel>>>) {
+        let channel = match channel_map.get(&token) {
+            Some(ch) => ch.clone(),
+            None => return
+        };
+        let mut channel = channel.lock().unwrap();
+        channel.flush_outbound();
+    }
+
+
+    fn process_readable(token: Token,
+                        expected_id: Option<ChannelId>,
+                        channel_map: &CHashMap<Token, Arc<Mutex<Channel>>>,
+                        channel_inbound_ctx_pipe_map: &CHashMap<Token, ChannelInboundHandlerCtxPipe>,
+                        allocator: &Arc<PooledByteBufAllocator>,
+                        selector: &Poll,
+                        token_slab: &Mutex<TokenSlab>,
+                        metrics: &EventLoopMetrics) {
+        let channel = match channel_map.get(&token) {
+            Some(ch) => ch.clone(),
+            None => return
+        };
+        let (id, closed, input_shutdown, mut buf, err) = {
+            let mut ch = channel.lock().unwrap();
+            // 关闭了自动读并且没有读请求，数据留在内核缓冲区，等待恢复读;
+            // 读请求属于已经关闭的旧channel 时, 不去读复用了token 的新channel
+            if ch.is_closed() || expected_id.map_or(false, |id| id != ch.id()) || !ch.take_read_permit() {
+                return;
+            }
+            let mut buf = allocator.buffer(65535);
+            let ch_ret = match ch.read(&mut buf) {
+                Ok(0) => {
+                    // 只读到EOF, 之前写出但还在出站缓冲区的数据写完再关闭
+                    ch.close_after_flush();
+                    None
+                }
+                Ok(n) => {
+                    None
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    None
+                }
+                Err(e) => {
+                    Some(e)
+                }
+            };
+            // 只带出后面要用的状态, 不复制整个channel
+            (ch.id(), ch.is_closed(), ch.is_input_shutdown(), buf, ch_ret)
+        };
+        if closed {
+            EventLoop::close_channel(token, id, channel_map, channel_inbound_ctx_pipe_map, selector, token_slab, metrics);
+        } else if let Some(err) = err {
+            {
+                let ctx_pipe = channel_inbound_ctx_pipe_map.get_mut(&token).unwrap();
+                // 出错之前已经读到的数据先交给pipeline
+                if !buf.is_empty() {
+                    ctx_pipe.head_channel_read(&mut buf);
+                }
+                let error: RettyErrorKind = err.into();
+                ctx_pipe.head_channel_exception(error);
+            }
+            buf.release();
+            // ECONNRESET 之类的读错误之后连接已经不能用了
+            channel.lock().unwrap().close();
+        } else if buf.is_empty() {
+            // 没有数据可读(比如恢复自动读时内核缓冲区是空的), 不触发 channel_read
+            buf.release();
+        } else {
+            // 读缓冲区直接交给pipeline, 不再拷贝, 这里drop 后归还给池
+            {
+                let ctx_pipe = channel_inbound_ctx_pipe_map.get_mut(&token).unwrap();
+                ctx_pipe.head_channel_read(&mut buf);
+            }
+            buf.release();
+            if input_shutdown {
+                // 数据和FIN 在同一次读到, 边缘触发不会再有事件, 数据交给pipeline 后在这里关闭
+                channel.lock().unwrap().close_after_flush();
+            }
+        }
+    }
+
+    ///
+    /// 从selector 注销已经关闭的channel, 写失败时先触发 channel_exception, 再触发 channel_inactive
+    ///
+    fn close_channel(token: Token,
+                     id: ChannelId,
+                     channel_map: &CHashMap<Token, Arc<Mutex<Channel>>>,
+                     channel_inbound_ctx_pipe_map: &CHashMap<Token, ChannelInboundHandlerCtxPipe>,
+                     selector: &Poll,
+                     token_slab: &Mutex<TokenSlab>,
+                     metrics: &EventLoopMetrics) {
+        // token 可能已经被回收并分配给了新的channel
+        let channel = match channel_map.get(&token) {
+            Some(ch) if ch.lock().unwrap().id() == id => ch.clone(),
+            _ => return,
+        };
+        channel_map.remove(&token);
+        let write_error = {
+            let mut ch = channel.lock().unwrap();
+            ch.close();
+            // 先从selector 注销再回收token, 避免旧连接的事件落到复用了token 的新连接上
+            ch.deregister(selector);
+            ch.take_write_error()
+        };
+        if let Some(ctx_pipe) = channel_inbound_ctx_pipe_map.remove(&token) {
+            if let Some(e) = write_error {
+                ctx_pipe.head_channel_exception(e.into());
+            }
+            ctx_pipe.head_channel_inactive();
+        }
+        token_slab.lock().unwrap().release(token);
+        metrics.record_closed();
+        log::debug!(target: trace::TARGET_EVENTLOOP, "channel closed, channel_id:{}", id);
+    }
+
+
+    ///
+    /// delay_ms 之后在EventLoop 线程上执行task
+    ///
     pub fn schedule_delayed<F>(&self, task: F, delay_ms: usize)
         where F: FnOnce() + Send + 'static {
-        thread::sleep(Duration::from_millis(delay_ms as u64));
-        self.excutor.spawn(task)
+        if self.driven.load(Ordering::Relaxed) {
+            let deadline = self.now_ms() + delay_ms as u64;
+            self.timers.lock().unwrap().push((deadline, Box::new(task)));
+        } else {
+            // 没有运行中的循环驱动定时器，在EventLoop 线程上等待
+            self.execute(move || {
+                thread::sleep(Duration::from_millis(delay_ms as u64));
+                task()
+            })
+        }
+    }
+
+    ///
+    /// 执行已经到期的定时任务
+    ///
+    pub(crate) fn run_due_timers(&self) {
+        EventLoop::fire_due_timers(&self.timers, self.clock.as_ref());
+    }
+
+    fn fire_due_timers(timers: &Mutex<Vec<(u64, TimerTask)>>, clock: &dyn Clock) {
+        let now = clock.now_ms();
+        let due: Vec<TimerTask> = {
+            let mut timers = timers.lock().unwrap();
+            if timers.is_empty() {
+                return;
+            }
+            let mut due = Vec::new();
+            let mut i = 0;
+            while i < timers.len() {
+                if timers[i].0 <= now {
+                    due.push(timers.remove(i).1);
+                } else {
+                    i += 1;
+                }
+            }
+            due
+        };
+        for task in due {
+            task();
+        }
     }
 }
 
@@ -164,7 +503,7 @@ impl EventLoopGroup {
 
     pub fn execute<F>(&mut self, task: F) where F: FnOnce() + Send + 'static {
         let executor = self.next().unwrap();
-        executor.excutor.spawn(task);
+        executor.execute(task);
     }
 
     pub fn event_loop_group(&self) -> &Vec<Arc<EventLoop>> {
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::core::eventloop::EventLoop;
+
+///
+/// 单个EventLoop 的运行指标
+///
+pub struct EventLoopMetrics {
+    /// 每轮poll 之后处理事件耗费的时间(微秒)，也就是下一次poll 被推迟的时间
+    poll_latency_us_sum: AtomicU64,
+    poll_latency_us_max: AtomicU64,
+    poll_count: AtomicU64,
+    /// 提交到EventLoop 还没执行的任务数
+    pending_tasks: AtomicUsize,
+    closed_connections: AtomicU64,
+    /// pipeline 异常, 按 ErrorKind 分类
+    exceptions: Mutex<HashMap<String, u64>>,
+}
+
+impl EventLoopMetrics {
+    pub fn new() -> EventLoopMetrics {
+        EventLoopMetrics {
+            poll_latency_us_sum: AtomicU64::new(0),
+            poll_latency_us_max: AtomicU64::new(0),
+            poll_count: AtomicU64::new(0),
+            pending_tasks: AtomicUsize::new(0),
+            closed_connections: AtomicU64::new(0),
+            exceptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn record_poll_latency(&self, latency: Duration) {
+        let us = latency.as_micros() as u64;
+        self.poll_latency_us_sum.fetch_add(us, Ordering::Relaxed);
+        self.poll_count.fetch_add(1, Ordering::Relaxed);
+        let mut max = self.poll_latency_us_max.load(Ordering::Relaxed);
+        while us > max {
+            match self.poll_latency_us_max.compare_exchange_weak(max, us, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => max = current,
+            }
+        }
+    }
+
+    pub(crate) fn task_submitted(&self) {
+        self.pending_tasks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn task_finished(&self) {
+        self.pending_tasks.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_closed(&self) {
+        self.closed_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_exception(&self, kind: &str) {
+        let mut exceptions = self.exceptions.lock().unwrap();
+        *exceptions.entry(kind.to_string()).or_insert(0) += 1;
+    }
+
+    pub fn pending_tasks(&self) -> usize {
+        self.pending_tasks.load(Ordering::Relaxed)
+    }
+
+    pub fn closed_connections(&self) -> u64 {
+        self.closed_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn poll_count(&self) -> u64 {
+        self.poll_count.load(Ordering::Relaxed)
+    }
+
+    pub fn poll_latency_us_sum(&self) -> u64 {
+        self.poll_latency_us_sum.load(Ordering::Relaxed)
+    }
+
+    pub fn poll_latency_us_max(&self) -> u64 {
+        self.poll_latency_us_max.load(Ordering::Relaxed)
+    }
+
+    pub fn exceptions(&self) -> HashMap<String, u64> {
+        self.exceptions.lock().unwrap().clone()
+    }
+}
+
+
+///
+/// 进程级别的指标注册表, 由Bootstrap 创建，汇总所有worker EventLoop 的指标
+///
+pub struct MetricsRegistry {
+    accepted_connections: AtomicU64,
+    accept_errors: AtomicU64,
+    event_loops: Mutex<Vec<(String, Arc<EventLoop>)>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> MetricsRegistry {
+        MetricsRegistry {
+            accepted_connections: AtomicU64::new(0),
+            accept_errors: AtomicU64::new(0),
+            event_loops: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn register_event_loop(&self, name: String, event_loop: Arc<EventLoop>) {
+        self.event_loops.lock().unwrap().push((name, event_loop));
+    }
+
+    pub(crate) fn record_accepted(&self) {
+        self.accepted_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_accept_error(&self) {
+        self.accept_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn accepted_connections(&self) -> u64 {
+        self.accepted_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn accept_errors(&self) -> u64 {
+        self.accept_errors.load(Ordering::Relaxed)
+    }
+
+    pub fn closed_connections(&self) -> u64 {
+        self.event_loops.lock().unwrap().iter().map(|(_, e)| e.metrics.closed_connections()).sum()
+    }
+
+    pub fn active_connections(&self) -> u64 {
+        self.accepted_connections().saturating_sub(self.closed_connections())
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.event_loops.lock().unwrap().iter().map(|(_, e)| e.stats.bytes_read()).sum()
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.event_loops.lock().unwrap().iter().map(|(_, e)| e.stats.bytes_written()).sum()
+    }
+
+    pub fn exceptions(&self) -> HashMap<String, u64> {
+        let mut all = HashMap::new();
+        for (_, e) in self.event_loops.lock().unwrap().iter() {
+            for (kind, n) in e.metrics.exceptions() {
+                *all.entry(kind).or_insert(0) += n;
+            }
+        }
+        all
+    }
+
+    ///
+    /// 按 Prometheus text exposition format 输出
+    ///
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        MetricsRegistry::write_metric(&mut out, "retty_connections_accepted_total", "counter", "Accepted connections.", self.accepted_connections());
+        MetricsRegistry::write_metric(&mut out, "retty_connections_closed_total", "counter", "Closed connections.", self.closed_connections());
+        MetricsRegistry::write_metric(&mut out, "retty_connections_active", "gauge", "Currently open connections.", self.active_connections());
+        MetricsRegistry::write_metric(&mut out, "retty_accept_errors_total", "counter", "Errors returned by accept.", self.accept_errors());
+        MetricsRegistry::write_metric(&mut out, "retty_bytes_read_total", "counter", "Bytes read from sockets.", self.bytes_read());
+        MetricsRegistry::write_metric(&mut out, "retty_bytes_written_total", "counter", "Bytes written to sockets.", self.bytes_written());
+
+        let mut exceptions: Vec<(String, u64)> = self.exceptions().into_iter().collect();
+        exceptions.sort();
+        MetricsRegistry::write_header(&mut out, "retty_pipeline_exceptions_total", "counter", "Exceptions fired into pipelines by kind.");
+        for (kind, n) in exceptions {
+            writeln!(out, "retty_pipeline_exceptions_total{{kind=\"{}\"}} {}", kind, n).unwrap();
+        }
+
+        let event_loops = self.event_loops.lock().unwrap();
+        MetricsRegistry::write_header(&mut out, "retty_eventloop_poll_latency_seconds", "summary", "Time spent handling events after each poll.");
+        for (name, e) in event_loops.iter() {
+            writeln!(out, "retty_eventloop_poll_latency_seconds_sum{{eventloop=\"{}\"}} {}", name, e.metrics.poll_latency_us_sum() as f64 / 1_000_000f64).unwrap();
+            writeln!(out, "retty_eventloop_poll_latency_seconds_count{{eventloop=\"{}\"}} {}", name, e.metrics.poll_count()).unwrap();
+        }
+        MetricsRegistry::write_header(&mut out, "retty_eventloop_poll_latency_max_seconds", "gauge", "Longest time spent handling events after a poll.");
+        for (name, e) in event_loops.iter() {
+            writeln!(out, "retty_eventloop_poll_latency_max_seconds{{eventloop=\"{}\"}} {}", name, e.metrics.poll_latency_us_max() as f64 / 1_000_000f64).unwrap();
+        }
+        MetricsRegistry::write_header(&mut out, "retty_eventloop_pending_tasks", "gauge", "Tasks submitted to the event loop and not yet run.");
+        for (name, e) in event_loops.iter() {
+            writeln!(out, "retty_eventloop_pending_tasks{{eventloop=\"{}\"}} {}", name, e.metrics.pending_tasks()).unwrap();
+        }
+        out
+    }
+
+    fn write_header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+        writeln!(out, "# HELP {} {}", name, help).unwrap();
+        writeln!(out, "# TYPE {} {}", name, metric_type).unwrap();
+    }
+
+    fn write_metric(out: &mut String, name: &str, metric_type: &str, help: &str, value: u64) {
+        MetricsRegistry::write_header(out, name, metric_type, help);
+        writeln!(out, "{} {}", name, value).unwrap();
+    }
+}
@@ -1,2 +1,6 @@
 pub mod bootstrap;
 pub mod eventloop;
+pub mod allocator;
+pub mod metrics;
+pub mod trace;
+pub mod clock;
@@ -0,0 +1,72 @@
+use std::net::SocketAddr;
+
+use crate::transport::channel_id::ChannelId;
+
+///
+/// 日志 target, 方便在日志管道里按模块过滤
+///
+pub const TARGET_BOOTSTRAP: &str = "retty::bootstrap";
+pub const TARGET_EVENTLOOP: &str = "retty::eventloop";
+pub const TARGET_CHANNEL: &str = "retty::channel";
+pub const TARGET_PIPELINE: &str = "retty::pipeline";
+pub const TARGET_EMBEDDED: &str = "retty::embedded";
+pub const TARGET_LOGGING: &str = "retty::logging_handler";
+
+
+///
+/// channel 级别的span, 打开 `tracing` feature 之后携带 channel_id 和 remote_addr
+///
+#[cfg(feature = "tracing")]
+pub type ChannelSpan = tracing::Span;
+
+#[cfg(not(feature = "tracing"))]
+#[derive(Clone)]
+pub struct ChannelSpan;
+
+///
+/// 进入span 之后返回的guard, drop 时退出
+///
+#[cfg(feature = "tracing")]
+pub type SpanGuard = tracing::span::EnteredSpan;
+
+#[cfg(not(feature = "tracing"))]
+pub struct SpanGuard;
+
+
+#[cfg(feature = "tracing")]
+pub(crate) fn channel_span(id: ChannelId, remote_addr: Option<SocketAddr>) -> ChannelSpan {
+    match remote_addr {
+        Some(addr) => tracing::debug_span!(target: "retty::channel", "channel", channel_id = %id, remote_addr = %addr),
+        None => tracing::debug_span!(target: "retty::channel", "channel", channel_id = %id),
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn channel_span(_id: ChannelId, _remote_addr: Option<SocketAddr>) -> ChannelSpan {
+    ChannelSpan
+}
+
+
+#[cfg(feature = "tracing")]
+pub(crate) fn enter_channel_span(span: &ChannelSpan) -> SpanGuard {
+    span.clone().entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn enter_channel_span(_span: &ChannelSpan) -> SpanGuard {
+    SpanGuard
+}
+
+
+///
+/// 每次调用handler 的span, 嵌套在channel span 里
+///
+#[cfg(feature = "tracing")]
+pub(crate) fn enter_handler_span(handler_id: &str, event: &'static str) -> SpanGuard {
+    tracing::trace_span!(target: "retty::pipeline", "handler", handler = handler_id, event = event).entered()
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn enter_handler_span(_handler_id: &str, _event: &'static str) -> SpanGuard {
+    SpanGuard
+}
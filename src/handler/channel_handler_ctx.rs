@@ -1,10 +1,14 @@
 use std::any::Any;
+use std::cell::Cell;
 use std::ops::Deref;
 use std::sync::{Arc, Mutex};
 
 use rayon_core::ThreadPool;
 
+use crate::core::allocator::PooledBuffer;
 use crate::core::eventloop::EventLoop;
+use crate::core::trace;
+use crate::core::trace::ChannelSpan;
 use crate::errors::RettyErrorKind;
 use crate::handler::channel_handler_ctx_pipe::{ChannelInboundHandlerCtxPipe, ChannelOutboundHandlerCtxPipe};
 use crate::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
@@ -14,6 +18,27 @@ use crate::transport::channel::{Channel, InboundChannelCtx, OutboundChannelCtx};
 一个handlerctx 对应一个handler
  **/
 
+thread_local! {
+    ///
+    /// 当前线程正在分发的 channel_exception 层数
+    ///
+    static EXCEPTION_DEPTH: Cell<usize> = Cell::new(0);
+}
+
+///
+/// 分发异常, 新产生的异常计入EventLoop 指标, handler 在 channel_exception 里往后传的不重复计数
+///
+pub(crate) fn dispatch_exception<F: FnOnce(RettyErrorKind)>(eventloop: &EventLoop, error: RettyErrorKind, f: F) {
+    EXCEPTION_DEPTH.with(|depth| {
+        if depth.get() == 0 {
+            eventloop.metrics.record_exception(&format!("{:?}", error.kind));
+        }
+        depth.set(depth.get() + 1);
+    });
+    f(error);
+    EXCEPTION_DEPTH.with(|depth| depth.set(depth.get() - 1));
+}
+
 
 pub struct ChannelInboundHandlerCtx {
     pub(crate) id: String,
@@ -32,6 +57,8 @@ pub struct ChannelInboundHandlerCtx {
     /// 持有ChannelOutboundHandlerCtxPipe,用于写数据
     ///
     pub(crate) outbound_context_pipe: Option<Arc<Mutex<ChannelOutboundHandlerCtxPipe>>>,
+
+    pub(crate) span: ChannelSpan,
 }
 
 impl ChannelInboundHandlerCtx {
@@ -41,6 +68,7 @@ impl ChannelInboundHandlerCtx {
                handler: Arc<Mutex<Box<dyn ChannelInboundHandler + Send + Sync>>>,
                outbound_context_pipe: Option<Arc<Mutex<ChannelOutboundHandlerCtxPipe>>>,
     ) -> ChannelInboundHandlerCtx {
+        let span = channel.lock().unwrap().span();
         ChannelInboundHandlerCtx {
             id,
             eventloop,
@@ -51,7 +79,8 @@ impl ChannelInboundHandlerCtx {
             next_handler: None,
             head_ctx: None,
             head_handler: None,
-            outbound_context_pipe
+            outbound_context_pipe,
+            span,
         }
     }
 
@@ -67,6 +96,7 @@ impl ChannelInboundHandlerCtx {
             let next_handler_arc = self.next_handler.as_ref().unwrap();
             let mut next_handler = next_handler_arc.lock().unwrap();
             let mut next_ctx_clone_ref = next_ctx_clone.lock().unwrap();
+            let _span = trace::enter_handler_span(&next_ctx_clone_ref.id, "channel_active");
             next_handler.channel_active(&mut *next_ctx_clone_ref)
         }
     }
@@ -78,6 +108,7 @@ impl ChannelInboundHandlerCtx {
             let next_handler_arc = self.next_handler.as_ref().unwrap();
             let mut next_handler = next_handler_arc.lock().unwrap();
             let mut next_ctx_clone_ref = next_ctx_clone.lock().unwrap();
+            let _span = trace::enter_handler_span(&next_ctx_clone_ref.id, "channel_inactive");
             next_handler.channel_inactive(&mut *next_ctx_clone_ref)
         }
     }
@@ -89,6 +120,7 @@ impl ChannelInboundHandlerCtx {
             let next_handler_arc = self.next_handler.as_ref().unwrap();
             let mut next_handler = next_handler_arc.lock().unwrap();
             let mut next_ctx_clone_ref = next_ctx_clone.lock().unwrap();
+            let _span = trace::enter_handler_span(&next_ctx_clone_ref.id, "channel_read");
             next_handler.channel_read(&mut *next_ctx_clone_ref, message)
         }
     }
@@ -101,7 +133,11 @@ impl ChannelInboundHandlerCtx {
             let next_handler_arc = self.next_handler.as_ref().unwrap();
             let mut next_handler = next_handler_arc.lock().unwrap();
             let mut next_ctx_clone_ref = next_ctx_clone.lock().unwrap();
-            next_handler.channel_exception(&mut *next_ctx_clone_ref, error)
+            let _span = trace::enter_handler_span(&next_ctx_clone_ref.id, "channel_exception");
+            dispatch_exception(&self.eventloop, error, |error| next_handler.channel_exception(&mut *next_ctx_clone_ref, error))
+        } else {
+            // 最后一个handler 产生的异常也要计数
+            dispatch_exception(&self.eventloop, error, |_| {})
         }
     }
 
@@ -121,7 +157,7 @@ impl ChannelInboundHandlerCtx {
             let pipe = pipe_arc.lock().unwrap();
             pipe.head_channel_write(message);
         } else {
-            println!("self.outbound_context_pipe is None");
+            log::warn!(target: trace::TARGET_PIPELINE, "outbound_context_pipe is None, message dropped, handler:{}", self.id);
         }
     }
 
@@ -137,6 +173,13 @@ impl ChannelInboundHandlerCtx {
     pub fn event_loop(&mut self) -> Arc<EventLoop> {
         self.eventloop.clone()
     }
+
+    ///
+    /// 从当前EventLoop 的内存池申请缓冲区
+    ///
+    pub fn alloc_buffer(&mut self, capacity: usize) -> PooledBuffer {
+        self.eventloop.allocator.buffer(capacity)
+    }
 }
 
 
@@ -158,6 +201,7 @@ pub struct ChannelOutboundHandlerCtx {
     pub(crate) head_handler: Option<Arc<Mutex<Box<dyn ChannelOutboundHandler + Send + Sync>>>>,
     pub(crate) next_handler: Option<Arc<Mutex<Box<dyn ChannelOutboundHandler + Send + Sync>>>>,
 
+    pub(crate) span: ChannelSpan,
 }
 
 impl ChannelOutboundHandlerCtx {
@@ -166,6 +210,7 @@ impl ChannelOutboundHandlerCtx {
                channel: Arc<Mutex<Channel>>,
                handler: Arc<Mutex<Box<dyn ChannelOutboundHandler + Send + Sync>>>,
     ) -> ChannelOutboundHandlerCtx {
+        let span = channel.lock().unwrap().span();
         ChannelOutboundHandlerCtx {
             id,
             eventloop,
@@ -176,6 +221,7 @@ impl ChannelOutboundHandlerCtx {
             next_handler: None,
             head_ctx: None,
             head_handler: None,
+            span,
         }
     }
 
@@ -189,6 +235,7 @@ impl ChannelOutboundHandlerCtx {
             let next_handler_arc = self.next_handler.as_ref().unwrap();
             let mut next_handler = next_handler_arc.lock().unwrap();
             let mut next_ctx_clone_ref = next_ctx_clone.lock().unwrap();
+            let _span = trace::enter_handler_span(&next_ctx_clone_ref.id, "channel_write");
             next_handler.channel_write(&mut *next_ctx_clone_ref, message)
         }
     }
@@ -205,4 +252,11 @@ impl ChannelOutboundHandlerCtx {
     pub fn event_loop(&mut self) -> Arc<EventLoop> {
         self.eventloop.clone()
     }
+
+    ///
+    /// 从当前EventLoop 的内存池申请缓冲区
+    ///
+    pub fn alloc_buffer(&mut self, capacity: usize) -> PooledBuffer {
+        self.eventloop.allocator.buffer(capacity)
+    }
 }
\ No newline at end of file
@@ -2,8 +2,9 @@ use std::any::Any;
 use std::ops::DerefMut;
 use std::sync::{Arc, Mutex, MutexGuard};
 
+use crate::core::trace;
 use crate::errors::RettyErrorKind;
-use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
+use crate::handler::channel_handler_ctx::{dispatch_exception, ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
 use crate::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
 use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
 
@@ -33,6 +34,8 @@ impl ChannelInboundHandlerCtxPipe {
         let head_handler_clone = self.header_handler().clone();
         let mut head_handler = head_handler_clone.lock().unwrap();
         let mut ctx_head_ref = ctx_head.lock().unwrap();
+        let _channel_span = trace::enter_channel_span(&ctx_head_ref.span);
+        let _handler_span = trace::enter_handler_span(&ctx_head_ref.id, "channel_read");
         head_handler.channel_read(&mut ctx_head_ref, msg);
     }
 
@@ -42,6 +45,8 @@ impl ChannelInboundHandlerCtxPipe {
         let head_handler_clone = pipe.header_handler().clone();
         let mut head_handler = head_handler_clone.lock().unwrap();
         let mut ctx_head_ref = ctx_head.lock().unwrap();
+        let _channel_span = trace::enter_channel_span(&ctx_head_ref.span);
+        let _handler_span = trace::enter_handler_span(&ctx_head_ref.id, "channel_active");
         head_handler.channel_active(&mut *ctx_head_ref);
     }
 
@@ -52,7 +57,11 @@ impl ChannelInboundHandlerCtxPipe {
         let head_handler_clone = pipe.header_handler().clone();
         let mut head_handler = head_handler_clone.lock().unwrap();
         let mut ctx_head_ref = ctx_head.lock().unwrap();
-        head_handler.channel_exception(&mut *ctx_head_ref, error);
+        let _channel_span = trace::enter_channel_span(&ctx_head_ref.span);
+        let _handler_span = trace::enter_handler_span(&ctx_head_ref.id, "channel_exception");
+        log::debug!(target: trace::TARGET_PIPELINE, "channel exception: {}", error);
+        let eventloop = ctx_head_ref.eventloop.clone();
+        dispatch_exception(&eventloop, error, |error| head_handler.channel_exception(&mut *ctx_head_ref, error));
     }
 
 
@@ -62,6 +71,8 @@ impl ChannelInboundHandlerCtxPipe {
         let head_handler_clone = pipe.header_handler().clone();
         let mut head_handler = head_handler_clone.lock().unwrap();
         let mut ctx_head_ref = ctx_head.lock().unwrap();
+        let _channel_span = trace::enter_channel_span(&ctx_head_ref.span);
+        let _handler_span = trace::enter_handler_span(&ctx_head_ref.id, "channel_inactive");
         head_handler.channel_inactive(&mut *ctx_head_ref);
     }
 
@@ -101,6 +112,8 @@ impl ChannelOutboundHandlerCtxPipe {
         let head_handler_clone = pipe.header_handler().clone();
         let mut head_handler = head_handler_clone.lock().unwrap();
         let mut ctx_head_ref = ctx_head.lock().unwrap();
+        let _channel_span = trace::enter_channel_span(&ctx_head_ref.span);
+        let _handler_span = trace::enter_handler_span(&ctx_head_ref.id, "channel_write");
         head_handler.channel_write(&mut *ctx_head_ref, msg);
     }
 
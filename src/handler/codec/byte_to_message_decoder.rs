@@ -0,0 +1,226 @@
+use std::any::Any;
+use std::io::ErrorKind;
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::core::allocator::PooledBuffer;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::handler::ChannelInboundHandler;
+use crate::transport::message;
+
+///
+/// 把字节流解码成消息, 累积缓冲区由 ByteToMessageDecoderHandler 管理
+///
+/// decode 从buf 的读指针开始读取, 数据不够一条消息时不要移动读指针, 直接返回
+///
+/// ```ignore
+/// struct IntDecoder;
+///
+/// impl ByteToMessageDecoder for IntDecoder {
+///     fn id(&self) -> String { "IntDecoder".to_string() }
+///
+///     fn decode(&mut self, ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+///         if buf.readable_bytes() >= 4 {
+///             out.push(Box::new(buf.read_u32_be()));
+///         }
+///     }
+/// }
+///
+/// handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(IntDecoder)));
+/// ```
+///
+pub trait ByteToMessageDecoder {
+    fn id(&self) -> String;
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>);
+
+    ///
+    /// 直接在累积缓冲区上解码, 返回消费的字节数, 数据不够一条消息时返回 Some(0)
+    ///
+    /// 默认返回None, 这时每次读会把累积的字节拷贝进一个ByteBuf 再调用 decode;
+    /// 一条消息要跨很多次读才能收全时(大帧), 实现这个方法可以避免重复拷贝
+    ///
+    fn decode_bytes(&mut self, _channel_handler_ctx: &mut ChannelInboundHandlerCtx, _bytes: &[u8], _out: &mut Vec<Box<dyn Any>>) -> Option<usize> {
+        None
+    }
+
+    ///
+    /// 返回true 之后不再解码, 剩下的字节和之后收到的消息原样传给下一个handler, 比如连接升级到其它协议之后;
+    /// 每次解码之前检查
+    ///
+    fn is_pass_through(&mut self) -> bool {
+        false
+    }
+
+    ///
+    /// channel 关闭时处理剩下的字节(可能为空), 默认有剩下的字节时再调用一次 decode
+    ///
+    fn decode_last(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        if buf.readable_bytes() > 0 {
+            self.decode(channel_handler_ctx, buf, out);
+        }
+    }
+}
+
+
+///
+/// 把 ByteToMessageDecoder 适配成入站handler
+///
+/// 收到的ByteBuf 或 PooledBuffer 追加到累积缓冲区, 循环调用 decode 直到没有进展, 解出的消息依次传给下一个handler,
+/// 然后丢弃已经消费的字节; 其它类型的消息原样传递; channel_inactive 时用 decode_last 处理剩下的字节
+///
+/// 没有半包时直接解码收到的字节, 只有剩下的半包才放进累积缓冲区; 解码器切换到原样传递之后不再拷贝收到的消息
+///
+pub struct ByteToMessageDecoderHandler<D: ByteToMessageDecoder> {
+    decoder: D,
+    ///
+    /// 累积缓冲区，从EventLoop 的内存池借出，消费完后归还
+    ///
+    cumulation: Option<PooledBuffer>,
+}
+
+impl<D: ByteToMessageDecoder> ByteToMessageDecoderHandler<D> {
+    pub fn new(decoder: D) -> Self {
+        ByteToMessageDecoderHandler {
+            decoder,
+            cumulation: None,
+        }
+    }
+
+    pub fn decoder(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+
+    ///
+    /// 循环解码, 返回消费的字节数, channel 被关闭时返回None
+    ///
+    fn call_decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, bytes: &[u8]) -> Option<usize> {
+        let mut out: Vec<Box<dyn Any>> = Vec::new();
+        let mut reader_index = 0;
+        // 解码器不支持原地解码时退回到ByteBuf, 每次读只拷贝一次
+        let mut buf: Option<ByteBuf> = None;
+        loop {
+            if self.decoder.is_pass_through() {
+                break;
+            }
+            let consumed = match buf.as_mut() {
+                None => match self.decoder.decode_bytes(channel_handler_ctx, &bytes[reader_index..], &mut out) {
+                    Some(consumed) => consumed,
+                    None => {
+                        buf = Some(ByteBuf::new_from(&bytes[reader_index..]));
+                        continue;
+                    }
+                },
+                Some(buf) => {
+                    let old_reader_index = buf.get_reader_index();
+                    self.decoder.decode(channel_handler_ctx, buf, &mut out);
+                    buf.get_reader_index() - old_reader_index
+                }
+            };
+            reader_index += consumed;
+            let decoded = out.len();
+            for mut message in out.drain(..) {
+                if channel_handler_ctx.channel().is_closing() {
+                    // handler 关闭了连接, 剩下的消息和数据不再处理
+                    return None;
+                }
+                channel_handler_ctx.fire_channel_read(&mut *message);
+            }
+            if channel_handler_ctx.channel().is_closing() {
+                return None;
+            }
+            if consumed == 0 {
+                if decoded > 0 {
+                    let err = RettyErrorKind::new(ErrorKind::InvalidData,
+                                                  format!("{}.decode() did not read anything but decoded a message", self.decoder.id()));
+                    channel_handler_ctx.fire_channel_exception(err);
+                }
+                break;
+            }
+            if reader_index == bytes.len() {
+                break;
+            }
+        }
+        Some(reader_index)
+    }
+}
+
+impl<D: ByteToMessageDecoder> ChannelInboundHandler for ByteToMessageDecoderHandler<D> {
+    fn id(&self) -> String {
+        self.decoder.id()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        let mut buf = match self.cumulation.take() {
+            Some(cumulation) => ByteBuf::new_from(&cumulation[..]),
+            None => ByteBuf::new_with_capacity(0),
+        };
+        let mut out: Vec<Box<dyn Any>> = Vec::new();
+        self.decoder.decode_last(channel_handler_ctx, &mut buf, &mut out);
+        for mut message in out.drain(..) {
+            channel_handler_ctx.fire_channel_read(&mut *message);
+        }
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if self.decoder.is_pass_through() {
+            // 收到的消息原样传递, 不拷贝
+            if let Some(mut cumulation) = self.cumulation.take() {
+                channel_handler_ctx.fire_channel_read(&mut cumulation);
+            }
+            channel_handler_ctx.fire_channel_read(message);
+            return;
+        }
+        let bytes = match message::as_bytes(message) {
+            Some(bytes) => bytes,
+            None => {
+                // 不是字节的消息原样传递
+                channel_handler_ctx.fire_channel_read(message);
+                return;
+            }
+        };
+        match self.cumulation.take() {
+            None => {
+                let consumed = match self.call_decode(channel_handler_ctx, bytes) {
+                    Some(consumed) => consumed,
+                    None => return,
+                };
+                if consumed < bytes.len() {
+                    // 只把半包放进累积缓冲区
+                    let mut cumulation = channel_handler_ctx.alloc_buffer(bytes.len() - consumed);
+                    cumulation.extend_from_slice(&bytes[consumed..]);
+                    self.cumulation = Some(cumulation);
+                }
+            }
+            Some(mut cumulation) => {
+                cumulation.extend_from_slice(bytes);
+                let consumed = match self.call_decode(channel_handler_ctx, &cumulation[..]) {
+                    Some(consumed) => consumed,
+                    None => return,
+                };
+                if consumed < cumulation.len() {
+                    // 丢弃已经消费的字节，只保留半包
+                    cumulation.discard_read_bytes(consumed);
+                    self.cumulation = Some(cumulation);
+                }
+                // 全部消费完, cumulation 在这里drop 归还给内存池
+            }
+        }
+        if self.decoder.is_pass_through() {
+            // 解码中途切换成原样传递(比如升级请求后面紧跟着新协议的数据), 剩下的字节马上传给下一个handler
+            if let Some(mut cumulation) = self.cumulation.take() {
+                channel_handler_ctx.fire_channel_read(&mut cumulation);
+            }
+        }
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
@@ -0,0 +1,125 @@
+use std::io::ErrorKind;
+
+use encoding_rs::{Encoding, GB18030, GBK, UTF_16BE, UTF_16LE};
+
+use crate::errors::RettyErrorKind;
+
+///
+/// StringDecoder / StringEncoder 支持的字符集
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Charset {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Gbk,
+    Gb18030,
+    ///
+    /// ISO-8859-1, 每个字节对应 U+0000..U+00FF
+    ///
+    Latin1,
+}
+
+///
+/// 遇到非法字节或者无法编码的字符时的处理方式
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CodingErrorAction {
+    ///
+    /// 报错
+    ///
+    Strict,
+    ///
+    /// 解码时替换为 U+FFFD, 编码时替换为 `?`
+    ///
+    Replace,
+}
+
+impl Charset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Utf8 => "UTF-8",
+            Charset::Utf16Le => "UTF-16LE",
+            Charset::Utf16Be => "UTF-16BE",
+            Charset::Gbk => "GBK",
+            Charset::Gb18030 => "GB18030",
+            Charset::Latin1 => "ISO-8859-1",
+        }
+    }
+
+    fn encoding(&self) -> Option<&'static Encoding> {
+        match self {
+            Charset::Utf16Le => Some(UTF_16LE),
+            Charset::Utf16Be => Some(UTF_16BE),
+            Charset::Gbk => Some(GBK),
+            Charset::Gb18030 => Some(GB18030),
+            Charset::Utf8 | Charset::Latin1 => None,
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8], action: CodingErrorAction) -> Result<String, RettyErrorKind> {
+        match self {
+            Charset::Utf8 => match action {
+                CodingErrorAction::Strict => String::from_utf8(bytes.to_vec()).map_err(|e| self.malformed(e.utf8_error().valid_up_to())),
+                CodingErrorAction::Replace => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            },
+            Charset::Latin1 => Ok(bytes.iter().map(|b| *b as char).collect()),
+            _ => {
+                let encoding = self.encoding().unwrap();
+                match action {
+                    CodingErrorAction::Strict => encoding.decode_without_bom_handling_and_without_replacement(bytes)
+                        .map(|s| s.into_owned())
+                        .ok_or_else(|| RettyErrorKind::new(ErrorKind::InvalidData, format!("MalformedInput: invalid {} sequence", self.name()))),
+                    CodingErrorAction::Replace => Ok(encoding.decode_without_bom_handling(bytes).0.into_owned()),
+                }
+            }
+        }
+    }
+
+    pub fn encode(&self, s: &str, action: CodingErrorAction) -> Result<Vec<u8>, RettyErrorKind> {
+        match self {
+            Charset::Utf8 => Ok(s.as_bytes().to_vec()),
+            // encoding_rs 不支持输出UTF-16, 自己编码
+            Charset::Utf16Le => Ok(s.encode_utf16().flat_map(|u| u.to_le_bytes().to_vec()).collect()),
+            Charset::Utf16Be => Ok(s.encode_utf16().flat_map(|u| u.to_be_bytes().to_vec()).collect()),
+            Charset::Latin1 => {
+                let mut out = Vec::with_capacity(s.len());
+                for c in s.chars() {
+                    match c as u32 {
+                        n if n <= 0xff => out.push(n as u8),
+                        _ if action == CodingErrorAction::Replace => out.push(b'?'),
+                        _ => return Err(self.unmappable(c)),
+                    }
+                }
+                Ok(out)
+            }
+            _ => {
+                let encoding = self.encoding().unwrap();
+                let (bytes, _, unmappable) = encoding.encode(s);
+                if !unmappable {
+                    return Ok(bytes.into_owned());
+                }
+                // encoding_rs 会把无法编码的字符替换成 HTML 数字实体, 逐个字符重新编码
+                let mut out = Vec::with_capacity(s.len());
+                let mut tmp = [0u8; 4];
+                for c in s.chars() {
+                    let (bytes, _, unmappable) = encoding.encode(c.encode_utf8(&mut tmp));
+                    match unmappable {
+                        false => out.extend_from_slice(&bytes),
+                        true if action == CodingErrorAction::Replace => out.push(b'?'),
+                        true => return Err(self.unmappable(c)),
+                    }
+                }
+                Ok(out)
+            }
+        }
+    }
+
+    fn malformed(&self, valid_up_to: usize) -> RettyErrorKind {
+        RettyErrorKind::new(ErrorKind::InvalidData, format!("MalformedInput: invalid {} sequence at {}", self.name(), valid_up_to))
+    }
+
+    fn unmappable(&self, c: char) -> RettyErrorKind {
+        RettyErrorKind::new(ErrorKind::InvalidData, format!("UnmappableCharacter: {:?} (U+{:04X}) can not be encoded in {}", c, c as u32, self.name()))
+    }
+}
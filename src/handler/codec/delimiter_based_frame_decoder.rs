@@ -0,0 +1,104 @@
+use crate::errors::RettyErrorKind;
+use crate::handler::codec::frame::{self, FrameDecode};
+
+///
+/// 按自定义分隔符拆帧的解码器, 有多个分隔符时取最先出现的一个
+///
+/// 一帧超过 max_frame_length 时丢弃到下一个分隔符并触发 `InvalidData` 异常,
+/// fail_fast 为true(默认) 时发现超长立即触发, 否则丢弃完整帧之后再触发
+///
+/// ```ignore
+/// handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(DelimiterBasedFrameDecoder::new(8192, vec![b"$_".to_vec()]))));
+/// ```
+///
+pub struct DelimiterBasedFrameDecoder {
+    max_frame_length: usize,
+    delimiters: Vec<Vec<u8>>,
+    strip_delimiter: bool,
+    fail_fast: bool,
+    ///
+    /// 正在丢弃超长帧
+    ///
+    discarding_too_long_frame: bool,
+    too_long_frame_length: usize,
+}
+
+impl DelimiterBasedFrameDecoder {
+    pub fn new(max_frame_length: usize, delimiters: Vec<Vec<u8>>) -> Self {
+        assert!(max_frame_length > 0, "max_frame_length must be a positive integer: {}", max_frame_length);
+        assert!(!delimiters.is_empty(), "empty delimiters");
+        assert!(delimiters.iter().all(|d| !d.is_empty()), "empty delimiter");
+        DelimiterBasedFrameDecoder {
+            max_frame_length,
+            delimiters,
+            strip_delimiter: true,
+            fail_fast: true,
+            discarding_too_long_frame: false,
+            too_long_frame_length: 0,
+        }
+    }
+
+    ///
+    /// 是否去掉帧尾的分隔符, 默认去掉
+    ///
+    pub fn strip_delimiter(mut self, strip_delimiter: bool) -> Self {
+        self.strip_delimiter = strip_delimiter;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    ///
+    /// 返回最先出现的分隔符的 (位置, 长度)
+    ///
+    fn find_delimiter(&self, bytes: &[u8]) -> Option<(usize, usize)> {
+        self.delimiters.iter()
+            .filter_map(|d| bytes.windows(d.len()).position(|w| w == &d[..]).map(|i| (i, d.len())))
+            .min_by_key(|(i, _)| *i)
+    }
+}
+
+impl FrameDecode for DelimiterBasedFrameDecoder {
+    fn id(&self) -> String {
+        return "DelimiterBasedFrameDecoder".to_string();
+    }
+
+    fn decode_frame(&mut self, bytes: &[u8], errors: &mut Vec<RettyErrorKind>) -> Option<(usize, Option<(usize, usize)>)> {
+        match self.find_delimiter(bytes) {
+            Some((frame_length, delimiter_length)) => {
+                if self.discarding_too_long_frame {
+                    // 超长帧丢弃完, 恢复正常解码
+                    let too_long_frame_length = self.too_long_frame_length + frame_length;
+                    self.discarding_too_long_frame = false;
+                    self.too_long_frame_length = 0;
+                    if !self.fail_fast {
+                        errors.push(frame::too_long_frame(too_long_frame_length as u64, self.max_frame_length));
+                    }
+                    return Some((frame_length + delimiter_length, None));
+                }
+                if frame_length > self.max_frame_length {
+                    errors.push(frame::too_long_frame(frame_length as u64, self.max_frame_length));
+                    return Some((frame_length + delimiter_length, None));
+                }
+                let end = if self.strip_delimiter { frame_length } else { frame_length + delimiter_length };
+                Some((frame_length + delimiter_length, Some((0, end))))
+            }
+            None if self.discarding_too_long_frame && !bytes.is_empty() => {
+                self.too_long_frame_length += bytes.len();
+                Some((bytes.len(), None))
+            }
+            None if !self.discarding_too_long_frame && bytes.len() > self.max_frame_length => {
+                self.too_long_frame_length = bytes.len();
+                self.discarding_too_long_frame = true;
+                if self.fail_fast {
+                    errors.push(frame::too_long_frame(self.too_long_frame_length as u64, self.max_frame_length));
+                }
+                Some((bytes.len(), None))
+            }
+            None => None,
+        }
+    }
+}
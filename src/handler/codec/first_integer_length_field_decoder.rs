@@ -1,79 +1,51 @@
 use std::any::Any;
-use std::io::ErrorKind;
-use std::sync::{Arc, Mutex};
-use std::sync::atomic::Ordering;
-
-use bytebuf_rs::bytebuf::{ByteBuf, slice_util};
 
 use crate::errors::RettyErrorKind;
 use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoderHandler;
+use crate::handler::codec::length_field_based_frame_decoder::LengthFieldBasedFrameDecoder;
 use crate::handler::handler::ChannelInboundHandler;
 
 ///
-/// 第一个字段为长度字段的解码器
+/// 第一个字段为长度字段的解码器, 长度是包含自身的大端 u32, 传给下一个handler 的帧保留长度字段
+///
+/// 等同于 `LengthFieldBasedFrameDecoder::new(8 * 1024 * 1024, 0, 4, -4, 0)`, 帧最大 8MB, 超过时触发 channel_exception
 ///
+#[deprecated(note = "use ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(8 * 1024 * 1024, 0, 4, -4, 0)); frames are limited to 8 MB")]
 pub struct FirstIntegerLengthFieldDecoder {
-    all_buf: Vec<u8>,
+    handler: ByteToMessageDecoderHandler<LengthFieldBasedFrameDecoder>,
 }
 
 
+#[allow(deprecated)]
 impl FirstIntegerLengthFieldDecoder {
     pub fn new() -> Self {
         FirstIntegerLengthFieldDecoder {
-            all_buf: Vec::<u8>::new()
+            handler: ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(8 * 1024 * 1024, 0, 4, -4, 0)),
         }
     }
 }
 
 
+#[allow(deprecated)]
 impl ChannelInboundHandler for FirstIntegerLengthFieldDecoder {
     fn id(&self) -> String {
         return "FirstIntegerLengthFieldDecoder".to_string();
     }
 
     fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
-        channel_handler_ctx.fire_channel_active();
+        self.handler.channel_active(channel_handler_ctx);
     }
 
     fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
-        channel_handler_ctx.fire_channel_inactive();
+        self.handler.channel_inactive(channel_handler_ctx);
     }
 
     fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
-        let buf_option = message.downcast_ref::<ByteBuf>();
-        if buf_option.is_some() {
-            let buf = buf_option.unwrap();
-            let n = buf.len();
-            if self.all_buf.len() != 0 {
-                self.all_buf = slice_util::append::<u8>(&self.all_buf, &buf[..n]);
-            } else {
-                self.all_buf = buf[..n].to_owned();
-            }
-            let mut bytebuf = ByteBuf::new_from(&self.all_buf[..]);
-            //处理粘包
-            loop {
-                if bytebuf.readable_bytes() < std::mem::size_of::<u32>() {
-                    return;
-                }
-                bytebuf.mark_reader_index();
-                let pkt_len = bytebuf.read_u32_be();
-                bytebuf.reset_reader_index();
-                if (bytebuf.readable_bytes() as u32) < pkt_len {
-                    return;
-                }
-                channel_handler_ctx.fire_channel_read(&mut bytebuf);
-                if bytebuf.get_reader_index() == bytebuf.get_writer_index() {
-                    self.all_buf = Vec::<u8>::new();
-                    return;
-                }
-            }
-        } else {
-            let err = RettyErrorKind::new(ErrorKind::Other, String::from("decoding error"));
-            channel_handler_ctx.fire_channel_exception(err);
-        }
+        self.handler.channel_read(channel_handler_ctx, message);
     }
 
     fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
-        channel_handler_ctx.fire_channel_exception(error);
+        self.handler.channel_exception(channel_handler_ctx, error);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,35 @@
+use crate::errors::RettyErrorKind;
+use crate::handler::codec::frame::FrameDecode;
+
+///
+/// 按固定长度拆帧的解码器
+///
+/// ```ignore
+/// handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(FixedLengthFrameDecoder::new(128))));
+/// ```
+///
+pub struct FixedLengthFrameDecoder {
+    frame_length: usize,
+}
+
+impl FixedLengthFrameDecoder {
+    pub fn new(frame_length: usize) -> Self {
+        assert!(frame_length > 0, "frame_length must be a positive integer: {}", frame_length);
+        FixedLengthFrameDecoder {
+            frame_length,
+        }
+    }
+}
+
+impl FrameDecode for FixedLengthFrameDecoder {
+    fn id(&self) -> String {
+        return "FixedLengthFrameDecoder".to_string();
+    }
+
+    fn decode_frame(&mut self, bytes: &[u8], _errors: &mut Vec<RettyErrorKind>) -> Option<(usize, Option<(usize, usize)>)> {
+        if bytes.len() < self.frame_length {
+            return None;
+        }
+        Some((self.frame_length, Some((0, self.frame_length))))
+    }
+}
@@ -0,0 +1,70 @@
+use std::any::Any;
+use std::io::ErrorKind;
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoder;
+
+///
+/// 拆帧规则, 由各个帧解码器实现, 实现了它的解码器自动实现 ByteToMessageDecoder
+///
+pub(crate) trait FrameDecode {
+    fn id(&self) -> String;
+
+    ///
+    /// 从bytes 头部解出一帧, 返回 (消费的字节数, 帧数据在bytes 里的区间), 数据不够时返回None
+    ///
+    /// 只丢弃数据不产生帧时区间为None, 需要报告的错误放进errors
+    ///
+    fn decode_frame(&mut self, bytes: &[u8], errors: &mut Vec<RettyErrorKind>) -> Option<(usize, Option<(usize, usize)>)>;
+}
+
+
+///
+/// 从bytes 头部解出一帧, 帧以单独的ByteBuf 放进out, 返回消费的字节数
+///
+/// 帧解码器的 ByteToMessageDecoder::decode_bytes 直接在累积缓冲区上拆帧
+///
+pub(crate) fn decode_frame<D: FrameDecode>(decoder: &mut D,
+                                           channel_handler_ctx: &mut ChannelInboundHandlerCtx,
+                                           bytes: &[u8],
+                                           out: &mut Vec<Box<dyn Any>>) -> usize {
+    let mut errors = Vec::new();
+    let decoded = decoder.decode_frame(bytes, &mut errors);
+    for error in errors {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+    match decoded {
+        Some((consumed, frame)) => {
+            if let Some((from, to)) = frame {
+                out.push(Box::new(ByteBuf::new_from(&bytes[from..to])));
+            }
+            consumed
+        }
+        None => 0,
+    }
+}
+
+
+impl<D: FrameDecode> ByteToMessageDecoder for D {
+    fn id(&self) -> String {
+        FrameDecode::id(self)
+    }
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        let consumed = decode_frame(self, channel_handler_ctx, buf.available_bytes(), out);
+        buf.skip_index(consumed);
+    }
+
+    fn decode_bytes(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, bytes: &[u8], out: &mut Vec<Box<dyn Any>>) -> Option<usize> {
+        Some(decode_frame(self, channel_handler_ctx, bytes, out))
+    }
+}
+
+
+pub(crate) fn too_long_frame(frame_length: u64, max_frame_length: usize) -> RettyErrorKind {
+    RettyErrorKind::new(ErrorKind::InvalidData,
+                        format!("TooLongFrame: frame length ({}) exceeds the allowed maximum ({}) - discarded", frame_length, max_frame_length))
+}
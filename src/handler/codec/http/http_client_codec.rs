@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoderHandler;
+use crate::handler::codec::http::http_request_encoder::HttpRequestEncoder;
+use crate::handler::codec::http::http_response_decoder::HttpResponseDecoder;
+use crate::handler::combined_channel_duplex_handler::CombinedChannelDuplexHandler;
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+
+///
+/// HttpRequestEncoder 和 HttpResponseDecoder 的组合
+///
+/// 按顺序记录发出的请求方法, HEAD 请求的响应不读消息体, 1xx 响应不对应请求;
+/// 连接在响应完整之前关闭时触发 `UnexpectedEof` 异常
+///
+/// ```ignore
+/// bootstrap.initialize_pipeline(|inbound, outbound| {
+///     HttpClientCodec::new().add_last(inbound, outbound);
+///     inbound.add_last(Box::new(HttpObjectAggregator::new(1024 * 1024)));
+///     inbound.add_last(Box::new(ResponseHandler::new()));
+/// });
+/// ```
+///
+pub struct HttpClientCodec {
+    decoder: HttpResponseDecoder,
+}
+
+impl HttpClientCodec {
+    pub fn new() -> Self {
+        HttpClientCodec {
+            decoder: HttpResponseDecoder::new(),
+        }
+    }
+
+    pub fn max_initial_line_length(mut self, max_initial_line_length: usize) -> Self {
+        self.decoder = self.decoder.max_initial_line_length(max_initial_line_length);
+        self
+    }
+
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        self.decoder = self.decoder.max_header_size(max_header_size);
+        self
+    }
+
+    pub fn max_header_count(mut self, max_header_count: usize) -> Self {
+        self.decoder = self.decoder.max_header_count(max_header_count);
+        self
+    }
+
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.decoder = self.decoder.max_chunk_size(max_chunk_size);
+        self
+    }
+
+    pub fn into_handler(self) -> CombinedChannelDuplexHandler {
+        let pending_methods = Arc::new(Mutex::new(VecDeque::new()));
+        let mut decoder = self.decoder;
+        decoder.pending_methods = Some(pending_methods.clone());
+        let mut encoder = HttpRequestEncoder::new();
+        encoder.pending_methods = Some(pending_methods);
+        CombinedChannelDuplexHandler::new("HttpClientCodec", Box::new(ByteToMessageDecoderHandler::new(decoder)), Box::new(encoder))
+    }
+
+    pub fn add_last(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_last(inbound_pipe, outbound_pipe);
+    }
+
+    pub fn add_first(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_first(inbound_pipe, outbound_pipe);
+    }
+}
@@ -0,0 +1,325 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crossbeam::channel::{bounded, RecvTimeoutError, Sender};
+
+use crate::core::bootstrap::Bootstrap;
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::http::http_client_codec::HttpClientCodec;
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpMessage, HttpResponseStatus, HttpVersion};
+use crate::handler::codec::http::http_object_aggregator::HttpObjectAggregator;
+use crate::handler::handler::ChannelInboundHandler;
+use crate::transport::attribute::AttributeKey;
+use crate::transport::socket::SocketChannel;
+
+type ResponseSender = Sender<Result<FullHttpResponse, RettyErrorKind>>;
+
+///
+/// 等待响应的请求, 收到响应、异常或连接关闭时取出
+///
+const RESPONSE_SENDER: AttributeKey<ResponseSender> = AttributeKey::new("HttpConnectionPoolResponse");
+
+
+///
+/// 一个 host:port 的连接
+///
+#[derive(Default)]
+struct HostConnections {
+    idle: Vec<SocketChannel>,
+    ///
+    /// 正在使用和正在建立的连接数
+    ///
+    leased: usize,
+}
+
+
+///
+/// 按 host:port 复用 keep-alive 连接的HTTP 客户端
+///
+/// ```ignore
+/// let pool = HttpConnectionPool::new().max_connections_per_host(16).max_idle_per_host(4);
+/// let request = FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/users/1", Vec::new());
+/// let response = pool.request("10.0.0.8", 8080, request)?;
+/// ```
+///
+/// 每个连接同一时间只发送一个请求; 没有空闲连接时新建连接, 达到 max_connections_per_host 后等待其它请求归还连接;
+/// 请求和响应都保持连接时连接放回空闲队列, 空闲连接超过 max_idle_per_host 时关闭, 空闲期间被对端关闭的连接在取出时丢弃
+///
+pub struct HttpConnectionPool {
+    max_connections_per_host: usize,
+    max_idle_per_host: usize,
+    max_content_length: usize,
+    request_timeout_ms: u64,
+    connect_timeout_ms: usize,
+    worker_group_size: usize,
+    ///
+    /// 第一次连接时创建
+    ///
+    bootstrap: Mutex<Option<Bootstrap>>,
+    hosts: Mutex<HashMap<String, HostConnections>>,
+    released: Condvar,
+}
+
+impl HttpConnectionPool {
+    ///
+    /// 默认每个host 最多8 个连接、4 个空闲连接, 响应体最大 10MB, 请求超时30 秒
+    ///
+    pub fn new() -> Self {
+        HttpConnectionPool {
+            max_connections_per_host: 8,
+            max_idle_per_host: 4,
+            max_content_length: 10 * 1024 * 1024,
+            request_timeout_ms: 30000,
+            connect_timeout_ms: 30000,
+            worker_group_size: 1,
+            bootstrap: Mutex::new(None),
+            hosts: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    pub fn max_connections_per_host(mut self, max_connections_per_host: usize) -> Self {
+        assert!(max_connections_per_host > 0, "max_connections_per_host must be a positive integer: {}", max_connections_per_host);
+        self.max_connections_per_host = max_connections_per_host;
+        self
+    }
+
+    pub fn max_idle_per_host(mut self, max_idle_per_host: usize) -> Self {
+        self.max_idle_per_host = max_idle_per_host;
+        self
+    }
+
+    pub fn max_content_length(mut self, max_content_length: usize) -> Self {
+        self.max_content_length = max_content_length;
+        self
+    }
+
+    ///
+    /// 从等待连接开始到收到完整响应的超时时间
+    ///
+    pub fn request_timeout_ms(mut self, request_timeout_ms: u64) -> Self {
+        self.request_timeout_ms = request_timeout_ms;
+        self
+    }
+
+    pub fn connect_timeout_ms(mut self, connect_timeout_ms: usize) -> Self {
+        self.connect_timeout_ms = connect_timeout_ms;
+        self
+    }
+
+    pub fn worker_group(mut self, n: usize) -> Self {
+        self.worker_group_size = n;
+        self
+    }
+
+    ///
+    /// 发送请求并阻塞到收到完整响应, 请求没有 Host 时使用 host:port
+    ///
+    pub fn request(&self, host: &str, port: u16, mut request: FullHttpRequest) -> Result<FullHttpResponse, RettyErrorKind> {
+        let deadline = Instant::now() + Duration::from_millis(self.request_timeout_ms);
+        let key = format!("{}:{}", host, port);
+        if !request.headers().contains(names::HOST) {
+            request.headers_mut().set(names::HOST, &key);
+        }
+        if !request.is_transfer_encoding_chunked() && request.content_length().is_none() && !request.content().is_empty() {
+            let length = request.content().len() as u64;
+            request.set_content_length(length);
+        }
+        let request_keep_alive = request.is_keep_alive();
+        let (channel, receiver) = loop {
+            let channel = self.acquire(&key, host, port, deadline)?;
+            let (sender, receiver) = bounded(1);
+            channel.channel().attr(&RESPONSE_SENDER).set(sender);
+            // 连接可能在取出之后、设置sender 之前被关闭, 这时 channel_inactive 已经触发过, 不会再完成sender;
+            // 请求还没写出, 换一个连接重试
+            if channel.is_active() || channel.channel().attr(&RESPONSE_SENDER).remove().is_none() {
+                break (channel, receiver);
+            }
+            self.release(&key, channel, false);
+        };
+        channel.write_and_flush(request);
+
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(Ok(response)) => {
+                let keep_alive = request_keep_alive && response.is_keep_alive();
+                self.release(&key, channel, keep_alive);
+                Ok(response)
+            }
+            Ok(Err(e)) => {
+                self.release(&key, channel, false);
+                Err(e)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                self.release(&key, channel, false);
+                Err(RettyErrorKind::new(ErrorKind::TimedOut, format!("RequestTimeout: no response from {} in {}ms", key, self.request_timeout_ms)))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                self.release(&key, channel, false);
+                Err(RettyErrorKind::new(ErrorKind::ConnectionAborted, format!("connection to {} closed before the response", key)))
+            }
+        }
+    }
+
+    ///
+    /// host:port 的空闲连接数
+    ///
+    pub fn idle_count(&self, host: &str, port: u16) -> usize {
+        let hosts = self.hosts.lock().unwrap();
+        hosts.get(&format!("{}:{}", host, port)).map_or(0, |h| h.idle.iter().filter(|c| c.is_active()).count())
+    }
+
+    ///
+    /// host:port 正在使用的连接数
+    ///
+    pub fn leased_count(&self, host: &str, port: u16) -> usize {
+        let hosts = self.hosts.lock().unwrap();
+        hosts.get(&format!("{}:{}", host, port)).map_or(0, |h| h.leased)
+    }
+
+    ///
+    /// 关闭所有空闲连接并停止EventLoop, 正在进行的请求会失败
+    ///
+    pub fn close(&self) {
+        let mut hosts = self.hosts.lock().unwrap();
+        for (_, host) in hosts.drain() {
+            host.idle.iter().for_each(|c| c.close());
+        }
+        if let Some(bootstrap) = self.bootstrap.lock().unwrap().as_mut() {
+            bootstrap.terminate();
+        }
+    }
+
+    fn acquire(&self, key: &str, host: &str, port: u16, deadline: Instant) -> Result<SocketChannel, RettyErrorKind> {
+        let mut hosts = self.hosts.lock().unwrap();
+        loop {
+            let connections = hosts.entry(key.to_owned()).or_default();
+            while let Some(channel) = connections.idle.pop() {
+                if channel.is_active() {
+                    connections.leased += 1;
+                    return Ok(channel);
+                }
+            }
+            if connections.leased < self.max_connections_per_host {
+                connections.leased += 1;
+                break;
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout == Duration::from_millis(0) {
+                return Err(RettyErrorKind::new(ErrorKind::TimedOut, format!("RequestTimeout: no connection to {} available in {}ms", key, self.request_timeout_ms)));
+            }
+            hosts = self.released.wait_timeout(hosts, timeout).unwrap().0;
+        }
+        drop(hosts);
+        // 连接可能很慢, 不持有锁
+        let connected = self.connect(host, port);
+        if connected.is_err() {
+            self.release_slot(key);
+        }
+        connected
+    }
+
+    fn connect(&self, host: &str, port: u16) -> Result<SocketChannel, RettyErrorKind> {
+        let (event_loop, opts, pipeline_fn) = {
+            let mut bootstrap = self.bootstrap.lock().unwrap();
+            let max_content_length = self.max_content_length;
+            let bootstrap = bootstrap.get_or_insert_with(|| {
+                let mut bootstrap = Bootstrap::new_client_bootstrap();
+                bootstrap.worker_group(self.worker_group_size)
+                    .opt_connect_timeout_ms(self.connect_timeout_ms)
+                    .opt_nodelay(true)
+                    .initialize_pipeline(move |inbound, outbound| {
+                        HttpClientCodec::new().add_last(inbound, outbound);
+                        inbound.add_last(Box::new(HttpObjectAggregator::new(max_content_length)));
+                        inbound.add_last(Box::new(PooledResponseHandler {}));
+                    });
+                bootstrap
+            });
+            bootstrap.prepare_connect()
+        };
+        crate::transport::socket::connect(host, port, opts, event_loop, pipeline_fn)
+    }
+
+    fn release(&self, key: &str, channel: SocketChannel, keep_alive: bool) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(connections) = hosts.get_mut(key) {
+            connections.leased = connections.leased.saturating_sub(1);
+            if keep_alive && channel.is_active() && connections.idle.len() < self.max_idle_per_host {
+                connections.idle.push(channel);
+            } else {
+                channel.close();
+            }
+        } else {
+            // 连接池已经关闭
+            channel.close();
+        }
+        self.released.notify_all();
+    }
+
+    fn release_slot(&self, key: &str) {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(connections) = hosts.get_mut(key) {
+            connections.leased = connections.leased.saturating_sub(1);
+        }
+        self.released.notify_all();
+    }
+}
+
+
+///
+/// 把聚合后的响应交给等待的请求
+///
+struct PooledResponseHandler {}
+
+impl PooledResponseHandler {
+    fn complete(channel_handler_ctx: &mut ChannelInboundHandlerCtx, result: Result<FullHttpResponse, RettyErrorKind>) {
+        match channel_handler_ctx.channel().attr(&RESPONSE_SENDER).remove() {
+            Some(sender) => {
+                let _ = sender.send(result);
+            }
+            None => {
+                if let Err(e) = result {
+                    log::debug!(target: trace::TARGET_PIPELINE, "HttpConnectionPool error without pending request, channel_id:{}, error:{:?}", channel_handler_ctx.channel().id(), e);
+                }
+            }
+        }
+    }
+}
+
+impl ChannelInboundHandler for PooledResponseHandler {
+    fn id(&self) -> String {
+        return "PooledResponseHandler".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        let closed = RettyErrorKind::new(ErrorKind::ConnectionAborted, String::from("connection closed before the response"));
+        PooledResponseHandler::complete(channel_handler_ctx, Err(closed));
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(response) = message.downcast_mut::<FullHttpResponse>() {
+            // 1xx 之后还有最终响应
+            if response.status().is_informational() {
+                return;
+            }
+            let response = std::mem::replace(response, FullHttpResponse::new(HttpVersion::Http11, HttpResponseStatus::OK, Vec::new()));
+            PooledResponseHandler::complete(channel_handler_ctx, Ok(response));
+        }
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        PooledResponseHandler::complete(channel_handler_ctx, Err(error));
+        channel_handler_ctx.channel().close();
+    }
+}
@@ -0,0 +1,251 @@
+use std::io::{self, ErrorKind, Write};
+
+use flate2::Compression;
+use flate2::write::{GzDecoder, GzEncoder, ZlibDecoder, ZlibEncoder};
+
+///
+/// brotli 内部缓冲区大小
+///
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+///
+/// brotli 窗口大小, 与命令行工具的默认值相同
+///
+const BROTLI_LG_WINDOW: u32 = 22;
+
+///
+/// 支持的 `Content-Encoding`
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ContentCoding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+impl ContentCoding {
+    ///
+    /// 不认识的编码返回None, `x-gzip` 和 `x-deflate` 当作 gzip 和 deflate
+    ///
+    pub fn parse(name: &str) -> Option<ContentCoding> {
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip") {
+            Some(ContentCoding::Gzip)
+        } else if name.eq_ignore_ascii_case("deflate") || name.eq_ignore_ascii_case("x-deflate") {
+            Some(ContentCoding::Deflate)
+        } else if name.eq_ignore_ascii_case("br") {
+            Some(ContentCoding::Brotli)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+            ContentCoding::Brotli => "br",
+        }
+    }
+
+    ///
+    /// 按 `Accept-Encoding` 的q 值选择编码, q 值相同时依次优先 br、gzip、deflate
+    ///
+    pub fn negotiate(accept_encoding: &str) -> Option<ContentCoding> {
+        let mut wildcard = None;
+        let mut explicit: Vec<(ContentCoding, f32)> = Vec::new();
+        for part in accept_encoding.split(',') {
+            let mut params = part.split(';');
+            let name = params.next().unwrap_or("").trim();
+            let mut q = 1.0f32;
+            for param in params {
+                let param = param.trim();
+                if param.len() > 2 && param[..2].eq_ignore_ascii_case("q=") {
+                    q = param[2..].trim().parse().unwrap_or(0.0);
+                }
+            }
+            if name == "*" {
+                wildcard = Some(q);
+            } else if let Some(coding) = ContentCoding::parse(name) {
+                explicit.push((coding, q));
+            }
+        }
+        let mut selected: Option<(ContentCoding, f32)> = None;
+        for coding in [ContentCoding::Brotli, ContentCoding::Gzip, ContentCoding::Deflate].iter() {
+            let q = explicit.iter().find(|(c, _)| c == coding).map(|(_, q)| *q).or(wildcard).unwrap_or(0.0);
+            if q > 0.0 && selected.map_or(true, |(_, best)| q > best) {
+                selected = Some((*coding, q));
+            }
+        }
+        selected.map(|(coding, _)| coding)
+    }
+}
+
+
+///
+/// 流式压缩, 每段内容压缩后立即 flush, 不需要缓存整个消息体
+///
+pub(crate) enum ContentEncoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+    Brotli(Box<brotli::CompressorWriter<Vec<u8>>>),
+}
+
+impl ContentEncoder {
+    ///
+    /// level 是压缩级别 0-9, brotli 直接用作 quality (brotli 的 quality 范围是0-11)
+    ///
+    pub(crate) fn new(coding: ContentCoding, level: u32) -> ContentEncoder {
+        match coding {
+            ContentCoding::Gzip => ContentEncoder::Gzip(GzEncoder::new(Vec::new(), Compression::new(level))),
+            ContentCoding::Deflate => ContentEncoder::Deflate(ZlibEncoder::new(Vec::new(), Compression::new(level))),
+            ContentCoding::Brotli => ContentEncoder::Brotli(Box::new(brotli::CompressorWriter::new(Vec::new(), BROTLI_BUFFER_SIZE, level, BROTLI_LG_WINDOW))),
+        }
+    }
+
+    ///
+    /// 压缩一段内容, 返回这次产生的压缩数据
+    ///
+    pub(crate) fn encode(&mut self, content: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            ContentEncoder::Gzip(e) => {
+                e.write_all(content)?;
+                e.flush()?;
+                Ok(std::mem::take(e.get_mut()))
+            }
+            ContentEncoder::Deflate(e) => {
+                e.write_all(content)?;
+                e.flush()?;
+                Ok(std::mem::take(e.get_mut()))
+            }
+            ContentEncoder::Brotli(e) => {
+                e.write_all(content)?;
+                e.flush()?;
+                Ok(std::mem::take(e.get_mut()))
+            }
+        }
+    }
+
+    ///
+    /// 结束压缩流, 返回剩下的数据
+    ///
+    pub(crate) fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            ContentEncoder::Gzip(e) => e.finish(),
+            ContentEncoder::Deflate(e) => e.finish(),
+            ContentEncoder::Brotli(e) => Ok(e.into_inner()),
+        }
+    }
+}
+
+
+///
+/// 解压输出, 整个消息体解压后超过 max 字节时写入失败, 解压器随即停止, 不会先把数据全部解压出来
+///
+pub(crate) struct DecodedContent {
+    data: Vec<u8>,
+    written: usize,
+    max: usize,
+    exceeded: bool,
+}
+
+impl DecodedContent {
+    fn new(max: usize) -> DecodedContent {
+        DecodedContent {
+            data: Vec::new(),
+            written: 0,
+            max,
+            exceeded: false,
+        }
+    }
+}
+
+impl Write for DecodedContent {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.len() > self.max - self.written {
+            self.exceeded = true;
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("content is larger than {} bytes after decompression", self.max)));
+        }
+        self.written += buf.len();
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+
+///
+/// 流式解压
+///
+pub(crate) enum ContentDecoder {
+    Gzip(GzDecoder<DecodedContent>),
+    Deflate(ZlibDecoder<DecodedContent>),
+    Brotli(Box<brotli::DecompressorWriter<DecodedContent>>),
+}
+
+impl ContentDecoder {
+    ///
+    /// max_size 是整个消息体解压后的最大长度
+    ///
+    pub(crate) fn new(coding: ContentCoding, max_size: usize) -> ContentDecoder {
+        match coding {
+            ContentCoding::Gzip => ContentDecoder::Gzip(GzDecoder::new(DecodedContent::new(max_size))),
+            ContentCoding::Deflate => ContentDecoder::Deflate(ZlibDecoder::new(DecodedContent::new(max_size))),
+            ContentCoding::Brotli => ContentDecoder::Brotli(Box::new(brotli::DecompressorWriter::new(DecodedContent::new(max_size), BROTLI_BUFFER_SIZE))),
+        }
+    }
+
+    ///
+    /// 解压一段内容, 返回这次解压出来的数据
+    ///
+    pub(crate) fn decode(&mut self, content: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            ContentDecoder::Gzip(d) => {
+                d.write_all(content)?;
+                Ok(std::mem::take(&mut d.get_mut().data))
+            }
+            ContentDecoder::Deflate(d) => {
+                d.write_all(content)?;
+                Ok(std::mem::take(&mut d.get_mut().data))
+            }
+            ContentDecoder::Brotli(d) => {
+                d.write_all(content)?;
+                Ok(std::mem::take(&mut d.get_mut().data))
+            }
+        }
+    }
+
+    ///
+    /// 解压后的长度超过了 max_size
+    ///
+    pub(crate) fn is_too_large(&self) -> bool {
+        match self {
+            ContentDecoder::Gzip(d) => d.get_ref().exceeded,
+            ContentDecoder::Deflate(d) => d.get_ref().exceeded,
+            ContentDecoder::Brotli(d) => d.get_ref().exceeded,
+        }
+    }
+
+    ///
+    /// 消息体结束, 返回剩下的数据, 压缩流不完整时返回错误
+    ///
+    pub(crate) fn finish(&mut self) -> io::Result<Vec<u8>> {
+        match self {
+            ContentDecoder::Gzip(d) => {
+                d.try_finish()?;
+                Ok(std::mem::take(&mut d.get_mut().data))
+            }
+            ContentDecoder::Deflate(d) => {
+                d.try_finish()?;
+                Ok(std::mem::take(&mut d.get_mut().data))
+            }
+            ContentDecoder::Brotli(d) => {
+                d.close().map_err(|_| io::Error::new(ErrorKind::UnexpectedEof, "brotli stream is truncated"))?;
+                Ok(std::mem::take(&mut d.get_mut().data))
+            }
+        }
+    }
+}
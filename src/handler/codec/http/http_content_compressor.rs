@@ -0,0 +1,259 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
+use crate::handler::codec::http::http_content_coding::{ContentCoding, ContentEncoder};
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpContent, HttpMessage, HttpMethod, HttpRequest, HttpResponse, HttpVersion, LastHttpContent};
+use crate::handler::combined_channel_duplex_handler::CombinedChannelDuplexHandler;
+use crate::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+use crate::transport::file_region::FileRegion;
+
+///
+/// 压缩 FileRegion 时每次读取的字节数
+///
+const FILE_REGION_CHUNK_SIZE: usize = 64 * 1024;
+
+///
+/// 请求的 Accept-Encoding, 以及是不是HEAD 请求
+///
+type AcceptEncodings = Arc<Mutex<VecDeque<(Option<String>, bool)>>>;
+
+///
+/// 根据请求的 `Accept-Encoding` 用 br/gzip/deflate 压缩响应的消息体, 加在 HttpServerCodec 后面:
+///
+/// ```ignore
+/// HttpServerCodec::new().add_last(inbound, outbound);
+/// HttpContentCompressor::new().content_size_threshold(1024).add_last(inbound, outbound);
+/// ```
+///
+/// `Content-Length` 小于阈值的响应、已经有 `Content-Encoding` 的响应和没有消息体的响应不压缩;
+/// 分段的响应边收边压缩, 去掉 `Content-Length` 改用chunked, 消息体中的 `FileRegion` 按64KB 分段读出来压缩
+///
+pub struct HttpContentCompressor {
+    compression_level: u32,
+    content_size_threshold: u64,
+}
+
+impl HttpContentCompressor {
+    pub fn new() -> Self {
+        HttpContentCompressor {
+            compression_level: 6,
+            content_size_threshold: 1024,
+        }
+    }
+
+    ///
+    /// 压缩级别, 0-9, 超过9 按9 处理; brotli 把它直接用作 quality
+    ///
+    pub fn compression_level(mut self, level: u32) -> Self {
+        self.compression_level = level.min(9);
+        self
+    }
+
+    ///
+    /// 已知长度小于这个值的消息体不压缩
+    ///
+    pub fn content_size_threshold(mut self, threshold: u64) -> Self {
+        self.content_size_threshold = threshold;
+        self
+    }
+
+    pub fn into_handler(self) -> CombinedChannelDuplexHandler {
+        let accept_encodings: AcceptEncodings = Arc::new(Mutex::new(VecDeque::new()));
+        let recorder = AcceptEncodingRecorder {
+            accept_encodings: accept_encodings.clone(),
+        };
+        let encoder = HttpContentEncoder {
+            compression_level: self.compression_level,
+            content_size_threshold: self.content_size_threshold,
+            accept_encodings,
+            encoder: None,
+        };
+        CombinedChannelDuplexHandler::new("HttpContentCompressor", Box::new(recorder), Box::new(encoder))
+    }
+
+    pub fn add_last(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_last(inbound_pipe, outbound_pipe);
+    }
+
+    pub fn add_first(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_first(inbound_pipe, outbound_pipe);
+    }
+}
+
+
+///
+/// 记录每个请求的 Accept-Encoding, 响应按顺序取出
+///
+struct AcceptEncodingRecorder {
+    accept_encodings: AcceptEncodings,
+}
+
+impl AcceptEncodingRecorder {
+    fn record<M: HttpMessage>(&self, request: &M, method: &HttpMethod) {
+        let accept_encoding = request.headers().get(names::ACCEPT_ENCODING).map(|v| v.to_owned());
+        self.accept_encodings.lock().unwrap().push_back((accept_encoding, *method == HttpMethod::Head));
+    }
+}
+
+impl ChannelInboundHandler for AcceptEncodingRecorder {
+    fn id(&self) -> String {
+        return "HttpContentCompressor".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(request) = message.downcast_ref::<HttpRequest>() {
+            self.record(request, request.method());
+        } else if let Some(request) = message.downcast_ref::<FullHttpRequest>() {
+            self.record(request, request.method());
+        }
+        channel_handler_ctx.fire_channel_read(message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
+
+
+struct HttpContentEncoder {
+    compression_level: u32,
+    content_size_threshold: u64,
+    accept_encodings: AcceptEncodings,
+    ///
+    /// 当前响应正在压缩
+    ///
+    encoder: Option<ContentEncoder>,
+}
+
+impl HttpContentEncoder {
+    ///
+    /// 决定响应使用的编码, 不压缩时返回None; 1xx 响应不对应请求
+    ///
+    fn select_coding(&mut self, response: &HttpResponse, content_length: Option<u64>) -> Option<ContentCoding> {
+        let code = response.status().code();
+        if response.status().is_informational() {
+            return None;
+        }
+        let (accept_encoding, head) = self.accept_encodings.lock().unwrap().pop_front()?;
+        if head || code == 204 || code == 304 {
+            return None;
+        }
+        if response.headers().get(names::CONTENT_ENCODING).map_or(false, |e| !e.trim().eq_ignore_ascii_case("identity")) {
+            return None;
+        }
+        if content_length.map_or(false, |len| len < self.content_size_threshold) {
+            return None;
+        }
+        ContentCoding::negotiate(accept_encoding.as_deref()?)
+    }
+
+    fn encode(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, content: &[u8]) {
+        let encoded = match self.encoder.as_mut().map(|e| e.encode(content)) {
+            Some(Ok(encoded)) => encoded,
+            Some(Err(e)) => {
+                log::warn!(target: trace::TARGET_PIPELINE, "HttpContentCompressor compress failed, channel_id:{}, error:{}", channel_handler_ctx.channel().id(), e);
+                return;
+            }
+            None => return,
+        };
+        if !encoded.is_empty() {
+            channel_handler_ctx.fire_channel_write(&mut HttpContent::new(encoded));
+        }
+    }
+
+    fn finish(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, last: &LastHttpContent) {
+        self.encode(channel_handler_ctx, last.content());
+        let rest = match self.encoder.take().map(|e| e.finish()) {
+            Some(Ok(rest)) => rest,
+            Some(Err(e)) => {
+                log::warn!(target: trace::TARGET_PIPELINE, "HttpContentCompressor compress failed, channel_id:{}, error:{}", channel_handler_ctx.channel().id(), e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        let mut encoded_last = LastHttpContent::new(rest);
+        *encoded_last.trailing_headers_mut() = last.trailing_headers().clone();
+        channel_handler_ctx.fire_channel_write(&mut encoded_last);
+    }
+}
+
+fn set_content_encoding(response: &mut HttpResponse, coding: ContentCoding) {
+    response.headers_mut().set(names::CONTENT_ENCODING, coding.as_str());
+    if !response.headers().contains_value(names::VARY, names::ACCEPT_ENCODING) {
+        response.headers_mut().add(names::VARY, names::ACCEPT_ENCODING);
+    }
+}
+
+impl ChannelOutboundHandler for HttpContentEncoder {
+    fn id(&self) -> String {
+        return "HttpContentCompressor".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(response) = message.downcast_mut::<HttpResponse>() {
+            self.encoder = None;
+            let content_length = response.content_length();
+            if let Some(coding) = self.select_coding(response, content_length) {
+                set_content_encoding(response, coding);
+                // 压缩后的长度未知
+                response.headers_mut().remove(names::CONTENT_LENGTH);
+                if response.version() == HttpVersion::Http11 {
+                    response.set_transfer_encoding_chunked(true);
+                }
+                self.encoder = Some(ContentEncoder::new(coding, self.compression_level));
+            }
+            channel_handler_ctx.fire_channel_write(message);
+        } else if let Some(response) = message.downcast_mut::<FullHttpResponse>() {
+            self.encoder = None;
+            let content_length = response.content().len() as u64;
+            if let Some(coding) = self.select_coding(response.head(), Some(content_length)) {
+                let mut encoder = ContentEncoder::new(coding, self.compression_level);
+                let encoded = encoder.encode(response.content()).and_then(|mut encoded| {
+                    encoded.extend(encoder.finish()?);
+                    Ok(encoded)
+                });
+                match encoded {
+                    Ok(encoded) => {
+                        set_content_encoding(response.head_mut(), coding);
+                        response.set_content_length(encoded.len() as u64);
+                        response.set_content(encoded);
+                    }
+                    Err(e) => log::warn!(target: trace::TARGET_PIPELINE, "HttpContentCompressor compress failed, channel_id:{}, error:{}", channel_handler_ctx.channel().id(), e),
+                }
+            }
+            channel_handler_ctx.fire_channel_write(message);
+        } else if self.encoder.is_none() {
+            channel_handler_ctx.fire_channel_write(message);
+        } else if let Some(content) = message.downcast_ref::<HttpContent>() {
+            self.encode(channel_handler_ctx, content.content());
+        } else if let Some(last) = message.downcast_ref::<LastHttpContent>() {
+            self.finish(channel_handler_ctx, last);
+        } else if let Some(region) = message.downcast_ref::<FileRegion>() {
+            // 压缩时没法零拷贝, 分段读出来压缩, 不把整个文件读进内存
+            match region.read_remaining_chunks(FILE_REGION_CHUNK_SIZE, |chunk| self.encode(channel_handler_ctx, chunk)) {
+                Ok(()) => {}
+                Err(e) => {
+                    // 响应已经写了一半, 只能关闭连接
+                    log::warn!(target: trace::TARGET_PIPELINE, "HttpContentCompressor read file region failed, channel_id:{}, error:{}", channel_handler_ctx.channel().id(), e);
+                    channel_handler_ctx.channel().close();
+                }
+            }
+        } else {
+            channel_handler_ctx.fire_channel_write(message);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::any::Any;
+use std::io::ErrorKind;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::http::http_content_coding::{ContentCoding, ContentDecoder};
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpContent, HttpMessage, HttpRequest, HttpResponse, HttpResponseStatus, HttpVersion, LastHttpContent};
+use crate::handler::handler::ChannelInboundHandler;
+
+///
+/// 按 `Content-Encoding` 解压 gzip/deflate/br 的消息体, 放在 HttpServerCodec 后面、HttpObjectAggregator 前面
+///
+/// 边收边解压, 解压后去掉 `Content-Encoding` 和 `Content-Length`, HTTP/1.1 的消息改成chunked;
+/// 请求和响应都可以处理, 解压失败时触发 InvalidData 异常并丢弃这个消息剩下的内容
+///
+/// 消息体解压后超过 max_content_length 时停止解压, 请求响应 `413 Request Entity Too Large`,
+/// 响应触发 InvalidData 异常, 之后丢弃这个消息剩下的内容
+///
+pub struct HttpContentDecompressor {
+    max_content_length: usize,
+    decoder: Option<ContentDecoder>,
+    ///
+    /// 正在解压的是请求时保存是否保持连接, 超过长度限制时响应413
+    ///
+    request_keep_alive: Option<bool>,
+    ///
+    /// 解压失败, 丢弃到 LastHttpContent
+    ///
+    discarding: bool,
+}
+
+impl HttpContentDecompressor {
+    ///
+    /// 默认解压后最多 64MB
+    ///
+    pub fn new() -> Self {
+        HttpContentDecompressor {
+            max_content_length: DEFAULT_MAX_CONTENT_LENGTH,
+            decoder: None,
+            request_keep_alive: None,
+            discarding: false,
+        }
+    }
+
+    pub fn max_content_length(mut self, max_content_length: usize) -> Self {
+        self.max_content_length = max_content_length;
+        self
+    }
+
+    ///
+    /// 消息头带有支持的 Content-Encoding 时返回解压器并改写消息头, 多重编码不处理
+    ///
+    fn prepare<M: HttpMessage>(message: &mut M, max_content_length: usize) -> Option<ContentDecoder> {
+        let coding = ContentCoding::parse(message.headers().get(names::CONTENT_ENCODING)?)?;
+        message.headers_mut().remove(names::CONTENT_ENCODING);
+        message.headers_mut().remove(names::CONTENT_LENGTH);
+        if message.version() == HttpVersion::Http11 {
+            message.set_transfer_encoding_chunked(true);
+        }
+        Some(ContentDecoder::new(coding, max_content_length))
+    }
+
+    ///
+    /// 完整消息一次解压, 重新设置 Content-Length
+    ///
+    fn decode_full<M: HttpMessage>(message: &mut M, content: &[u8], max_content_length: usize) -> Result<Option<Vec<u8>>, (ContentDecoder, std::io::Error)> {
+        let mut decoder = match HttpContentDecompressor::prepare(message, max_content_length) {
+            Some(decoder) => decoder,
+            None => return Ok(None),
+        };
+        let decoded = match decode_last(&mut decoder, content) {
+            Ok(decoded) => decoded,
+            Err(e) => return Err((decoder, e)),
+        };
+        message.set_transfer_encoding_chunked(false);
+        message.set_content_length(decoded.len() as u64);
+        Ok(Some(decoded))
+    }
+
+    ///
+    /// 解压失败或超过长度限制, 丢弃这个消息剩下的内容
+    ///
+    fn fail(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, decoder: &ContentDecoder, error: std::io::Error, request_keep_alive: Option<bool>) {
+        self.decoder = None;
+        self.request_keep_alive = None;
+        self.discarding = true;
+        if !decoder.is_too_large() {
+            channel_handler_ctx.fire_channel_exception(RettyErrorKind::new(ErrorKind::InvalidData, format!("DecompressionError: {}", error)));
+            return;
+        }
+        match request_keep_alive {
+            Some(keep_alive) => {
+                let mut response = FullHttpResponse::new(HttpVersion::Http11, HttpResponseStatus::REQUEST_ENTITY_TOO_LARGE, Vec::new());
+                response.set_content_length(0);
+                if !keep_alive {
+                    response.set_keep_alive(false);
+                }
+                channel_handler_ctx.write_and_flush(&mut response);
+                if !keep_alive && channel_handler_ctx.channel().is_active() {
+                    channel_handler_ctx.close();
+                }
+            }
+            None => {
+                let err = RettyErrorKind::new(ErrorKind::InvalidData,
+                                              format!("TooLongFrame: content is larger than {} bytes after decompression", self.max_content_length));
+                channel_handler_ctx.fire_channel_exception(err);
+            }
+        }
+    }
+}
+
+///
+/// 默认解压后的最大长度
+///
+const DEFAULT_MAX_CONTENT_LENGTH: usize = 64 * 1024 * 1024;
+
+fn decode_last(decoder: &mut ContentDecoder, content: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoded = decoder.decode(content)?;
+    decoded.extend(decoder.finish()?);
+    Ok(decoded)
+}
+
+impl ChannelInboundHandler for HttpContentDecompressor {
+    fn id(&self) -> String {
+        return "HttpContentDecompressor".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        self.decoder = None;
+        self.request_keep_alive = None;
+        self.discarding = false;
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(request) = message.downcast_mut::<HttpRequest>() {
+            self.discarding = false;
+            self.decoder = HttpContentDecompressor::prepare(request, self.max_content_length);
+            self.request_keep_alive = Some(request.is_keep_alive());
+        } else if let Some(response) = message.downcast_mut::<HttpResponse>() {
+            self.discarding = false;
+            self.decoder = HttpContentDecompressor::prepare(response, self.max_content_length);
+            self.request_keep_alive = None;
+        } else if let Some(request) = message.downcast_mut::<FullHttpRequest>() {
+            let content = request.content().to_vec();
+            match HttpContentDecompressor::decode_full(request, &content, self.max_content_length) {
+                Ok(Some(decoded)) => request.set_content(decoded),
+                Ok(None) => {}
+                Err((decoder, error)) => {
+                    self.fail(channel_handler_ctx, &decoder, error, Some(request.is_keep_alive()));
+                    self.discarding = false;
+                    return;
+                }
+            }
+        } else if let Some(response) = message.downcast_mut::<FullHttpResponse>() {
+            let content = response.content().to_vec();
+            match HttpContentDecompressor::decode_full(response, &content, self.max_content_length) {
+                Ok(Some(decoded)) => response.set_content(decoded),
+                Ok(None) => {}
+                Err((decoder, error)) => {
+                    self.fail(channel_handler_ctx, &decoder, error, None);
+                    self.discarding = false;
+                    return;
+                }
+            }
+        } else if self.discarding {
+            if message.is::<LastHttpContent>() {
+                self.discarding = false;
+            }
+            return;
+        } else if let (Some(decoder), Some(content)) = (self.decoder.as_mut(), message.downcast_ref::<HttpContent>()) {
+            match decoder.decode(content.content()) {
+                Ok(decoded) if decoded.is_empty() => {}
+                Ok(decoded) => channel_handler_ctx.fire_channel_read(&mut HttpContent::new(decoded)),
+                Err(e) => {
+                    let decoder = self.decoder.take().unwrap();
+                    let request_keep_alive = self.request_keep_alive.take();
+                    self.fail(channel_handler_ctx, &decoder, e, request_keep_alive);
+                }
+            }
+            return;
+        } else if let (Some(_), Some(last)) = (self.decoder.as_ref(), message.downcast_ref::<LastHttpContent>()) {
+            let mut decoder = self.decoder.take().unwrap();
+            let request_keep_alive = self.request_keep_alive.take();
+            match decode_last(&mut decoder, last.content()) {
+                Ok(decoded) => {
+                    let mut decoded_last = LastHttpContent::new(decoded);
+                    *decoded_last.trailing_headers_mut() = last.trailing_headers().clone();
+                    channel_handler_ctx.fire_channel_read(&mut decoded_last);
+                }
+                Err(e) => {
+                    self.fail(channel_handler_ctx, &decoder, e, request_keep_alive);
+                    // 消息已经结束, 不用再丢弃
+                    self.discarding = false;
+                }
+            }
+            return;
+        }
+        channel_handler_ctx.fire_channel_read(message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
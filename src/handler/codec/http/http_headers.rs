@@ -0,0 +1,149 @@
+///
+/// 常用的header 名字
+///
+pub mod names {
+    pub const ACCEPT_ENCODING: &str = "Accept-Encoding";
+    pub const ACCEPT_RANGES: &str = "Accept-Ranges";
+    pub const ALLOW: &str = "Allow";
+    pub const CONNECTION: &str = "Connection";
+    pub const CONTENT_ENCODING: &str = "Content-Encoding";
+    pub const CONTENT_LENGTH: &str = "Content-Length";
+    pub const CONTENT_RANGE: &str = "Content-Range";
+    pub const CONTENT_TYPE: &str = "Content-Type";
+    pub const DATE: &str = "Date";
+    pub const ETAG: &str = "ETag";
+    pub const EXPECT: &str = "Expect";
+    pub const HOST: &str = "Host";
+    pub const IF_MODIFIED_SINCE: &str = "If-Modified-Since";
+    pub const IF_NONE_MATCH: &str = "If-None-Match";
+    pub const IF_RANGE: &str = "If-Range";
+    pub const LAST_MODIFIED: &str = "Last-Modified";
+    pub const LOCATION: &str = "Location";
+    pub const RANGE: &str = "Range";
+    pub const SEC_WEBSOCKET_ACCEPT: &str = "Sec-WebSocket-Accept";
+    pub const SEC_WEBSOCKET_EXTENSIONS: &str = "Sec-WebSocket-Extensions";
+    pub const SEC_WEBSOCKET_KEY: &str = "Sec-WebSocket-Key";
+    pub const SEC_WEBSOCKET_PROTOCOL: &str = "Sec-WebSocket-Protocol";
+    pub const SEC_WEBSOCKET_VERSION: &str = "Sec-WebSocket-Version";
+    pub const TRANSFER_ENCODING: &str = "Transfer-Encoding";
+    pub const UPGRADE: &str = "Upgrade";
+    pub const VARY: &str = "Vary";
+}
+
+///
+/// 常用的header 值
+///
+pub mod values {
+    pub const CHUNKED: &str = "chunked";
+    pub const CLOSE: &str = "close";
+    pub const CONTINUE: &str = "100-continue";
+    pub const KEEP_ALIVE: &str = "keep-alive";
+    pub const UPGRADE: &str = "upgrade";
+    pub const WEBSOCKET: &str = "websocket";
+}
+
+
+///
+/// HTTP header 列表, 名字不区分大小写, 保留添加的顺序和原始大小写
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HttpHeaders {
+    entries: Vec<(String, String)>,
+}
+
+impl HttpHeaders {
+    pub fn new() -> HttpHeaders {
+        HttpHeaders {
+            entries: Vec::new(),
+        }
+    }
+
+    ///
+    /// 第一个同名header 的值
+    ///
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries.iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.entries.iter()
+            .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|(n, _)| n.eq_ignore_ascii_case(name))
+    }
+
+    ///
+    /// 同名header 里逗号分隔的值是否包含value, 不区分大小写
+    ///
+    pub fn contains_value(&self, name: &str, value: &str) -> bool {
+        self.get_all(name).iter()
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim().eq_ignore_ascii_case(value))
+    }
+
+    pub fn add(&mut self, name: &str, value: &str) -> &mut Self {
+        self.entries.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    ///
+    /// 替换所有同名header
+    ///
+    pub fn set(&mut self, name: &str, value: &str) -> &mut Self {
+        match self.entries.iter().position(|(n, _)| n.eq_ignore_ascii_case(name)) {
+            Some(i) => {
+                self.entries[i].1 = value.to_owned();
+                let mut index = 0;
+                self.entries.retain(|(n, _)| {
+                    index += 1;
+                    index - 1 <= i || !n.eq_ignore_ascii_case(name)
+                });
+            }
+            None => {
+                self.add(name, value);
+            }
+        }
+        self
+    }
+
+    ///
+    /// 把续行追加到最后一个header 的值后面, 没有header 时返回false
+    ///
+    pub(crate) fn append_to_last(&mut self, value: &str) -> bool {
+        match self.entries.last_mut() {
+            Some((_, last)) => {
+                last.push(' ');
+                last.push_str(value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> &mut Self {
+        self.entries.retain(|(n, _)| !n.eq_ignore_ascii_case(name));
+        self
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&str, &str)> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
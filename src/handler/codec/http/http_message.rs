@@ -0,0 +1,596 @@
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+use crate::handler::codec::http::http_headers::{HttpHeaders, names, values};
+
+///
+/// HTTP 协议版本
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum HttpVersion {
+    Http10,
+    Http11,
+}
+
+impl HttpVersion {
+    pub fn parse(text: &str) -> Option<HttpVersion> {
+        match text {
+            "HTTP/1.1" => Some(HttpVersion::Http11),
+            "HTTP/1.0" => Some(HttpVersion::Http10),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+        }
+    }
+
+    ///
+    /// 没有 Connection header 时是否保持连接
+    ///
+    pub fn is_keep_alive_default(&self) -> bool {
+        *self == HttpVersion::Http11
+    }
+}
+
+impl Display for HttpVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.text())
+    }
+}
+
+
+///
+/// HTTP 请求方法, 不认识的方法保存在 Other 里
+///
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub enum HttpMethod {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    Other(String),
+}
+
+impl HttpMethod {
+    ///
+    /// 方法名是区分大小写的token, 空字符串或包含非法字符时返回None
+    ///
+    pub fn parse(text: &str) -> Option<HttpMethod> {
+        let method = match text {
+            "GET" => HttpMethod::Get,
+            "HEAD" => HttpMethod::Head,
+            "POST" => HttpMethod::Post,
+            "PUT" => HttpMethod::Put,
+            "DELETE" => HttpMethod::Delete,
+            "CONNECT" => HttpMethod::Connect,
+            "OPTIONS" => HttpMethod::Options,
+            "TRACE" => HttpMethod::Trace,
+            "PATCH" => HttpMethod::Patch,
+            _ => {
+                if text.is_empty() || !text.bytes().all(is_token_char) {
+                    return None;
+                }
+                HttpMethod::Other(text.to_owned())
+            }
+        };
+        Some(method)
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Connect => "CONNECT",
+            HttpMethod::Options => "OPTIONS",
+            HttpMethod::Trace => "TRACE",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Other(method) => method,
+        }
+    }
+}
+
+impl Display for HttpMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+///
+/// RFC 7230 token 字符
+///
+pub(crate) fn is_token_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+
+///
+/// 响应状态码和原因短语, 只按状态码比较
+///
+#[derive(Clone, Debug)]
+pub struct HttpResponseStatus {
+    code: u16,
+    reason_phrase: Cow<'static, str>,
+}
+
+impl HttpResponseStatus {
+    pub const CONTINUE: HttpResponseStatus = HttpResponseStatus::of(100, "Continue");
+    pub const SWITCHING_PROTOCOLS: HttpResponseStatus = HttpResponseStatus::of(101, "Switching Protocols");
+    pub const OK: HttpResponseStatus = HttpResponseStatus::of(200, "OK");
+    pub const CREATED: HttpResponseStatus = HttpResponseStatus::of(201, "Created");
+    pub const ACCEPTED: HttpResponseStatus = HttpResponseStatus::of(202, "Accepted");
+    pub const NO_CONTENT: HttpResponseStatus = HttpResponseStatus::of(204, "No Content");
+    pub const PARTIAL_CONTENT: HttpResponseStatus = HttpResponseStatus::of(206, "Partial Content");
+    pub const MOVED_PERMANENTLY: HttpResponseStatus = HttpResponseStatus::of(301, "Moved Permanently");
+    pub const FOUND: HttpResponseStatus = HttpResponseStatus::of(302, "Found");
+    pub const NOT_MODIFIED: HttpResponseStatus = HttpResponseStatus::of(304, "Not Modified");
+    pub const BAD_REQUEST: HttpResponseStatus = HttpResponseStatus::of(400, "Bad Request");
+    pub const UNAUTHORIZED: HttpResponseStatus = HttpResponseStatus::of(401, "Unauthorized");
+    pub const FORBIDDEN: HttpResponseStatus = HttpResponseStatus::of(403, "Forbidden");
+    pub const NOT_FOUND: HttpResponseStatus = HttpResponseStatus::of(404, "Not Found");
+    pub const METHOD_NOT_ALLOWED: HttpResponseStatus = HttpResponseStatus::of(405, "Method Not Allowed");
+    pub const REQUEST_TIMEOUT: HttpResponseStatus = HttpResponseStatus::of(408, "Request Timeout");
+    pub const PRECONDITION_FAILED: HttpResponseStatus = HttpResponseStatus::of(412, "Precondition Failed");
+    pub const REQUEST_ENTITY_TOO_LARGE: HttpResponseStatus = HttpResponseStatus::of(413, "Request Entity Too Large");
+    pub const REQUEST_URI_TOO_LONG: HttpResponseStatus = HttpResponseStatus::of(414, "Request-URI Too Long");
+    pub const REQUESTED_RANGE_NOT_SATISFIABLE: HttpResponseStatus = HttpResponseStatus::of(416, "Requested Range Not Satisfiable");
+    pub const EXPECTATION_FAILED: HttpResponseStatus = HttpResponseStatus::of(417, "Expectation Failed");
+    pub const UPGRADE_REQUIRED: HttpResponseStatus = HttpResponseStatus::of(426, "Upgrade Required");
+    pub const REQUEST_HEADER_FIELDS_TOO_LARGE: HttpResponseStatus = HttpResponseStatus::of(431, "Request Header Fields Too Large");
+    pub const INTERNAL_SERVER_ERROR: HttpResponseStatus = HttpResponseStatus::of(500, "Internal Server Error");
+    pub const NOT_IMPLEMENTED: HttpResponseStatus = HttpResponseStatus::of(501, "Not Implemented");
+    pub const BAD_GATEWAY: HttpResponseStatus = HttpResponseStatus::of(502, "Bad Gateway");
+    pub const SERVICE_UNAVAILABLE: HttpResponseStatus = HttpResponseStatus::of(503, "Service Unavailable");
+
+    const fn of(code: u16, reason_phrase: &'static str) -> HttpResponseStatus {
+        HttpResponseStatus {
+            code,
+            reason_phrase: Cow::Borrowed(reason_phrase),
+        }
+    }
+
+    pub fn new(code: u16, reason_phrase: &str) -> HttpResponseStatus {
+        HttpResponseStatus {
+            code,
+            reason_phrase: Cow::Owned(reason_phrase.to_owned()),
+        }
+    }
+
+    ///
+    /// 标准状态码使用标准的原因短语
+    ///
+    pub fn value_of(code: u16) -> HttpResponseStatus {
+        let known = [
+            HttpResponseStatus::CONTINUE, HttpResponseStatus::SWITCHING_PROTOCOLS, HttpResponseStatus::OK,
+            HttpResponseStatus::CREATED, HttpResponseStatus::ACCEPTED, HttpResponseStatus::NO_CONTENT,
+            HttpResponseStatus::PARTIAL_CONTENT, HttpResponseStatus::MOVED_PERMANENTLY, HttpResponseStatus::FOUND,
+            HttpResponseStatus::NOT_MODIFIED, HttpResponseStatus::BAD_REQUEST, HttpResponseStatus::UNAUTHORIZED,
+            HttpResponseStatus::FORBIDDEN, HttpResponseStatus::NOT_FOUND, HttpResponseStatus::METHOD_NOT_ALLOWED,
+            HttpResponseStatus::REQUEST_TIMEOUT, HttpResponseStatus::PRECONDITION_FAILED,
+            HttpResponseStatus::REQUEST_ENTITY_TOO_LARGE, HttpResponseStatus::REQUEST_URI_TOO_LONG,
+            HttpResponseStatus::REQUESTED_RANGE_NOT_SATISFIABLE, HttpResponseStatus::EXPECTATION_FAILED,
+            HttpResponseStatus::UPGRADE_REQUIRED, HttpResponseStatus::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            HttpResponseStatus::INTERNAL_SERVER_ERROR, HttpResponseStatus::NOT_IMPLEMENTED,
+            HttpResponseStatus::BAD_GATEWAY, HttpResponseStatus::SERVICE_UNAVAILABLE,
+        ];
+        match known.iter().find(|s| s.code == code) {
+            Some(status) => status.clone(),
+            None => HttpResponseStatus::new(code, "Unknown Status"),
+        }
+    }
+
+    pub fn code(&self) -> u16 {
+        self.code
+    }
+
+    pub fn reason_phrase(&self) -> &str {
+        &self.reason_phrase
+    }
+
+    ///
+    /// 1xx
+    ///
+    pub fn is_informational(&self) -> bool {
+        self.code >= 100 && self.code < 200
+    }
+}
+
+impl PartialEq for HttpResponseStatus {
+    fn eq(&self, other: &Self) -> bool {
+        self.code == other.code
+    }
+}
+
+impl Eq for HttpResponseStatus {}
+
+impl Display for HttpResponseStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.code, self.reason_phrase)
+    }
+}
+
+
+///
+/// 请求和响应共用的 header 操作
+///
+pub trait HttpMessage {
+    fn version(&self) -> HttpVersion;
+
+    fn headers(&self) -> &HttpHeaders;
+
+    fn headers_mut(&mut self) -> &mut HttpHeaders;
+
+    ///
+    /// `Connection: close` 时不保持连接, 否则 HTTP/1.1 默认保持, HTTP/1.0 需要 `Connection: keep-alive`
+    ///
+    fn is_keep_alive(&self) -> bool {
+        if self.headers().contains_value(names::CONNECTION, values::CLOSE) {
+            return false;
+        }
+        self.version().is_keep_alive_default() || self.headers().contains_value(names::CONNECTION, values::KEEP_ALIVE)
+    }
+
+    fn set_keep_alive(&mut self, keep_alive: bool) {
+        let default = self.version().is_keep_alive_default();
+        let headers = self.headers_mut();
+        match (keep_alive, default) {
+            (true, true) => headers.remove(names::CONNECTION),
+            (true, false) => headers.set(names::CONNECTION, values::KEEP_ALIVE),
+            (false, _) => headers.set(names::CONNECTION, values::CLOSE),
+        };
+    }
+
+    ///
+    /// Content-Length 不存在或不是合法数字时返回None
+    ///
+    fn content_length(&self) -> Option<u64> {
+        self.headers().get(names::CONTENT_LENGTH).and_then(|v| v.trim().parse().ok())
+    }
+
+    fn set_content_length(&mut self, length: u64) {
+        self.headers_mut().set(names::CONTENT_LENGTH, &length.to_string());
+    }
+
+    fn is_transfer_encoding_chunked(&self) -> bool {
+        self.headers().contains_value(names::TRANSFER_ENCODING, values::CHUNKED)
+    }
+
+    ///
+    /// 设置为chunked 时去掉 Content-Length
+    ///
+    fn set_transfer_encoding_chunked(&mut self, chunked: bool) {
+        let headers = self.headers_mut();
+        if chunked {
+            headers.set(names::TRANSFER_ENCODING, values::CHUNKED);
+            headers.remove(names::CONTENT_LENGTH);
+        } else {
+            headers.remove(names::TRANSFER_ENCODING);
+        }
+    }
+
+    ///
+    /// HTTP/1.1 请求带有 `Expect: 100-continue`
+    ///
+    fn is_100_continue_expected(&self) -> bool {
+        self.version() == HttpVersion::Http11 && self.headers().contains_value(names::EXPECT, values::CONTINUE)
+    }
+}
+
+
+///
+/// 请求头, 后面跟着一个或多个 HttpContent, 以 LastHttpContent 结束
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HttpRequest {
+    method: HttpMethod,
+    uri: String,
+    version: HttpVersion,
+    headers: HttpHeaders,
+}
+
+impl HttpRequest {
+    pub fn new(version: HttpVersion, method: HttpMethod, uri: &str) -> HttpRequest {
+        HttpRequest {
+            method,
+            uri: uri.to_owned(),
+            version,
+            headers: HttpHeaders::new(),
+        }
+    }
+
+    pub fn method(&self) -> &HttpMethod {
+        &self.method
+    }
+
+    pub fn set_method(&mut self, method: HttpMethod) {
+        self.method = method;
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    pub fn set_uri(&mut self, uri: &str) {
+        self.uri = uri.to_owned();
+    }
+}
+
+impl HttpMessage for HttpRequest {
+    fn version(&self) -> HttpVersion {
+        self.version
+    }
+
+    fn headers(&self) -> &HttpHeaders {
+        &self.headers
+    }
+
+    fn headers_mut(&mut self) -> &mut HttpHeaders {
+        &mut self.headers
+    }
+}
+
+
+///
+/// 响应头, 后面跟着一个或多个 HttpContent, 以 LastHttpContent 结束
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HttpResponse {
+    version: HttpVersion,
+    status: HttpResponseStatus,
+    headers: HttpHeaders,
+}
+
+impl HttpResponse {
+    pub fn new(version: HttpVersion, status: HttpResponseStatus) -> HttpResponse {
+        HttpResponse {
+            version,
+            status,
+            headers: HttpHeaders::new(),
+        }
+    }
+
+    pub fn status(&self) -> &HttpResponseStatus {
+        &self.status
+    }
+
+    pub fn set_status(&mut self, status: HttpResponseStatus) {
+        self.status = status;
+    }
+}
+
+impl HttpMessage for HttpResponse {
+    fn version(&self) -> HttpVersion {
+        self.version
+    }
+
+    fn headers(&self) -> &HttpHeaders {
+        &self.headers
+    }
+
+    fn headers_mut(&mut self) -> &mut HttpHeaders {
+        &mut self.headers
+    }
+}
+
+
+///
+/// 消息体的一段
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct HttpContent {
+    content: Vec<u8>,
+}
+
+impl HttpContent {
+    pub fn new(content: Vec<u8>) -> HttpContent {
+        HttpContent {
+            content,
+        }
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn into_content(self) -> Vec<u8> {
+        self.content
+    }
+}
+
+
+///
+/// 消息体的最后一段, chunked 编码时带有 trailer
+///
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LastHttpContent {
+    content: Vec<u8>,
+    trailing_headers: HttpHeaders,
+}
+
+impl LastHttpContent {
+    pub fn new(content: Vec<u8>) -> LastHttpContent {
+        LastHttpContent {
+            content,
+            trailing_headers: HttpHeaders::new(),
+        }
+    }
+
+    pub fn empty() -> LastHttpContent {
+        LastHttpContent::default()
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn into_content(self) -> Vec<u8> {
+        self.content
+    }
+
+    pub fn trailing_headers(&self) -> &HttpHeaders {
+        &self.trailing_headers
+    }
+
+    pub fn trailing_headers_mut(&mut self) -> &mut HttpHeaders {
+        &mut self.trailing_headers
+    }
+}
+
+
+///
+/// 带有完整消息体的请求, 由 HttpObjectAggregator 聚合得到
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FullHttpRequest {
+    head: HttpRequest,
+    content: Vec<u8>,
+    trailing_headers: HttpHeaders,
+}
+
+impl FullHttpRequest {
+    pub fn new(version: HttpVersion, method: HttpMethod, uri: &str, content: Vec<u8>) -> FullHttpRequest {
+        FullHttpRequest::from_parts(HttpRequest::new(version, method, uri), content)
+    }
+
+    pub fn from_parts(head: HttpRequest, content: Vec<u8>) -> FullHttpRequest {
+        FullHttpRequest {
+            head,
+            content,
+            trailing_headers: HttpHeaders::new(),
+        }
+    }
+
+    pub fn head(&self) -> &HttpRequest {
+        &self.head
+    }
+
+    pub fn head_mut(&mut self) -> &mut HttpRequest {
+        &mut self.head
+    }
+
+    pub fn method(&self) -> &HttpMethod {
+        self.head.method()
+    }
+
+    pub fn uri(&self) -> &str {
+        self.head.uri()
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn set_content(&mut self, content: Vec<u8>) {
+        self.content = content;
+    }
+
+    pub fn trailing_headers(&self) -> &HttpHeaders {
+        &self.trailing_headers
+    }
+
+    pub fn trailing_headers_mut(&mut self) -> &mut HttpHeaders {
+        &mut self.trailing_headers
+    }
+
+    pub fn into_parts(self) -> (HttpRequest, Vec<u8>) {
+        (self.head, self.content)
+    }
+}
+
+impl HttpMessage for FullHttpRequest {
+    fn version(&self) -> HttpVersion {
+        self.head.version()
+    }
+
+    fn headers(&self) -> &HttpHeaders {
+        self.head.headers()
+    }
+
+    fn headers_mut(&mut self) -> &mut HttpHeaders {
+        self.head.headers_mut()
+    }
+}
+
+
+///
+/// 带有完整消息体的响应, 由 HttpObjectAggregator 聚合得到, 也可以直接写出
+///
+/// 不会自动设置 Content-Length, 写出前需要调用 `set_content_length`
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FullHttpResponse {
+    head: HttpResponse,
+    content: Vec<u8>,
+    trailing_headers: HttpHeaders,
+}
+
+impl FullHttpResponse {
+    pub fn new(version: HttpVersion, status: HttpResponseStatus, content: Vec<u8>) -> FullHttpResponse {
+        FullHttpResponse::from_parts(HttpResponse::new(version, status), content)
+    }
+
+    pub fn from_parts(head: HttpResponse, content: Vec<u8>) -> FullHttpResponse {
+        FullHttpResponse {
+            head,
+            content,
+            trailing_headers: HttpHeaders::new(),
+        }
+    }
+
+    pub fn head(&self) -> &HttpResponse {
+        &self.head
+    }
+
+    pub fn head_mut(&mut self) -> &mut HttpResponse {
+        &mut self.head
+    }
+
+    pub fn status(&self) -> &HttpResponseStatus {
+        self.head.status()
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn set_content(&mut self, content: Vec<u8>) {
+        self.content = content;
+    }
+
+    pub fn trailing_headers(&self) -> &HttpHeaders {
+        &self.trailing_headers
+    }
+
+    pub fn trailing_headers_mut(&mut self) -> &mut HttpHeaders {
+        &mut self.trailing_headers
+    }
+
+    pub fn into_parts(self) -> (HttpResponse, Vec<u8>) {
+        (self.head, self.content)
+    }
+}
+
+impl HttpMessage for FullHttpResponse {
+    fn version(&self) -> HttpVersion {
+        self.head.version()
+    }
+
+    fn headers(&self) -> &HttpHeaders {
+        self.head.headers()
+    }
+
+    fn headers_mut(&mut self) -> &mut HttpHeaders {
+        self.head.headers_mut()
+    }
+}
@@ -0,0 +1,213 @@
+use std::any::Any;
+use std::io::ErrorKind;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpContent, HttpMessage, HttpRequest, HttpResponse, HttpResponseStatus, HttpVersion, LastHttpContent};
+use crate::handler::codec::http::http_request_decoder::HttpDecoderState;
+use crate::handler::handler::ChannelInboundHandler;
+
+///
+/// 正在聚合的消息
+///
+enum Aggregating {
+    Request(HttpRequest, Vec<u8>),
+    Response(HttpResponse, Vec<u8>),
+}
+
+
+///
+/// 把 HttpRequest/HttpResponse 和后面的 HttpContent、LastHttpContent 聚合成
+/// FullHttpRequest/FullHttpResponse, 放在 HttpServerCodec 后面
+///
+/// 聚合后的消息去掉 `Transfer-Encoding: chunked`, 并把 Content-Length 设置为消息体的长度
+///
+/// 请求体超过 max_content_length 时响应 `413 Request Entity Too Large` 并丢弃这个请求,
+/// 请求不保持连接时响应后关闭连接; `Expect: 100-continue` 的请求在长度没有超过限制时先响应
+/// `100 Continue`, 其它期望响应 `417 Expectation Failed`;
+/// 响应体超过限制时触发 `InvalidData` 异常并丢弃这个响应
+///
+/// ```ignore
+/// bootstrap.initialize_pipeline(|inbound, outbound| {
+///     HttpServerCodec::new().add_last(inbound, outbound);
+///     inbound.add_last(Box::new(HttpObjectAggregator::new(1024 * 1024)));
+///     inbound.add_last(Box::new(BizHandler::new()));
+/// });
+/// ```
+///
+pub struct HttpObjectAggregator {
+    max_content_length: usize,
+    current: Option<Aggregating>,
+    ///
+    /// 正在丢弃超长或被拒绝的消息的内容
+    ///
+    discarding: bool,
+}
+
+impl HttpObjectAggregator {
+    pub fn new(max_content_length: usize) -> Self {
+        HttpObjectAggregator {
+            max_content_length,
+            current: None,
+            discarding: false,
+        }
+    }
+
+    fn begin_request(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, mut request: HttpRequest) {
+        let too_large = request.content_length().map_or(false, |length| length > self.max_content_length as u64);
+        // HTTP/1.0 的请求忽略 Expect
+        if request.version() == HttpVersion::Http11 && request.headers().contains(names::EXPECT) {
+            if !request.is_100_continue_expected() {
+                self.discarding = true;
+                let mut response = FullHttpResponse::new(HttpVersion::Http11, HttpResponseStatus::EXPECTATION_FAILED, Vec::new());
+                response.set_content_length(0);
+                channel_handler_ctx.write_and_flush(&mut response);
+                HttpDecoderState::set_expectation_failed(&channel_handler_ctx.channel());
+                return;
+            }
+            if !too_large {
+                let mut response = FullHttpResponse::new(HttpVersion::Http11, HttpResponseStatus::CONTINUE, Vec::new());
+                channel_handler_ctx.write_and_flush(&mut response);
+                request.headers_mut().remove(names::EXPECT);
+            }
+        }
+        if too_large {
+            self.handle_oversized_request(channel_handler_ctx, &request);
+            return;
+        }
+        request.set_transfer_encoding_chunked(false);
+        self.current = Some(Aggregating::Request(request, Vec::new()));
+    }
+
+    fn begin_response(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, mut response: HttpResponse) {
+        if response.content_length().map_or(false, |length| length > self.max_content_length as u64) {
+            self.handle_oversized_response(channel_handler_ctx);
+            return;
+        }
+        response.set_transfer_encoding_chunked(false);
+        self.current = Some(Aggregating::Response(response, Vec::new()));
+    }
+
+    ///
+    /// 追加内容, 超过限制时丢弃正在聚合的消息
+    ///
+    fn append(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, content: &[u8]) {
+        let max_content_length = self.max_content_length;
+        let too_large = match self.current.as_mut() {
+            Some(Aggregating::Request(_, buf)) | Some(Aggregating::Response(_, buf)) => {
+                if buf.len() + content.len() > max_content_length {
+                    true
+                } else {
+                    buf.extend_from_slice(content);
+                    false
+                }
+            }
+            None => false,
+        };
+        if !too_large {
+            return;
+        }
+        match self.current.take() {
+            Some(Aggregating::Request(request, _)) => self.handle_oversized_request(channel_handler_ctx, &request),
+            Some(Aggregating::Response(_, _)) => self.handle_oversized_response(channel_handler_ctx),
+            None => {}
+        }
+    }
+
+    fn finish(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, last: &LastHttpContent) {
+        match self.current.take() {
+            Some(Aggregating::Request(request, content)) => {
+                let mut request = FullHttpRequest::from_parts(request, content);
+                request.set_content_length(request.content().len() as u64);
+                *request.trailing_headers_mut() = last.trailing_headers().clone();
+                channel_handler_ctx.fire_channel_read(&mut request);
+            }
+            Some(Aggregating::Response(response, content)) => {
+                let mut response = FullHttpResponse::from_parts(response, content);
+                if !response.status().is_informational() {
+                    response.set_content_length(response.content().len() as u64);
+                }
+                *response.trailing_headers_mut() = last.trailing_headers().clone();
+                channel_handler_ctx.fire_channel_read(&mut response);
+            }
+            None => {}
+        }
+    }
+
+    ///
+    /// 响应413, 请求不保持连接也不等待 100-continue 时关闭连接
+    ///
+    fn handle_oversized_request(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, request: &HttpRequest) {
+        self.discarding = true;
+        let close = !request.is_100_continue_expected() && !request.is_keep_alive();
+        let mut response = FullHttpResponse::new(HttpVersion::Http11, HttpResponseStatus::REQUEST_ENTITY_TOO_LARGE, Vec::new());
+        response.set_content_length(0);
+        if close {
+            response.set_keep_alive(false);
+        }
+        channel_handler_ctx.write_and_flush(&mut response);
+        if request.is_100_continue_expected() {
+            HttpDecoderState::set_expectation_failed(&channel_handler_ctx.channel());
+        }
+        if close && channel_handler_ctx.channel().is_active() {
+            channel_handler_ctx.close();
+        }
+    }
+
+    fn handle_oversized_response(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        self.discarding = true;
+        let err = RettyErrorKind::new(ErrorKind::InvalidData,
+                                      format!("TooLongFrame: response entity is larger than {} bytes", self.max_content_length));
+        channel_handler_ctx.fire_channel_exception(err);
+    }
+}
+
+impl ChannelInboundHandler for HttpObjectAggregator {
+    fn id(&self) -> String {
+        return "HttpObjectAggregator".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        self.current = None;
+        self.discarding = false;
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(request) = message.downcast_ref::<HttpRequest>() {
+            self.current = None;
+            self.discarding = false;
+            self.begin_request(channel_handler_ctx, request.clone());
+        } else if let Some(response) = message.downcast_ref::<HttpResponse>() {
+            self.current = None;
+            self.discarding = false;
+            self.begin_response(channel_handler_ctx, response.clone());
+        } else if let Some(content) = message.downcast_ref::<HttpContent>() {
+            if !self.discarding {
+                self.append(channel_handler_ctx, content.content());
+            }
+        } else if let Some(last) = message.downcast_ref::<LastHttpContent>() {
+            if self.discarding {
+                self.discarding = false;
+                return;
+            }
+            self.append(channel_handler_ctx, last.content());
+            if self.discarding {
+                self.discarding = false;
+                return;
+            }
+            self.finish(channel_handler_ctx, last);
+        } else {
+            channel_handler_ctx.fire_channel_read(message);
+        }
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
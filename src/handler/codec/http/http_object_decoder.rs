@@ -0,0 +1,481 @@
+use std::any::Any;
+use std::io::ErrorKind;
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::codec::http::http_headers::{HttpHeaders, names, values};
+use crate::handler::codec::http::http_message::{HttpContent, HttpMessage, HttpMethod, HttpRequest, HttpResponse, HttpResponseStatus, HttpVersion, is_token_char, LastHttpContent};
+
+pub(crate) const DEFAULT_MAX_INITIAL_LINE_LENGTH: usize = 4096;
+pub(crate) const DEFAULT_MAX_HEADER_SIZE: usize = 8192;
+pub(crate) const DEFAULT_MAX_HEADER_COUNT: usize = 100;
+pub(crate) const DEFAULT_MAX_CHUNK_SIZE: usize = 8192;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum State {
+    SkipControlChars,
+    ReadInitial,
+    ReadHeader,
+    ReadFixedLengthContent,
+    ///
+    /// 没有长度的响应体, 读到连接关闭为止
+    ///
+    ReadVariableLengthContent,
+    ReadChunkSize,
+    ReadChunkedContent,
+    ReadChunkDelimiter,
+    ReadChunkFooter,
+    BadMessage,
+    ///
+    /// 101 之后连接已经切换协议, 后面的字节原样传递
+    ///
+    Upgraded,
+}
+
+///
+/// 消息体的长度
+///
+enum BodyLength {
+    Empty,
+    Fixed(u64),
+    Chunked,
+    UntilClose,
+}
+
+///
+/// 正在读取header 的消息
+///
+enum DecodingMessage {
+    Request(HttpRequest),
+    Response(HttpResponse),
+}
+
+impl DecodingMessage {
+    fn headers_mut(&mut self) -> &mut HttpHeaders {
+        match self {
+            DecodingMessage::Request(request) => request.headers_mut(),
+            DecodingMessage::Response(response) => response.headers_mut(),
+        }
+    }
+}
+
+
+///
+/// HTTP/1.x 请求或响应的解码状态机
+///
+/// 把字节流解码成 HttpRequest/HttpResponse、零个或多个 HttpContent 和一个 LastHttpContent,
+/// 一个消息结束后继续解码同一个缓冲区里的下一个消息
+///
+pub(crate) struct HttpObjectDecoder {
+    pub(crate) max_initial_line_length: usize,
+    pub(crate) max_header_size: usize,
+    pub(crate) max_header_count: usize,
+    pub(crate) max_chunk_size: usize,
+    ///
+    /// 解码响应而不是请求
+    ///
+    response: bool,
+    ///
+    /// 正在解码的响应对应HEAD 请求, 由 HttpResponseDecoder 在每个响应开始前设置
+    ///
+    pub(crate) head_request: bool,
+    state: State,
+    message: Option<DecodingMessage>,
+    trailers: HttpHeaders,
+    header_size: usize,
+    ///
+    /// 当前消息体或chunk 剩下的字节数
+    ///
+    remaining: u64,
+}
+
+impl HttpObjectDecoder {
+    pub(crate) fn new() -> HttpObjectDecoder {
+        HttpObjectDecoder {
+            max_initial_line_length: DEFAULT_MAX_INITIAL_LINE_LENGTH,
+            max_header_size: DEFAULT_MAX_HEADER_SIZE,
+            max_header_count: DEFAULT_MAX_HEADER_COUNT,
+            max_chunk_size: DEFAULT_MAX_CHUNK_SIZE,
+            response: false,
+            head_request: false,
+            state: State::SkipControlChars,
+            message: None,
+            trailers: HttpHeaders::new(),
+            header_size: 0,
+            remaining: 0,
+        }
+    }
+
+    pub(crate) fn new_response() -> HttpObjectDecoder {
+        HttpObjectDecoder {
+            response: true,
+            ..HttpObjectDecoder::new()
+        }
+    }
+
+    ///
+    /// 正在解码一个消息, 还没有读到 LastHttpContent
+    ///
+    pub(crate) fn is_decoding(&self) -> bool {
+        self.state != State::SkipControlChars && self.state != State::BadMessage && self.state != State::Upgraded
+    }
+
+    ///
+    /// 101 之后连接已经切换协议
+    ///
+    pub(crate) fn is_upgraded(&self) -> bool {
+        self.state == State::Upgraded
+    }
+
+    ///
+    /// 连接关闭, 读到关闭为止的响应体在这里结束; 返回false 表示消息不完整
+    ///
+    pub(crate) fn finish_on_close(&mut self, out: &mut Vec<Box<dyn Any>>) -> bool {
+        if self.state == State::ReadVariableLengthContent {
+            out.push(Box::new(LastHttpContent::empty()));
+            self.reset();
+            return true;
+        }
+        !self.is_decoding()
+    }
+
+    ///
+    /// 丢弃正在解码的消息, 从下一个消息开始
+    ///
+    pub(crate) fn reset(&mut self) {
+        self.state = State::SkipControlChars;
+        self.message = None;
+        self.trailers = HttpHeaders::new();
+        self.header_size = 0;
+        self.remaining = 0;
+    }
+
+    ///
+    /// 推进一步, 解出的消息放进out, 返回消费的字节数, 数据不够时返回None
+    ///
+    /// 出错后进入 BadMessage 状态, 之后的数据全部丢弃
+    ///
+    pub(crate) fn decode_step(&mut self, bytes: &[u8], out: &mut Vec<Box<dyn Any>>) -> Result<Option<usize>, RettyErrorKind> {
+        self.decode_once(bytes, out).map_err(|e| {
+            self.reset();
+            self.state = State::BadMessage;
+            e
+        })
+    }
+
+    fn decode_once(&mut self, bytes: &[u8], out: &mut Vec<Box<dyn Any>>) -> Result<Option<usize>, RettyErrorKind> {
+        match self.state {
+            State::SkipControlChars => {
+                let skipped = bytes.iter().take_while(|b| b.is_ascii_control() || b.is_ascii_whitespace()).count();
+                if skipped == bytes.len() {
+                    return Ok(if skipped > 0 { Some(skipped) } else { None });
+                }
+                self.state = State::ReadInitial;
+                Ok(Some(skipped))
+            }
+            State::ReadInitial => {
+                let (line, consumed) = match read_line(bytes, self.max_initial_line_length, too_long_line)? {
+                    Some(line) => line,
+                    None => return Ok(None),
+                };
+                self.message = Some(if self.response {
+                    DecodingMessage::Response(parse_status_line(line)?)
+                } else {
+                    DecodingMessage::Request(parse_request_line(line)?)
+                });
+                self.header_size = 0;
+                self.state = State::ReadHeader;
+                Ok(Some(consumed))
+            }
+            State::ReadHeader => {
+                let max_header_size = self.max_header_size;
+                let header_size = self.header_size;
+                let (line, consumed) = match read_line(bytes, max_header_size.saturating_sub(header_size), |_| too_long_header(max_header_size))? {
+                    Some(line) => line,
+                    None => return Ok(None),
+                };
+                self.header_size += consumed;
+                if !line.is_empty() {
+                    let headers = self.message.as_mut().unwrap().headers_mut();
+                    add_header_line(headers, line, self.max_header_count)?;
+                    return Ok(Some(consumed));
+                }
+                let (body, upgraded) = match self.message.take().unwrap() {
+                    DecodingMessage::Request(mut request) => {
+                        let body = body_length(request.headers_mut(), false)?;
+                        out.push(Box::new(request));
+                        (body, false)
+                    }
+                    DecodingMessage::Response(mut response) => {
+                        let body = response_body_length(&mut response, self.head_request)?;
+                        let upgraded = *response.status() == HttpResponseStatus::SWITCHING_PROTOCOLS;
+                        out.push(Box::new(response));
+                        (body, upgraded)
+                    }
+                };
+                match body {
+                    BodyLength::Empty => {
+                        out.push(Box::new(LastHttpContent::empty()));
+                        self.reset();
+                        if upgraded {
+                            self.state = State::Upgraded;
+                        }
+                    }
+                    BodyLength::Fixed(length) => {
+                        self.remaining = length;
+                        self.state = State::ReadFixedLengthContent;
+                    }
+                    BodyLength::Chunked => self.state = State::ReadChunkSize,
+                    BodyLength::UntilClose => self.state = State::ReadVariableLengthContent,
+                }
+                Ok(Some(consumed))
+            }
+            State::ReadFixedLengthContent => {
+                if bytes.is_empty() {
+                    return Ok(None);
+                }
+                let length = self.content_slice_length(bytes);
+                let content = bytes[..length].to_vec();
+                self.remaining -= length as u64;
+                if self.remaining == 0 {
+                    out.push(Box::new(LastHttpContent::new(content)));
+                    self.reset();
+                } else {
+                    out.push(Box::new(HttpContent::new(content)));
+                }
+                Ok(Some(length))
+            }
+            State::ReadVariableLengthContent => {
+                if bytes.is_empty() {
+                    return Ok(None);
+                }
+                let length = bytes.len().min(self.max_chunk_size);
+                out.push(Box::new(HttpContent::new(bytes[..length].to_vec())));
+                Ok(Some(length))
+            }
+            State::ReadChunkSize => {
+                let (line, consumed) = match read_line(bytes, self.max_initial_line_length, too_long_line)? {
+                    Some(line) => line,
+                    None => return Ok(None),
+                };
+                let size = parse_chunk_size(line)?;
+                if size == 0 {
+                    self.header_size = 0;
+                    self.state = State::ReadChunkFooter;
+                } else {
+                    self.remaining = size;
+                    self.state = State::ReadChunkedContent;
+                }
+                Ok(Some(consumed))
+            }
+            State::ReadChunkedContent => {
+                if bytes.is_empty() {
+                    return Ok(None);
+                }
+                let length = self.content_slice_length(bytes);
+                out.push(Box::new(HttpContent::new(bytes[..length].to_vec())));
+                self.remaining -= length as u64;
+                if self.remaining == 0 {
+                    self.state = State::ReadChunkDelimiter;
+                }
+                Ok(Some(length))
+            }
+            State::ReadChunkDelimiter => {
+                match read_line(bytes, self.max_initial_line_length, too_long_line)? {
+                    Some((line, consumed)) if line.is_empty() => {
+                        self.state = State::ReadChunkSize;
+                        Ok(Some(consumed))
+                    }
+                    Some(_) => Err(bad_message("chunk data is not followed by CRLF".to_string())),
+                    None => Ok(None),
+                }
+            }
+            State::ReadChunkFooter => {
+                let max_header_size = self.max_header_size;
+                let header_size = self.header_size;
+                let (line, consumed) = match read_line(bytes, max_header_size.saturating_sub(header_size), |_| too_long_header(max_header_size))? {
+                    Some(line) => line,
+                    None => return Ok(None),
+                };
+                self.header_size += consumed;
+                if !line.is_empty() {
+                    add_header_line(&mut self.trailers, line, self.max_header_count)?;
+                    return Ok(Some(consumed));
+                }
+                let mut last = LastHttpContent::empty();
+                *last.trailing_headers_mut() = std::mem::take(&mut self.trailers);
+                out.push(Box::new(last));
+                self.reset();
+                Ok(Some(consumed))
+            }
+            State::BadMessage => Ok(if bytes.is_empty() { None } else { Some(bytes.len()) }),
+            State::Upgraded => {
+                if bytes.is_empty() {
+                    return Ok(None);
+                }
+                out.push(Box::new(ByteBuf::new_from(bytes)));
+                Ok(Some(bytes.len()))
+            }
+        }
+    }
+
+    fn content_slice_length(&self, bytes: &[u8]) -> usize {
+        (bytes.len() as u64).min(self.remaining).min(self.max_chunk_size as u64) as usize
+    }
+}
+
+
+///
+/// 读取一行, 返回不含 CRLF 的内容和消费的字节数, 超过max_length 时返回too_long 生成的错误
+///
+fn read_line<F>(bytes: &[u8], max_length: usize, too_long: F) -> Result<Option<(&[u8], usize)>, RettyErrorKind>
+    where F: Fn(usize) -> RettyErrorKind
+{
+    match bytes.iter().position(|b| *b == b'\n') {
+        Some(i) => {
+            let line = if i > 0 && bytes[i - 1] == b'\r' { &bytes[..i - 1] } else { &bytes[..i] };
+            if line.len() > max_length {
+                return Err(too_long(max_length));
+            }
+            Ok(Some((line, i + 1)))
+        }
+        None if bytes.len() > max_length + 1 => Err(too_long(max_length)),
+        None => Ok(None),
+    }
+}
+
+fn parse_request_line(line: &[u8]) -> Result<HttpRequest, RettyErrorKind> {
+    let line = std::str::from_utf8(line).map_err(|_| bad_message("request line is not valid UTF-8".to_string()))?;
+    let mut parts = line.split(|c: char| c == ' ' || c == '\t').filter(|s| !s.is_empty());
+    let (method, uri, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(uri), Some(version), None) => (method, uri, version),
+        _ => return Err(bad_message(format!("invalid request line: {}", line))),
+    };
+    let method = HttpMethod::parse(method).ok_or_else(|| bad_message(format!("invalid method: {}", method)))?;
+    let version = HttpVersion::parse(version).ok_or_else(|| bad_message(format!("unsupported HTTP version: {}", version)))?;
+    Ok(HttpRequest::new(version, method, uri))
+}
+
+///
+/// 状态行是 `HTTP-version SP status-code SP [reason-phrase]`, 原因短语可以为空或包含空格
+///
+fn parse_status_line(line: &[u8]) -> Result<HttpResponse, RettyErrorKind> {
+    let line = std::str::from_utf8(line).map_err(|_| bad_message("status line is not valid UTF-8".to_string()))?;
+    let mut parts = line.trim_end().splitn(3, ' ');
+    let (version, code) = match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) => (version, code),
+        _ => return Err(bad_message(format!("invalid status line: {}", line))),
+    };
+    let reason_phrase = parts.next().unwrap_or("").trim();
+    let version = HttpVersion::parse(version).ok_or_else(|| bad_message(format!("unsupported HTTP version: {}", version)))?;
+    let code = match code.parse::<u16>() {
+        Ok(value) if code.len() == 3 && code.bytes().all(|b| b.is_ascii_digit()) && value >= 100 => value,
+        _ => return Err(bad_message(format!("invalid status code: {}", code))),
+    };
+    let status = if reason_phrase.is_empty() {
+        HttpResponseStatus::value_of(code)
+    } else {
+        HttpResponseStatus::new(code, reason_phrase)
+    };
+    Ok(HttpResponse::new(version, status))
+}
+
+///
+/// 解析一行header, 以空白开头的行是上一个header 的续行
+///
+fn add_header_line(headers: &mut HttpHeaders, line: &[u8], max_header_count: usize) -> Result<(), RettyErrorKind> {
+    let line = std::str::from_utf8(line).map_err(|_| bad_message("header is not valid UTF-8".to_string()))?;
+    if line.starts_with(' ') || line.starts_with('\t') {
+        if !headers.append_to_last(line.trim()) {
+            return Err(bad_message(format!("invalid header: {}", line)));
+        }
+        return Ok(());
+    }
+    let colon = line.find(':').ok_or_else(|| bad_message(format!("invalid header: {}", line)))?;
+    let name = &line[..colon];
+    if name.is_empty() || !name.bytes().all(is_token_char) {
+        return Err(bad_message(format!("invalid header name: {}", name)));
+    }
+    if headers.len() >= max_header_count {
+        return Err(RettyErrorKind::new(ErrorKind::InvalidData,
+                                       format!("TooLongHttpHeader: HTTP header count exceeds the limit of {}", max_header_count)));
+    }
+    headers.add(name, line[colon + 1..].trim());
+    Ok(())
+}
+
+///
+/// 按 RFC 7230 3.3.3 确定消息体的长度, 同时有 Transfer-Encoding 和 Content-Length 时去掉 Content-Length
+///
+/// 最后的传输编码不是chunked 时, 响应读到连接关闭为止, 请求则是错误
+///
+fn body_length(headers: &mut HttpHeaders, response: bool) -> Result<BodyLength, RettyErrorKind> {
+    if headers.contains(names::TRANSFER_ENCODING) {
+        let last_coding = headers.get_all(names::TRANSFER_ENCODING).iter()
+            .flat_map(|v| v.split(','))
+            .map(|v| v.trim().to_owned())
+            .last();
+        headers.remove(names::CONTENT_LENGTH);
+        if matches!(last_coding, Some(ref coding) if coding.eq_ignore_ascii_case(values::CHUNKED)) {
+            return Ok(BodyLength::Chunked);
+        }
+        if response {
+            return Ok(BodyLength::UntilClose);
+        }
+        return Err(bad_message("chunked must be the final transfer coding of a request".to_string()));
+    }
+    let lengths = headers.get_all(names::CONTENT_LENGTH).iter()
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_owned())
+        .collect::<Vec<_>>();
+    if lengths.is_empty() {
+        return Ok(if response { BodyLength::UntilClose } else { BodyLength::Empty });
+    }
+    if lengths.iter().any(|v| *v != lengths[0]) {
+        return Err(bad_message(format!("multiple Content-Length values: {}", lengths.join(", "))));
+    }
+    let length = match lengths[0].parse::<u64>() {
+        Ok(length) if lengths[0].bytes().all(|b| b.is_ascii_digit()) => length,
+        _ => return Err(bad_message(format!("invalid Content-Length: {}", lengths[0]))),
+    };
+    if lengths.len() > 1 {
+        headers.set(names::CONTENT_LENGTH, &lengths[0]);
+    }
+    Ok(if length == 0 { BodyLength::Empty } else { BodyLength::Fixed(length) })
+}
+
+///
+/// HEAD 请求的响应和 1xx、204、304 没有消息体, 不管header 怎么写
+///
+fn response_body_length(response: &mut HttpResponse, head_request: bool) -> Result<BodyLength, RettyErrorKind> {
+    let code = response.status().code();
+    if head_request || response.status().is_informational() || code == 204 || code == 304 {
+        return Ok(BodyLength::Empty);
+    }
+    body_length(response.headers_mut(), true)
+}
+
+///
+/// chunk-size 是十六进制数, 后面可以跟 `;` 开头的扩展
+///
+fn parse_chunk_size(line: &[u8]) -> Result<u64, RettyErrorKind> {
+    let end = line.iter().position(|b| *b == b';' || b.is_ascii_whitespace()).unwrap_or_else(|| line.len());
+    let hex = std::str::from_utf8(&line[..end]).unwrap_or("");
+    if hex.is_empty() || hex.len() > 16 {
+        return Err(bad_message(format!("invalid chunk size: {}", String::from_utf8_lossy(line))));
+    }
+    u64::from_str_radix(hex, 16).map_err(|_| bad_message(format!("invalid chunk size: {}", hex)))
+}
+
+fn too_long_line(max_length: usize) -> RettyErrorKind {
+    RettyErrorKind::new(ErrorKind::InvalidData, format!("TooLongHttpLine: An HTTP line is larger than {} bytes", max_length))
+}
+
+fn too_long_header(max_header_size: usize) -> RettyErrorKind {
+    RettyErrorKind::new(ErrorKind::InvalidData, format!("TooLongHttpHeader: HTTP header is larger than {} bytes", max_header_size))
+}
+
+fn bad_message(message: String) -> RettyErrorKind {
+    RettyErrorKind::new(ErrorKind::InvalidData, format!("BadHttpMessage: {}", message))
+}
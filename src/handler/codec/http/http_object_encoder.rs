@@ -0,0 +1,93 @@
+use crate::handler::codec::http::http_headers::HttpHeaders;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum EncoderState {
+    ///
+    /// 等待消息头
+    ///
+    Init,
+    ContentNonChunk,
+    ContentChunk,
+    ///
+    /// HEAD 请求的响应、1xx、204、304 没有消息体, 丢弃内容
+    ///
+    ContentAlwaysEmpty,
+}
+
+///
+/// HTTP/1.x 消息头和消息体的编码, 请求和响应共用
+///
+pub(crate) struct HttpObjectEncoder {
+    pub(crate) state: EncoderState,
+}
+
+impl HttpObjectEncoder {
+    pub(crate) fn new() -> HttpObjectEncoder {
+        HttpObjectEncoder {
+            state: EncoderState::Init,
+        }
+    }
+
+    ///
+    /// 写出起始行和header, 决定后面消息体的编码方式
+    ///
+    pub(crate) fn encode_head(&mut self, initial_line: &str, headers: &HttpHeaders, chunked: bool, content_always_empty: bool, out: &mut Vec<u8>) {
+        out.extend_from_slice(initial_line.as_bytes());
+        out.extend_from_slice(b"\r\n");
+        encode_headers(headers, out);
+        out.extend_from_slice(b"\r\n");
+        self.state = if content_always_empty {
+            EncoderState::ContentAlwaysEmpty
+        } else if chunked {
+            EncoderState::ContentChunk
+        } else {
+            EncoderState::ContentNonChunk
+        };
+    }
+
+    pub(crate) fn encode_content(&mut self, content: &[u8], out: &mut Vec<u8>) {
+        match self.state {
+            EncoderState::ContentNonChunk => out.extend_from_slice(content),
+            EncoderState::ContentChunk => encode_chunk(content, out),
+            _ => {}
+        }
+    }
+
+    ///
+    /// 写出最后一段内容, chunked 编码时写出结束块和trailer
+    ///
+    pub(crate) fn encode_last_content(&mut self, content: &[u8], trailers: &HttpHeaders, out: &mut Vec<u8>) {
+        match self.state {
+            EncoderState::ContentNonChunk => out.extend_from_slice(content),
+            EncoderState::ContentChunk => {
+                encode_chunk(content, out);
+                out.extend_from_slice(b"0\r\n");
+                encode_headers(trailers, out);
+                out.extend_from_slice(b"\r\n");
+            }
+            _ => {}
+        }
+        self.state = EncoderState::Init;
+    }
+}
+
+fn encode_headers(headers: &HttpHeaders, out: &mut Vec<u8>) {
+    for (name, value) in headers.iter() {
+        out.extend_from_slice(name.as_bytes());
+        out.extend_from_slice(b": ");
+        out.extend_from_slice(value.as_bytes());
+        out.extend_from_slice(b"\r\n");
+    }
+}
+
+///
+/// 空内容不能写成chunk, 大小为0 的chunk 表示结束
+///
+fn encode_chunk(content: &[u8], out: &mut Vec<u8>) {
+    if content.is_empty() {
+        return;
+    }
+    out.extend_from_slice(format!("{:x}\r\n", content.len()).as_bytes());
+    out.extend_from_slice(content);
+    out.extend_from_slice(b"\r\n");
+}
@@ -0,0 +1,197 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoder;
+use crate::handler::codec::http::http_message::{HttpMessage, HttpMethod, HttpRequest};
+use crate::handler::codec::http::http_object_decoder::HttpObjectDecoder;
+use crate::transport::attribute::AttributeKey;
+use crate::transport::channel::InboundChannelCtx;
+
+///
+/// 已经解码、还没有响应的请求, HttpServerCodec 用来决定响应后是否保持连接
+///
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PendingRequest {
+    pub(crate) head: bool,
+    pub(crate) keep_alive: bool,
+}
+
+pub(crate) type PendingRequests = Arc<Mutex<VecDeque<PendingRequest>>>;
+
+///
+/// 后面的handler 通知 HttpRequestDecoder 的标志, 每个channel 一份, 保存在 `HTTP_DECODER_STATE` 属性里
+///
+/// 不在handler 里摘掉或替换解码器: channel_read 执行时前面的ctx 和handler 都被锁住, 从里面修改pipeline 会死锁,
+/// 所以解码器自己检查标志。解码器第一次解码时创建并持有这份状态, 之后每一步只读两个原子变量
+///
+#[derive(Default)]
+pub(crate) struct HttpDecoderState {
+    ///
+    /// 拒绝了请求的 Expect 之后设置, 解码器丢弃这个请求剩下的部分, 从下一个请求开始解码
+    ///
+    expectation_failed: AtomicBool,
+    ///
+    /// 协议升级的响应写出之后设置, 解码器不再解码HTTP, 剩下的字节和之后收到的ByteBuf 原样传递
+    ///
+    upgraded: AtomicBool,
+}
+
+const HTTP_DECODER_STATE: AttributeKey<HttpDecoderState> = AttributeKey::new("HttpDecoderState");
+
+impl HttpDecoderState {
+    fn attach(channel: &InboundChannelCtx) -> Arc<HttpDecoderState> {
+        let attr = channel.attr(&HTTP_DECODER_STATE);
+        match attr.set_if_absent(HttpDecoderState::default()) {
+            Some(state) => state,
+            None => attr.get().unwrap(),
+        }
+    }
+
+    pub(crate) fn set_expectation_failed(channel: &InboundChannelCtx) {
+        if let Some(state) = channel.attr(&HTTP_DECODER_STATE).get() {
+            state.expectation_failed.store(true, Ordering::Release);
+        }
+    }
+
+    pub(crate) fn set_upgraded(channel: &InboundChannelCtx) {
+        if let Some(state) = channel.attr(&HTTP_DECODER_STATE).get() {
+            state.upgraded.store(true, Ordering::Release);
+        }
+    }
+}
+
+
+///
+/// 把字节流解码成 HttpRequest、HttpContent 和 LastHttpContent
+///
+/// 支持 Content-Length 和 chunked 编码的请求体, 同一个连接上的流水线请求按顺序解出;
+/// 请求行、header 大小或header 数量超过限制, 或者请求格式错误时触发 `InvalidData` 异常,
+/// 之后收到的数据全部丢弃, 由异常handler 响应400 或关闭连接
+///
+/// ```ignore
+/// handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(HttpRequestDecoder::new().max_header_count(64))));
+/// ```
+///
+pub struct HttpRequestDecoder {
+    decoder: HttpObjectDecoder,
+    pub(crate) pending_requests: Option<PendingRequests>,
+    state: Option<Arc<HttpDecoderState>>,
+    ///
+    /// 连接已经切换到其它协议
+    ///
+    upgraded: bool,
+}
+
+impl HttpRequestDecoder {
+    ///
+    /// 默认请求行4096 字节, header 8192 字节、100 个, 每个HttpContent 最多8192 字节
+    ///
+    pub fn new() -> Self {
+        HttpRequestDecoder {
+            decoder: HttpObjectDecoder::new(),
+            pending_requests: None,
+            state: None,
+            upgraded: false,
+        }
+    }
+
+    pub fn max_initial_line_length(mut self, max_initial_line_length: usize) -> Self {
+        assert!(max_initial_line_length > 0, "max_initial_line_length must be a positive integer: {}", max_initial_line_length);
+        self.decoder.max_initial_line_length = max_initial_line_length;
+        self
+    }
+
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        assert!(max_header_size > 0, "max_header_size must be a positive integer: {}", max_header_size);
+        self.decoder.max_header_size = max_header_size;
+        self
+    }
+
+    pub fn max_header_count(mut self, max_header_count: usize) -> Self {
+        self.decoder.max_header_count = max_header_count;
+        self
+    }
+
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        assert!(max_chunk_size > 0, "max_chunk_size must be a positive integer: {}", max_chunk_size);
+        self.decoder.max_chunk_size = max_chunk_size;
+        self
+    }
+}
+
+impl ByteToMessageDecoder for HttpRequestDecoder {
+    fn id(&self) -> String {
+        return "HttpRequestDecoder".to_string();
+    }
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        let consumed = self.decode_bytes(channel_handler_ctx, buf.available_bytes(), out).unwrap_or(0);
+        buf.skip_index(consumed);
+    }
+
+    ///
+    /// 每次推进一步, 上一步解出的请求交给后面的handler 之后才检查 Expect 是否被拒绝、是否已经升级
+    ///
+    fn decode_bytes(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, bytes: &[u8], out: &mut Vec<Box<dyn Any>>) -> Option<usize> {
+        let state = match &self.state {
+            Some(state) => state,
+            None => self.state.insert(HttpDecoderState::attach(&channel_handler_ctx.channel())),
+        };
+        if state.expectation_failed.swap(false, Ordering::AcqRel) {
+            // 期望被拒绝, 客户端不会发送这个请求的消息体
+            self.decoder.reset();
+        }
+        loop {
+            match self.decoder.decode_step(bytes, out) {
+                // 跳过控制字符之后切换状态, 没有消费也没有产出
+                Ok(Some(0)) if out.is_empty() => continue,
+                Ok(Some(consumed)) => {
+                    if let Some(pending) = &self.pending_requests {
+                        for message in out.iter() {
+                            if let Some(request) = message.downcast_ref::<HttpRequest>() {
+                                pending.lock().unwrap().push_back(PendingRequest {
+                                    head: *request.method() == HttpMethod::Head,
+                                    keep_alive: request.is_keep_alive(),
+                                });
+                            }
+                        }
+                    }
+                    return Some(consumed);
+                }
+                Ok(None) => return Some(0),
+                Err(e) => {
+                    // 进入 BadMessage 状态, 剩下的数据全部丢弃
+                    channel_handler_ctx.fire_channel_exception(e);
+                    return Some(bytes.len());
+                }
+            }
+        }
+    }
+
+    ///
+    /// 升级响应写出之后, 客户端已经发送的新协议数据和之后的数据原样传递
+    ///
+    fn is_pass_through(&mut self) -> bool {
+        if !self.upgraded && self.state.as_ref().map_or(false, |state| state.upgraded.load(Ordering::Acquire)) {
+            self.upgraded = true;
+            self.decoder.reset();
+        }
+        self.upgraded
+    }
+
+    fn decode_last(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, _buf: &mut ByteBuf, _out: &mut Vec<Box<dyn Any>>) {
+        if !self.upgraded && self.decoder.is_decoding() {
+            let err = RettyErrorKind::new(ErrorKind::UnexpectedEof, String::from("PrematureChannelClosure: connection closed before the request was complete"));
+            channel_handler_ctx.fire_channel_exception(err);
+        }
+        self.decoder.reset();
+        self.upgraded = false;
+    }
+}
@@ -0,0 +1,91 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::core::trace;
+use crate::handler::channel_handler_ctx::ChannelOutboundHandlerCtx;
+use crate::handler::codec::http::http_message::{FullHttpRequest, HttpContent, HttpMessage, HttpMethod, HttpRequest, LastHttpContent};
+use crate::handler::codec::http::http_object_encoder::{EncoderState, HttpObjectEncoder};
+use crate::handler::handler::ChannelOutboundHandler;
+use crate::transport::file_region::FileRegion;
+
+///
+/// 已经发出、还没有收到响应的请求方法, HttpClientCodec 用来判断HEAD 请求的响应没有消息体
+///
+pub(crate) type PendingMethods = Arc<Mutex<VecDeque<HttpMethod>>>;
+
+
+///
+/// 把 HttpRequest、HttpContent、LastHttpContent 和 FullHttpRequest 编码成ByteBuf, 其它类型的消息原样传递
+///
+/// 请求头带有 `Transfer-Encoding: chunked` 时消息体按chunk 编码, 消息体中的 `FileRegion` 原样传给 TailHandler 发送
+///
+pub struct HttpRequestEncoder {
+    encoder: HttpObjectEncoder,
+    pub(crate) pending_methods: Option<PendingMethods>,
+}
+
+impl HttpRequestEncoder {
+    pub fn new() -> Self {
+        HttpRequestEncoder {
+            encoder: HttpObjectEncoder::new(),
+            pending_methods: None,
+        }
+    }
+
+    fn encode_request(&mut self, request: &HttpRequest, out: &mut Vec<u8>) {
+        if let Some(pending) = &self.pending_methods {
+            pending.lock().unwrap().push_back(request.method().clone());
+        }
+        let uri = if request.uri().is_empty() { "/" } else { request.uri() };
+        let initial_line = format!("{} {} {}", request.method(), uri, request.version());
+        let chunked = request.is_transfer_encoding_chunked();
+        self.encoder.encode_head(&initial_line, request.headers(), chunked, false, out);
+    }
+}
+
+impl ChannelOutboundHandler for HttpRequestEncoder {
+    fn id(&self) -> String {
+        return "HttpRequestEncoder".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        let mut out = channel_handler_ctx.alloc_buffer(256);
+        if let Some(request) = message.downcast_ref::<HttpRequest>() {
+            if self.encoder.state != EncoderState::Init {
+                log::warn!(target: trace::TARGET_PIPELINE, "HttpRequestEncoder previous request is not finished, channel_id:{}", channel_handler_ctx.channel().id());
+            }
+            self.encode_request(request, &mut out);
+        } else if let Some(request) = message.downcast_ref::<FullHttpRequest>() {
+            if self.encoder.state != EncoderState::Init {
+                log::warn!(target: trace::TARGET_PIPELINE, "HttpRequestEncoder previous request is not finished, channel_id:{}", channel_handler_ctx.channel().id());
+            }
+            self.encode_request(request.head(), &mut out);
+            self.encoder.encode_last_content(request.content(), request.trailing_headers(), &mut out);
+        } else if let Some(content) = message.downcast_ref::<HttpContent>() {
+            self.encoder.encode_content(content.content(), &mut out);
+        } else if let Some(content) = message.downcast_ref::<LastHttpContent>() {
+            self.encoder.encode_last_content(content.content(), content.trailing_headers(), &mut out);
+        } else if let Some(region) = message.downcast_ref::<FileRegion>() {
+            match self.encoder.state {
+                EncoderState::ContentChunk if region.remaining() > 0 => {
+                    out.extend_from_slice(format!("{:x}\r\n", region.remaining()).as_bytes());
+                    channel_handler_ctx.fire_channel_write(&mut out);
+                    channel_handler_ctx.fire_channel_write(message);
+                    let mut crlf = channel_handler_ctx.alloc_buffer(2);
+                    crlf.extend_from_slice(b"\r\n");
+                    channel_handler_ctx.fire_channel_write(&mut crlf);
+                }
+                EncoderState::ContentChunk => {}
+                _ => channel_handler_ctx.fire_channel_write(message),
+            }
+            return;
+        } else {
+            channel_handler_ctx.fire_channel_write(message);
+            return;
+        }
+        if !out.is_empty() {
+            channel_handler_ctx.fire_channel_write(&mut out);
+        }
+    }
+}
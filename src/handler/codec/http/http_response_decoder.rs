@@ -0,0 +1,145 @@
+use std::any::Any;
+use std::io::ErrorKind;
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoder;
+use crate::handler::codec::http::http_message::{HttpMethod, HttpResponse, HttpResponseStatus, LastHttpContent};
+use crate::handler::codec::http::http_object_decoder::HttpObjectDecoder;
+use crate::handler::codec::http::http_request_encoder::PendingMethods;
+
+///
+/// 把字节流解码成 HttpResponse、HttpContent 和 LastHttpContent, 客户端使用
+///
+/// 支持 Content-Length、chunked 和读到连接关闭为止的响应体; 1xx、204、304 的响应没有消息体,
+/// 与 HttpClientCodec 一起使用时HEAD 请求的响应也没有消息体; `101 Switching Protocols` 之后的数据
+/// 原样传递; 格式错误时触发 `InvalidData` 异常, 之后收到的数据全部丢弃
+///
+pub struct HttpResponseDecoder {
+    decoder: HttpObjectDecoder,
+    pub(crate) pending_methods: Option<PendingMethods>,
+    ///
+    /// 当前响应是 1xx, 不对应请求
+    ///
+    informational: bool,
+}
+
+impl HttpResponseDecoder {
+    ///
+    /// 默认状态行4096 字节, header 8192 字节、100 个, 每个HttpContent 最多8192 字节
+    ///
+    pub fn new() -> Self {
+        HttpResponseDecoder {
+            decoder: HttpObjectDecoder::new_response(),
+            pending_methods: None,
+            informational: false,
+        }
+    }
+
+    pub fn max_initial_line_length(mut self, max_initial_line_length: usize) -> Self {
+        assert!(max_initial_line_length > 0, "max_initial_line_length must be a positive integer: {}", max_initial_line_length);
+        self.decoder.max_initial_line_length = max_initial_line_length;
+        self
+    }
+
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        assert!(max_header_size > 0, "max_header_size must be a positive integer: {}", max_header_size);
+        self.decoder.max_header_size = max_header_size;
+        self
+    }
+
+    pub fn max_header_count(mut self, max_header_count: usize) -> Self {
+        self.decoder.max_header_count = max_header_count;
+        self
+    }
+
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        assert!(max_chunk_size > 0, "max_chunk_size must be a positive integer: {}", max_chunk_size);
+        self.decoder.max_chunk_size = max_chunk_size;
+        self
+    }
+
+    ///
+    /// 响应结束时取出对应的请求, 1xx 不对应请求
+    ///
+    fn track(&mut self, message: &dyn Any) {
+        if let Some(response) = message.downcast_ref::<HttpResponse>() {
+            self.informational = response.status().is_informational() && *response.status() != HttpResponseStatus::SWITCHING_PROTOCOLS;
+        } else if message.is::<LastHttpContent>() {
+            if !self.informational {
+                if let Some(pending) = &self.pending_methods {
+                    pending.lock().unwrap().pop_front();
+                }
+            }
+            self.informational = false;
+        }
+    }
+}
+
+impl ByteToMessageDecoder for HttpResponseDecoder {
+    fn id(&self) -> String {
+        return "HttpResponseDecoder".to_string();
+    }
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        let consumed = self.decode_bytes(channel_handler_ctx, buf.available_bytes(), out).unwrap_or(0);
+        buf.skip_index(consumed);
+    }
+
+    fn decode_bytes(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, bytes: &[u8], out: &mut Vec<Box<dyn Any>>) -> Option<usize> {
+        loop {
+            if !self.decoder.is_decoding() {
+                let head = self.pending_methods.as_ref()
+                    .map_or(false, |p| p.lock().unwrap().front() == Some(&HttpMethod::Head));
+                self.decoder.head_request = head;
+            }
+            match self.decoder.decode_step(bytes, out) {
+                // 跳过控制字符之后切换状态, 没有消费也没有产出
+                Ok(Some(0)) if out.is_empty() => continue,
+                Ok(Some(consumed)) => {
+                    for message in out.iter() {
+                        self.track(&**message);
+                    }
+                    return Some(consumed);
+                }
+                Ok(None) => return Some(0),
+                Err(e) => {
+                    // 进入 BadMessage 状态, 剩下的数据全部丢弃
+                    channel_handler_ctx.fire_channel_exception(e);
+                    return Some(bytes.len());
+                }
+            }
+        }
+    }
+
+    fn is_pass_through(&mut self) -> bool {
+        self.decoder.is_upgraded()
+    }
+
+    ///
+    /// 结束读到连接关闭为止的响应体, 响应不完整或者还有请求没有收到响应时触发异常
+    ///
+    fn decode_last(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, _buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        let complete = self.decoder.finish_on_close(out);
+        // 先把最后的消息交给后面的handler, 再报告缺少的响应
+        for mut message in out.drain(..) {
+            self.track(&*message);
+            channel_handler_ctx.fire_channel_read(&mut *message);
+        }
+        let missing = self.pending_methods.as_ref().map_or(0, |p| p.lock().unwrap().len());
+        if !complete {
+            let err = RettyErrorKind::new(ErrorKind::UnexpectedEof, String::from("PrematureChannelClosure: connection closed before the response was complete"));
+            channel_handler_ctx.fire_channel_exception(err);
+        } else if missing > 0 {
+            let err = RettyErrorKind::new(ErrorKind::UnexpectedEof, format!("PrematureChannelClosure: connection closed with {} request(s) still waiting for a response", missing));
+            channel_handler_ctx.fire_channel_exception(err);
+        }
+        self.decoder.reset();
+        self.informational = false;
+        if let Some(pending) = &self.pending_methods {
+            pending.lock().unwrap().clear();
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use std::any::Any;
+
+use crate::core::trace;
+use crate::handler::channel_handler_ctx::ChannelOutboundHandlerCtx;
+use crate::handler::codec::http::http_message::{FullHttpResponse, HttpContent, HttpMessage, HttpResponse, HttpVersion, LastHttpContent};
+use crate::handler::codec::http::http_object_encoder::{EncoderState, HttpObjectEncoder};
+use crate::handler::codec::http::http_request_decoder::PendingRequests;
+use crate::handler::handler::ChannelOutboundHandler;
+use crate::transport::file_region::FileRegion;
+
+///
+/// 把 HttpResponse、HttpContent、LastHttpContent 和 FullHttpResponse 编码成ByteBuf, 其它类型的消息原样传递
+///
+/// 响应头带有 `Transfer-Encoding: chunked` 时消息体按chunk 编码, 消息体中的 `FileRegion` 原样传给 TailHandler 发送,
+/// chunked 时在前后加上chunk 的长度行和结束符
+///
+pub struct HttpResponseEncoder {
+    encoder: HttpObjectEncoder,
+    pub(crate) pending_requests: Option<PendingRequests>,
+    ///
+    /// 当前响应是 1xx, 不对应请求
+    ///
+    informational: bool,
+    ///
+    /// 当前响应写完后关闭连接
+    ///
+    close_after_response: bool,
+}
+
+impl HttpResponseEncoder {
+    pub fn new() -> Self {
+        HttpResponseEncoder {
+            encoder: HttpObjectEncoder::new(),
+            pending_requests: None,
+            informational: false,
+            close_after_response: false,
+        }
+    }
+
+    ///
+    /// 与 HttpServerCodec 一起使用时, 根据请求决定响应是否保持连接
+    ///
+    fn prepare_keep_alive(&mut self, response: &mut HttpResponse, head_request: bool, request_keep_alive: bool) {
+        let content_always_empty = head_request || is_content_always_empty(response);
+        // 没有长度的消息体只能通过关闭连接结束
+        let self_delimited = content_always_empty || response.content_length().is_some() || response.is_transfer_encoding_chunked();
+        let keep_alive = request_keep_alive && response.is_keep_alive() && self_delimited;
+        if keep_alive != response.is_keep_alive() || (keep_alive && response.version() == HttpVersion::Http10) {
+            response.set_keep_alive(keep_alive);
+        }
+        self.close_after_response = !keep_alive;
+    }
+
+    fn encode_response(&mut self, response: &mut HttpResponse, out: &mut Vec<u8>) {
+        self.informational = response.status().is_informational();
+        let mut head_request = false;
+        if !self.informational {
+            let pending = self.pending_requests.as_ref().and_then(|p| p.lock().unwrap().front().copied());
+            if let Some(pending) = pending {
+                head_request = pending.head;
+                self.prepare_keep_alive(response, pending.head, pending.keep_alive);
+            }
+        }
+        let initial_line = format!("{} {}", response.version(), response.status());
+        let content_always_empty = head_request || is_content_always_empty(response);
+        let chunked = response.is_transfer_encoding_chunked();
+        self.encoder.encode_head(&initial_line, response.headers(), chunked, content_always_empty, out);
+    }
+
+    ///
+    /// 响应结束, 返回是否需要关闭连接
+    ///
+    fn finish_response(&mut self) -> bool {
+        if self.informational {
+            self.informational = false;
+            return false;
+        }
+        if let Some(pending) = &self.pending_requests {
+            pending.lock().unwrap().pop_front();
+        }
+        let close = self.close_after_response;
+        self.close_after_response = false;
+        close
+    }
+}
+
+///
+/// 1xx、204、304 的响应没有消息体
+///
+fn is_content_always_empty(response: &HttpResponse) -> bool {
+    let code = response.status().code();
+    (code >= 100 && code < 200) || code == 204 || code == 304
+}
+
+impl ChannelOutboundHandler for HttpResponseEncoder {
+    fn id(&self) -> String {
+        return "HttpResponseEncoder".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        let mut out = channel_handler_ctx.alloc_buffer(256);
+        let mut close = false;
+        if let Some(response) = message.downcast_mut::<HttpResponse>() {
+            if self.encoder.state != EncoderState::Init {
+                log::warn!(target: trace::TARGET_PIPELINE, "HttpResponseEncoder previous response is not finished, channel_id:{}", channel_handler_ctx.channel().id());
+            }
+            self.encode_response(response, &mut out);
+        } else if let Some(response) = message.downcast_mut::<FullHttpResponse>() {
+            if self.encoder.state != EncoderState::Init {
+                log::warn!(target: trace::TARGET_PIPELINE, "HttpResponseEncoder previous response is not finished, channel_id:{}", channel_handler_ctx.channel().id());
+            }
+            self.encode_response(response.head_mut(), &mut out);
+            self.encoder.encode_last_content(response.content(), response.trailing_headers(), &mut out);
+            close = self.finish_response();
+        } else if let Some(content) = message.downcast_ref::<HttpContent>() {
+            self.encoder.encode_content(content.content(), &mut out);
+        } else if let Some(content) = message.downcast_ref::<LastHttpContent>() {
+            self.encoder.encode_last_content(content.content(), content.trailing_headers(), &mut out);
+            close = self.finish_response();
+        } else if let Some(region) = message.downcast_ref::<FileRegion>() {
+            match self.encoder.state {
+                EncoderState::ContentAlwaysEmpty => {}
+                EncoderState::ContentChunk if region.remaining() > 0 => {
+                    out.extend_from_slice(format!("{:x}\r\n", region.remaining()).as_bytes());
+                    channel_handler_ctx.fire_channel_write(&mut out);
+                    channel_handler_ctx.fire_channel_write(message);
+                    let mut crlf = channel_handler_ctx.alloc_buffer(2);
+                    crlf.extend_from_slice(b"\r\n");
+                    channel_handler_ctx.fire_channel_write(&mut crlf);
+                }
+                EncoderState::ContentChunk => {}
+                _ => channel_handler_ctx.fire_channel_write(message),
+            }
+            return;
+        } else {
+            channel_handler_ctx.fire_channel_write(message);
+            return;
+        }
+        if !out.is_empty() {
+            channel_handler_ctx.fire_channel_write(&mut out);
+        }
+        if close {
+            // ctx 的 close 等出站缓冲区写完再关闭, 上面写出的最后一块不会被截断
+            channel_handler_ctx.channel().close();
+        }
+    }
+}
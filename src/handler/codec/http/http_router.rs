@@ -0,0 +1,316 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpMessage, HttpMethod, HttpResponseStatus, HttpVersion};
+use crate::handler::codec::http::query_string_decoder::{decode_component, QueryStringDecoder};
+use crate::handler::handler::ChannelInboundHandler;
+
+///
+/// 路由处理函数, 参数是匹配到的请求, 返回完整的响应
+///
+pub type RouteHandler = Arc<dyn Fn(&RouteRequest) -> FullHttpResponse + Send + Sync>;
+
+///
+/// 路由匹配后的请求, 带路径参数和查询参数
+///
+pub struct RouteRequest {
+    request: FullHttpRequest,
+    query: QueryStringDecoder,
+    path_params: Vec<(String, String)>,
+}
+
+impl RouteRequest {
+    pub fn request(&self) -> &FullHttpRequest {
+        &self.request
+    }
+
+    pub fn method(&self) -> &HttpMethod {
+        self.request.method()
+    }
+
+    pub fn version(&self) -> HttpVersion {
+        self.request.version()
+    }
+
+    ///
+    /// 解码后的请求路径, 不含查询参数
+    ///
+    pub fn path(&self) -> &str {
+        self.query.path()
+    }
+
+    pub fn content(&self) -> &[u8] {
+        self.request.content()
+    }
+
+    ///
+    /// 路径参数, `/users/:id` 中的 `id` 或 `/files/*path` 中的 `path`
+    ///
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.path_params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn params(&self) -> &[(String, String)] {
+        &self.path_params
+    }
+
+    ///
+    /// 第一个同名查询参数
+    ///
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.parameter(name)
+    }
+
+    pub fn query_all(&self, name: &str) -> Vec<&str> {
+        self.query.parameter_all(name)
+    }
+
+    pub fn query_params(&self) -> &[(String, String)] {
+        self.query.parameters()
+    }
+}
+
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Static(String),
+    Param(String),
+    ///
+    /// 匹配剩余的所有路径段, 只能放在最后
+    ///
+    Wildcard(String),
+}
+
+#[derive(Clone)]
+struct Route {
+    method: HttpMethod,
+    segments: Vec<Segment>,
+    handler: RouteHandler,
+}
+
+impl Route {
+    fn parse(pattern: &str) -> Vec<Segment> {
+        assert!(pattern.starts_with('/'), "route pattern must start with '/': {}", pattern);
+        let parts: Vec<&str> = pattern[1..].split('/').collect();
+        let last = parts.len() - 1;
+        parts.iter().enumerate().map(|(i, part)| {
+            if let Some(name) = part.strip_prefix(':') {
+                assert!(!name.is_empty(), "empty path parameter name: {}", pattern);
+                Segment::Param(name.to_owned())
+            } else if let Some(name) = part.strip_prefix('*') {
+                assert!(i == last, "wildcard must be the last segment: {}", pattern);
+                Segment::Wildcard(name.to_owned())
+            } else {
+                Segment::Static(part.to_string())
+            }
+        }).collect()
+    }
+
+    ///
+    /// 匹配成功返回路径参数
+    ///
+    fn matches(&self, parts: &[&str]) -> Option<Vec<(String, String)>> {
+        let mut params = Vec::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Wildcard(name) => {
+                    let rest = if i < parts.len() { parts[i..].join("/") } else { String::new() };
+                    params.push((name.clone(), decode_component(&rest, false)));
+                    return Some(params);
+                }
+                _ if i >= parts.len() => return None,
+                Segment::Static(s) => {
+                    if decode_component(parts[i], false) != *s {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => params.push((name.clone(), decode_component(parts[i], false))),
+            }
+        }
+        if parts.len() == self.segments.len() { Some(params) } else { None }
+    }
+
+    ///
+    /// 静态段越多越优先, 其次没有通配符的优先
+    ///
+    fn priority(&self) -> (usize, bool) {
+        let statics = self.segments.iter().filter(|s| matches!(s, Segment::Static(_))).count();
+        let wildcard = self.segments.iter().any(|s| matches!(s, Segment::Wildcard(_)));
+        (statics, !wildcard)
+    }
+}
+
+
+///
+/// HTTP 路由, 放在 HttpServerCodec 和 HttpObjectAggregator 后面, 按方法和路径把 FullHttpRequest 分发给处理函数,
+/// 处理函数返回的响应通过 write_and_flush 写出
+///
+/// 路径支持 `:name` 参数和放在最后的 `*name` 通配; 路径匹配但方法不匹配时响应 `405` 并带上 `Allow`,
+/// 都不匹配时调用 not_found, 默认响应 `404`; 没有注册 HEAD 的路径用 GET 的处理函数响应 HEAD
+///
+/// ```ignore
+/// let router = HttpRouter::new()
+///     .get("/users/:id", |req| {
+///         let body = format!("user {}", req.param("id").unwrap());
+///         FullHttpResponse::new(req.version(), HttpResponseStatus::OK, body.into_bytes())
+///     });
+///
+/// bootstrap.initialize_pipeline(move |inbound, outbound| {
+///     HttpServerCodec::new().add_last(inbound, outbound);
+///     inbound.add_last(Box::new(HttpObjectAggregator::new(1024 * 1024)));
+///     inbound.add_last(Box::new(router.clone()));
+/// });
+/// ```
+///
+#[derive(Clone)]
+pub struct HttpRouter {
+    routes: Vec<Route>,
+    not_found: Option<RouteHandler>,
+}
+
+impl HttpRouter {
+    pub fn new() -> Self {
+        HttpRouter {
+            routes: Vec::new(),
+            not_found: None,
+        }
+    }
+
+    pub fn route<F>(mut self, method: HttpMethod, pattern: &str, handler: F) -> Self
+        where F: Fn(&RouteRequest) -> FullHttpResponse + Send + Sync + 'static {
+        self.routes.push(Route {
+            method,
+            segments: Route::parse(pattern),
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn get<F>(self, pattern: &str, handler: F) -> Self
+        where F: Fn(&RouteRequest) -> FullHttpResponse + Send + Sync + 'static {
+        self.route(HttpMethod::Get, pattern, handler)
+    }
+
+    pub fn post<F>(self, pattern: &str, handler: F) -> Self
+        where F: Fn(&RouteRequest) -> FullHttpResponse + Send + Sync + 'static {
+        self.route(HttpMethod::Post, pattern, handler)
+    }
+
+    pub fn put<F>(self, pattern: &str, handler: F) -> Self
+        where F: Fn(&RouteRequest) -> FullHttpResponse + Send + Sync + 'static {
+        self.route(HttpMethod::Put, pattern, handler)
+    }
+
+    pub fn delete<F>(self, pattern: &str, handler: F) -> Self
+        where F: Fn(&RouteRequest) -> FullHttpResponse + Send + Sync + 'static {
+        self.route(HttpMethod::Delete, pattern, handler)
+    }
+
+    ///
+    /// 没有匹配的路由时调用
+    ///
+    pub fn not_found<F>(mut self, handler: F) -> Self
+        where F: Fn(&RouteRequest) -> FullHttpResponse + Send + Sync + 'static {
+        self.not_found = Some(Arc::new(handler));
+        self
+    }
+
+    fn dispatch(&self, request: FullHttpRequest) -> FullHttpResponse {
+        let query = QueryStringDecoder::new(request.uri());
+        let raw_path = query.raw_path().to_owned();
+        let parts: Vec<&str> = raw_path.strip_prefix('/').unwrap_or(&raw_path).split('/').collect();
+
+        let mut allowed: Vec<HttpMethod> = Vec::new();
+        let mut best: Option<(&Route, Vec<(String, String)>)> = None;
+        let mut head_fallback: Option<(&Route, Vec<(String, String)>)> = None;
+        for route in self.routes.iter() {
+            let params = match route.matches(&parts) {
+                Some(params) => params,
+                None => continue,
+            };
+            if !allowed.contains(&route.method) {
+                allowed.push(route.method.clone());
+            }
+            let candidate = if route.method == *request.method() {
+                &mut best
+            } else if route.method == HttpMethod::Get && *request.method() == HttpMethod::Head {
+                &mut head_fallback
+            } else {
+                continue;
+            };
+            if candidate.as_ref().map_or(true, |(r, _)| route.priority() > r.priority()) {
+                *candidate = Some((route, params));
+            }
+        }
+
+        let version = request.version();
+        let (handler, path_params) = match best.or(head_fallback) {
+            Some((route, params)) => (Some(route.handler.clone()), params),
+            None => (None, Vec::new()),
+        };
+        let route_request = RouteRequest {
+            request,
+            query,
+            path_params,
+        };
+        if let Some(handler) = handler {
+            return handler(&route_request);
+        }
+        if !allowed.is_empty() {
+            if allowed.contains(&HttpMethod::Get) && !allowed.contains(&HttpMethod::Head) {
+                allowed.push(HttpMethod::Head);
+            }
+            let allow: Vec<&str> = allowed.iter().map(|m| m.as_str()).collect();
+            let mut response = HttpRouter::text_response(version, HttpResponseStatus::METHOD_NOT_ALLOWED);
+            response.headers_mut().set(names::ALLOW, &allow.join(", "));
+            return response;
+        }
+        match self.not_found.as_ref() {
+            Some(not_found) => not_found(&route_request),
+            None => HttpRouter::text_response(version, HttpResponseStatus::NOT_FOUND),
+        }
+    }
+
+    fn text_response(version: HttpVersion, status: HttpResponseStatus) -> FullHttpResponse {
+        let mut response = FullHttpResponse::new(version, status.clone(), status.reason_phrase().as_bytes().to_vec());
+        response.headers_mut().set(names::CONTENT_TYPE, "text/plain; charset=utf-8");
+        response
+    }
+}
+
+impl ChannelInboundHandler for HttpRouter {
+    fn id(&self) -> String {
+        return "HttpRouter".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        let request = match message.downcast_mut::<FullHttpRequest>() {
+            Some(request) => std::mem::replace(request, FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/", Vec::new())),
+            None => {
+                channel_handler_ctx.fire_channel_read(message);
+                return;
+            }
+        };
+        let mut response = self.dispatch(request);
+        if response.content_length().is_none() && !response.is_transfer_encoding_chunked() {
+            let length = response.content().len() as u64;
+            response.set_content_length(length);
+        }
+        channel_handler_ctx.write_and_flush(&mut response);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
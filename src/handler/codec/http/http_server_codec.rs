@@ -0,0 +1,71 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoderHandler;
+use crate::handler::codec::http::http_request_decoder::HttpRequestDecoder;
+use crate::handler::codec::http::http_response_encoder::HttpResponseEncoder;
+use crate::handler::combined_channel_duplex_handler::CombinedChannelDuplexHandler;
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+
+///
+/// HttpRequestDecoder 和 HttpResponseEncoder 的组合
+///
+/// 记录每个流水线请求的 keep-alive 和方法: 请求不保持连接或响应没有确定的长度时,
+/// 响应加上 `Connection: close` 并在写完后关闭连接; HEAD 请求的响应不写消息体
+///
+/// ```ignore
+/// bootstrap.initialize_pipeline(|inbound, outbound| {
+///     HttpServerCodec::new().max_header_size(16 * 1024).add_last(inbound, outbound);
+///     inbound.add_last(Box::new(HttpServerExpectContinueHandler::new()));
+///     inbound.add_last(Box::new(BizHandler::new()));
+/// });
+/// ```
+///
+pub struct HttpServerCodec {
+    decoder: HttpRequestDecoder,
+}
+
+impl HttpServerCodec {
+    pub fn new() -> Self {
+        HttpServerCodec {
+            decoder: HttpRequestDecoder::new(),
+        }
+    }
+
+    pub fn max_initial_line_length(mut self, max_initial_line_length: usize) -> Self {
+        self.decoder = self.decoder.max_initial_line_length(max_initial_line_length);
+        self
+    }
+
+    pub fn max_header_size(mut self, max_header_size: usize) -> Self {
+        self.decoder = self.decoder.max_header_size(max_header_size);
+        self
+    }
+
+    pub fn max_header_count(mut self, max_header_count: usize) -> Self {
+        self.decoder = self.decoder.max_header_count(max_header_count);
+        self
+    }
+
+    pub fn max_chunk_size(mut self, max_chunk_size: usize) -> Self {
+        self.decoder = self.decoder.max_chunk_size(max_chunk_size);
+        self
+    }
+
+    pub fn into_handler(self) -> CombinedChannelDuplexHandler {
+        let pending_requests = Arc::new(Mutex::new(VecDeque::new()));
+        let mut decoder = self.decoder;
+        decoder.pending_requests = Some(pending_requests.clone());
+        let mut encoder = HttpResponseEncoder::new();
+        encoder.pending_requests = Some(pending_requests);
+        CombinedChannelDuplexHandler::new("HttpServerCodec", Box::new(ByteToMessageDecoderHandler::new(decoder)), Box::new(encoder))
+    }
+
+    pub fn add_last(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_last(inbound_pipe, outbound_pipe);
+    }
+
+    pub fn add_first(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_first(inbound_pipe, outbound_pipe);
+    }
+}
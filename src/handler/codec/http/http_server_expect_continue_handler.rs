@@ -0,0 +1,76 @@
+use std::any::Any;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{HttpMessage, HttpRequest, HttpResponse, HttpResponseStatus, HttpVersion, LastHttpContent};
+use crate::handler::codec::http::http_request_decoder::HttpDecoderState;
+use crate::handler::handler::ChannelInboundHandler;
+
+///
+/// 处理 HTTP/1.1 请求的 `Expect` header, 放在 HttpServerCodec 后面
+///
+/// `Expect: 100-continue` 时先响应 `100 Continue` 再把去掉 Expect 的请求传下去,
+/// 其它期望响应 `417 Expectation Failed` 并丢弃这个请求和它的消息体
+///
+pub struct HttpServerExpectContinueHandler {
+    ///
+    /// 正在丢弃被拒绝的请求的消息体
+    ///
+    discarding: bool,
+}
+
+impl HttpServerExpectContinueHandler {
+    pub fn new() -> Self {
+        HttpServerExpectContinueHandler {
+            discarding: false,
+        }
+    }
+}
+
+impl ChannelInboundHandler for HttpServerExpectContinueHandler {
+    fn id(&self) -> String {
+        return "HttpServerExpectContinueHandler".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        self.discarding = false;
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(request) = message.downcast_mut::<HttpRequest>() {
+            self.discarding = false;
+            // HTTP/1.0 的请求忽略 Expect
+            if request.version() == HttpVersion::Http11 && request.headers().contains(names::EXPECT) {
+                if request.is_100_continue_expected() {
+                    channel_handler_ctx.write_and_flush(&mut HttpResponse::new(HttpVersion::Http11, HttpResponseStatus::CONTINUE));
+                    channel_handler_ctx.write_and_flush(&mut LastHttpContent::empty());
+                    request.headers_mut().remove(names::EXPECT);
+                } else {
+                    let mut response = HttpResponse::new(HttpVersion::Http11, HttpResponseStatus::EXPECTATION_FAILED);
+                    response.set_content_length(0);
+                    channel_handler_ctx.write_and_flush(&mut response);
+                    channel_handler_ctx.write_and_flush(&mut LastHttpContent::empty());
+                    HttpDecoderState::set_expectation_failed(&channel_handler_ctx.channel());
+                    self.discarding = true;
+                    return;
+                }
+            }
+        } else if self.discarding {
+            if message.is::<LastHttpContent>() {
+                self.discarding = false;
+            }
+            return;
+        }
+        channel_handler_ctx.fire_channel_read(message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
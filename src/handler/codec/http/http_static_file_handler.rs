@@ -0,0 +1,350 @@
+use std::any::Any;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpMessage, HttpMethod, HttpResponse, HttpResponseStatus, HttpVersion, LastHttpContent};
+use crate::handler::codec::http::query_string_decoder::QueryStringDecoder;
+use crate::handler::handler::ChannelInboundHandler;
+use crate::transport::file_region::FileRegion;
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+///
+/// 从根目录提供静态文件, 放在 HttpServerCodec 和 HttpObjectAggregator 后面
+///
+/// 支持 GET/HEAD、单个 `Range`、`If-Range`、`If-None-Match`/`ETag` 和 `If-Modified-Since`,
+/// 文件内容以 `FileRegion` 写出, 由 TailHandler 用 sendfile 发送; 路径中的 `..` 和指向根目录外面的符号链接响应 `403`
+///
+/// ```ignore
+/// bootstrap.initialize_pipeline(|inbound, outbound| {
+///     HttpServerCodec::new().add_last(inbound, outbound);
+///     inbound.add_last(Box::new(HttpObjectAggregator::new(8192)));
+///     inbound.add_last(Box::new(HttpStaticFileHandler::new("/var/firmware").prefix("/firmware")));
+/// });
+/// ```
+///
+/// 设置了prefix 时不在prefix 下的请求原样传给下一个handler, 可以和 HttpRouter 一起使用
+///
+pub struct HttpStaticFileHandler {
+    root: PathBuf,
+    prefix: String,
+}
+
+impl HttpStaticFileHandler {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        HttpStaticFileHandler {
+            root: root.into(),
+            prefix: String::new(),
+        }
+    }
+
+    ///
+    /// 只处理这个路径下的请求, 例如 `/static` 对应 `/static/a.js`
+    ///
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.trim_end_matches('/').to_owned();
+        self
+    }
+
+    ///
+    /// 去掉prefix 后的相对路径, 不在prefix 下返回None
+    ///
+    fn relative_path<'a>(&self, path: &'a str) -> Option<&'a str> {
+        let rest = path.strip_prefix(self.prefix.as_str())?;
+        if rest.is_empty() || rest.starts_with('/') {
+            Some(rest)
+        } else {
+            None
+        }
+    }
+
+    ///
+    /// 把相对路径解析到根目录下的文件, 不允许离开根目录
+    ///
+    fn resolve(&self, relative: &str) -> Result<PathBuf, HttpResponseStatus> {
+        let mut path = self.root.clone();
+        for segment in relative.split('/') {
+            if segment.is_empty() || segment == "." {
+                continue;
+            }
+            if segment == ".." || segment.contains('\\') || segment.contains('\0') {
+                return Err(HttpResponseStatus::FORBIDDEN);
+            }
+            path.push(segment);
+        }
+        let root = self.root.canonicalize().map_err(|_| HttpResponseStatus::NOT_FOUND)?;
+        let path = path.canonicalize().map_err(|_| HttpResponseStatus::NOT_FOUND)?;
+        // 符号链接不能指到根目录外面
+        if !path.starts_with(&root) {
+            return Err(HttpResponseStatus::FORBIDDEN);
+        }
+        Ok(path)
+    }
+
+    fn serve(&self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, request: &FullHttpRequest, relative: &str) {
+        let version = request.version();
+        let head_request = *request.method() == HttpMethod::Head;
+        if !head_request && *request.method() != HttpMethod::Get {
+            let mut response = error_response(version, HttpResponseStatus::METHOD_NOT_ALLOWED);
+            response.headers_mut().set(names::ALLOW, "GET, HEAD");
+            channel_handler_ctx.write_and_flush(&mut response);
+            return;
+        }
+        let path = match self.resolve(relative) {
+            Ok(path) => path,
+            Err(status) => {
+                channel_handler_ctx.write_and_flush(&mut error_response(version, status));
+                return;
+            }
+        };
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => {
+                channel_handler_ctx.write_and_flush(&mut error_response(version, HttpResponseStatus::NOT_FOUND));
+                return;
+            }
+        };
+        let metadata = match file.metadata() {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => {
+                channel_handler_ctx.write_and_flush(&mut error_response(version, HttpResponseStatus::FORBIDDEN));
+                return;
+            }
+            Err(_) => {
+                channel_handler_ctx.write_and_flush(&mut error_response(version, HttpResponseStatus::NOT_FOUND));
+                return;
+            }
+        };
+
+        let len = metadata.len();
+        let modified = metadata.modified().ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+        let etag = format!("\"{:x}-{:x}\"", len, modified.unwrap_or(0));
+
+        if is_not_modified(request, &etag, modified) {
+            let mut response = HttpResponse::new(version, HttpResponseStatus::NOT_MODIFIED);
+            set_validators(&mut response, &etag, modified);
+            channel_handler_ctx.write_and_flush(&mut response);
+            channel_handler_ctx.write_and_flush(&mut LastHttpContent::empty());
+            return;
+        }
+
+        let mut range = None;
+        if let Some(value) = request.headers().get(names::RANGE) {
+            if is_range_fresh(request, &etag, modified) {
+                match parse_range(value, len) {
+                    Ok(r) => range = r,
+                    Err(()) => {
+                        let mut response = HttpResponse::new(version, HttpResponseStatus::REQUESTED_RANGE_NOT_SATISFIABLE);
+                        response.headers_mut().set(names::CONTENT_RANGE, &format!("bytes */{}", len));
+                        response.set_content_length(0);
+                        channel_handler_ctx.write_and_flush(&mut response);
+                        channel_handler_ctx.write_and_flush(&mut LastHttpContent::empty());
+                        return;
+                    }
+                }
+            }
+        }
+
+        let (status, position, count) = match range {
+            Some((start, end)) => (HttpResponseStatus::PARTIAL_CONTENT, start, end - start + 1),
+            None => (HttpResponseStatus::OK, 0, len),
+        };
+        let mut response = HttpResponse::new(version, status);
+        response.headers_mut().set(names::CONTENT_TYPE, guess_mime_type(&path));
+        response.headers_mut().set(names::ACCEPT_RANGES, "bytes");
+        set_validators(&mut response, &etag, modified);
+        if let Some((start, end)) = range {
+            response.headers_mut().set(names::CONTENT_RANGE, &format!("bytes {}-{}/{}", start, end, len));
+        }
+        response.set_content_length(count);
+        channel_handler_ctx.write_and_flush(&mut response);
+        if !head_request && count > 0 {
+            channel_handler_ctx.write_and_flush(&mut FileRegion::new(file, position, count));
+        }
+        channel_handler_ctx.write_and_flush(&mut LastHttpContent::empty());
+    }
+}
+
+impl ChannelInboundHandler for HttpStaticFileHandler {
+    fn id(&self) -> String {
+        return "HttpStaticFileHandler".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        let request = match message.downcast_ref::<FullHttpRequest>() {
+            Some(request) => request,
+            None => {
+                channel_handler_ctx.fire_channel_read(message);
+                return;
+            }
+        };
+        let decoder = QueryStringDecoder::new(request.uri());
+        match self.relative_path(decoder.path()) {
+            Some(relative) => self.serve(channel_handler_ctx, request, relative),
+            None => channel_handler_ctx.fire_channel_read(message),
+        }
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
+
+
+fn error_response(version: HttpVersion, status: HttpResponseStatus) -> FullHttpResponse {
+    let mut response = FullHttpResponse::new(version, status.clone(), status.reason_phrase().as_bytes().to_vec());
+    response.headers_mut().set(names::CONTENT_TYPE, "text/plain; charset=utf-8");
+    response.set_content_length(response.content().len() as u64);
+    response
+}
+
+fn set_validators(response: &mut HttpResponse, etag: &str, modified: Option<i64>) {
+    response.headers_mut().set(names::ETAG, etag);
+    if let Some(modified) = modified {
+        response.headers_mut().set(names::LAST_MODIFIED, &format_http_date(modified));
+    }
+}
+
+///
+/// 有 If-None-Match 时只比较ETag, 否则比较 If-Modified-Since
+///
+fn is_not_modified(request: &FullHttpRequest, etag: &str, modified: Option<i64>) -> bool {
+    if let Some(value) = request.headers().get(names::IF_NONE_MATCH) {
+        return value.trim() == "*" || value.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag.strip_prefix("W/").unwrap_or(tag) == etag
+        });
+    }
+    match (request.headers().get(names::IF_MODIFIED_SINCE).and_then(parse_http_date), modified) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+///
+/// If-Range 与当前文件不一致时忽略 Range, 返回整个文件
+///
+fn is_range_fresh(request: &FullHttpRequest, etag: &str, modified: Option<i64>) -> bool {
+    let value = match request.headers().get(names::IF_RANGE) {
+        Some(value) => value.trim(),
+        None => return true,
+    };
+    if value.starts_with('"') {
+        return value == etag;
+    }
+    match (parse_http_date(value), modified) {
+        (Some(date), Some(modified)) => modified <= date,
+        _ => false,
+    }
+}
+
+///
+/// 解析单个字节范围, 返回闭区间; 语法不对或者有多个范围时返回 Ok(None) 忽略, 范围不可满足时返回 Err
+///
+fn parse_range(value: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let value = value.trim();
+    if value.len() < 6 || !value[..6].eq_ignore_ascii_case("bytes=") {
+        return Ok(None);
+    }
+    let spec = value[6..].trim();
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let dash = match spec.find('-') {
+        Some(dash) => dash,
+        None => return Ok(None),
+    };
+    let (first, last) = (spec[..dash].trim(), spec[dash + 1..].trim());
+    if first.is_empty() {
+        // 最后n 个字节
+        let suffix = match last.parse::<u64>() {
+            Ok(suffix) => suffix,
+            Err(_) => return Ok(None),
+        };
+        if suffix == 0 || len == 0 {
+            return Err(());
+        }
+        return Ok(Some((len.saturating_sub(suffix), len - 1)));
+    }
+    let start = match first.parse::<u64>() {
+        Ok(start) => start,
+        Err(_) => return Ok(None),
+    };
+    let end = if last.is_empty() {
+        u64::MAX
+    } else {
+        match last.parse::<u64>() {
+            Ok(end) if end >= start => end,
+            _ => return Ok(None),
+        }
+    };
+    if start >= len {
+        return Err(());
+    }
+    Ok(Some((start, end.min(len - 1))))
+}
+
+fn format_http_date(secs: i64) -> String {
+    match Utc.timestamp_opt(secs, 0).single() {
+        Some(time) => time.format(HTTP_DATE_FORMAT).to_string(),
+        None => String::new(),
+    }
+}
+
+fn parse_http_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value, HTTP_DATE_FORMAT).map(|t| Utc.from_utc_datetime(&t).timestamp())
+        .or_else(|_| DateTime::parse_from_rfc2822(value).map(|t| t.timestamp()))
+        .ok()
+}
+
+///
+/// 按扩展名猜 Content-Type, 不认识的返回 `application/octet-stream`
+///
+pub fn guess_mime_type(path: &Path) -> &'static str {
+    let extension = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => extension.to_ascii_lowercase(),
+        None => return "application/octet-stream",
+    };
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" | "log" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "wasm" => "application/wasm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,20 @@
+pub mod http_client_codec;
+pub mod http_connection_pool;
+pub mod http_content_coding;
+pub mod http_content_compressor;
+pub mod http_content_decompressor;
+pub mod http_headers;
+pub mod http_message;
+pub mod http_object_aggregator;
+pub(crate) mod http_object_decoder;
+pub(crate) mod http_object_encoder;
+pub mod http_request_decoder;
+pub mod http_request_encoder;
+pub mod http_response_decoder;
+pub mod http_response_encoder;
+pub mod http_router;
+pub mod http_server_codec;
+pub mod http_server_expect_continue_handler;
+pub mod http_static_file_handler;
+pub mod query_string_decoder;
+pub mod websocket;
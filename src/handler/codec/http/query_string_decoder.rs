@@ -0,0 +1,104 @@
+///
+/// 把请求的uri 拆成解码后的路径和查询参数
+///
+/// ```ignore
+/// let decoder = QueryStringDecoder::new("/users/a%20b?tag=x&tag=y&q=1+2");
+/// assert_eq!(decoder.path(), "/users/a b");
+/// assert_eq!(decoder.parameter_all("tag"), vec!["x", "y"]);
+/// assert_eq!(decoder.parameter("q"), Some("1 2"));
+/// ```
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct QueryStringDecoder {
+    raw_path: String,
+    path: String,
+    parameters: Vec<(String, String)>,
+}
+
+impl QueryStringDecoder {
+    pub fn new(uri: &str) -> QueryStringDecoder {
+        // 去掉 fragment
+        let uri = uri.split('#').next().unwrap_or("");
+        let (raw_path, query) = match uri.find('?') {
+            Some(i) => (&uri[..i], &uri[i + 1..]),
+            None => (uri, ""),
+        };
+        QueryStringDecoder {
+            raw_path: raw_path.to_owned(),
+            path: decode_component(raw_path, false),
+            parameters: decode_parameters(query),
+        }
+    }
+
+    ///
+    /// 没有解码的路径
+    ///
+    pub fn raw_path(&self) -> &str {
+        &self.raw_path
+    }
+
+    ///
+    /// 百分号解码后的路径
+    ///
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn parameters(&self) -> &[(String, String)] {
+        &self.parameters
+    }
+
+    ///
+    /// 第一个同名参数的值
+    ///
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn parameter_all(&self, name: &str) -> Vec<&str> {
+        self.parameters.iter().filter(|(n, _)| n == name).map(|(_, v)| v.as_str()).collect()
+    }
+}
+
+fn decode_parameters(query: &str) -> Vec<(String, String)> {
+    query.split(|c| c == '&' || c == ';')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(i) => (decode_component(&pair[..i], true), decode_component(&pair[i + 1..], true)),
+            None => (decode_component(pair, true), String::new()),
+        })
+        .collect()
+}
+
+///
+/// 百分号解码, 查询参数里的 `+` 解码成空格, 非法的转义原样保留, 非UTF-8 的字节替换为 U+FFFD
+///
+pub(crate) fn decode_component(s: &str, plus_as_space: bool) -> String {
+    if !s.contains('%') && !(plus_as_space && s.contains('+')) {
+        return s.to_owned();
+    }
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() && hex_value(bytes[i + 1]).is_some() && hex_value(bytes[i + 2]).is_some() => {
+                decoded.push(hex_value(bytes[i + 1]).unwrap() << 4 | hex_value(bytes[i + 2]).unwrap());
+                i += 3;
+            }
+            b'+' if plus_as_space => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn hex_value(b: u8) -> Option<u8> {
+    (b as char).to_digit(16).map(|v| v as u8)
+}
@@ -0,0 +1,7 @@
+pub mod websocket_client_handshaker;
+pub mod websocket_client_protocol_handler;
+pub mod websocket_frame;
+pub mod websocket_permessage_deflate;
+pub mod websocket_protocol;
+pub mod websocket_server_handshaker;
+pub mod websocket_server_protocol_handler;
@@ -0,0 +1,173 @@
+use std::io::ErrorKind;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::codec::http::http_headers::{names, values, HttpHeaders};
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpMessage, HttpMethod, HttpResponseStatus, HttpVersion};
+use crate::handler::codec::http::websocket::websocket_permessage_deflate::{DeflateParams, PerMessageDeflate};
+use crate::handler::codec::http::websocket::websocket_server_handshaker::{accept_key, WEBSOCKET_VERSION};
+
+///
+/// 生成 WebSocket 升级请求并校验server 的响应
+///
+/// 每次生成请求时使用新的随机 `Sec-WebSocket-Key`; 响应必须是 `101 Switching Protocols`,
+/// 带有 `Upgrade: websocket`、`Connection: Upgrade` 和对应的 `Sec-WebSocket-Accept`,
+/// 子协议和扩展必须是请求中提议过的, 否则返回 `WebSocketHandshakeException`
+///
+/// ```ignore
+/// let mut handshaker = WebSocketClientHandshaker::new("ws://127.0.0.1:8080/ws").subprotocols(&["chat"]);
+/// channel.write_and_flush(handshaker.new_handshake_request());
+/// // 收到 FullHttpResponse 后
+/// handshaker.finish_handshake(&response)?;
+/// ```
+///
+pub struct WebSocketClientHandshaker {
+    ///
+    /// 请求行里的路径和查询参数
+    ///
+    path: String,
+    ///
+    /// Host header, 默认端口时不带端口
+    ///
+    host: String,
+    subprotocols: Vec<String>,
+    custom_headers: HttpHeaders,
+    permessage_deflate: Option<PerMessageDeflate>,
+    ///
+    /// 最近一次请求的key 对应的 Sec-WebSocket-Accept
+    ///
+    expected_accept: Option<String>,
+    subprotocol: Option<String>,
+    pub(crate) deflate_params: Option<DeflateParams>,
+}
+
+impl WebSocketClientHandshaker {
+    ///
+    /// uri 形如 `ws://host:port/path?query`, 端口默认80
+    ///
+    pub fn new(uri: &str) -> Self {
+        let rest = uri.strip_prefix("ws://");
+        assert!(rest.is_some(), "websocket uri must start with ws://: {}", uri);
+        let rest = rest.unwrap();
+        let (authority, path) = match rest.find(&['/', '?'][..]) {
+            Some(i) if rest[i..].starts_with('?') => (&rest[..i], format!("/{}", &rest[i..])),
+            Some(i) => (&rest[..i], rest[i..].to_owned()),
+            None => (rest, "/".to_owned()),
+        };
+        assert!(!authority.is_empty(), "websocket uri has no host: {}", uri);
+        let host = match authority.strip_suffix(":80") {
+            Some(host) => host.to_owned(),
+            None => authority.to_owned(),
+        };
+        WebSocketClientHandshaker {
+            path,
+            host,
+            subprotocols: Vec::new(),
+            custom_headers: HttpHeaders::new(),
+            permessage_deflate: None,
+            expected_accept: None,
+            subprotocol: None,
+            deflate_params: None,
+        }
+    }
+
+    ///
+    /// 按优先级提议的子协议
+    ///
+    pub fn subprotocols(mut self, subprotocols: &[&str]) -> Self {
+        self.subprotocols = subprotocols.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    ///
+    /// 握手请求额外的header, 例如 Origin 和 Authorization
+    ///
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.custom_headers.add(name, value);
+        self
+    }
+
+    ///
+    /// 提议 permessage-deflate 压缩扩展, server 不接受时不压缩
+    ///
+    pub fn permessage_deflate(mut self, permessage_deflate: PerMessageDeflate) -> Self {
+        self.permessage_deflate = Some(permessage_deflate);
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    ///
+    /// server 选择的子协议, 握手完成后才有值
+    ///
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
+    pub fn new_handshake_request(&mut self) -> FullHttpRequest {
+        let key = base64::encode(uuid::Uuid::new_v4().as_bytes());
+        self.expected_accept = Some(accept_key(&key));
+        self.subprotocol = None;
+        self.deflate_params = None;
+
+        let mut request = FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, &self.path, Vec::new());
+        for (name, value) in self.custom_headers.iter() {
+            request.headers_mut().add(name, value);
+        }
+        request.headers_mut()
+            .set(names::HOST, &self.host)
+            .set(names::UPGRADE, values::WEBSOCKET)
+            .set(names::CONNECTION, "Upgrade")
+            .set(names::SEC_WEBSOCKET_KEY, &key)
+            .set(names::SEC_WEBSOCKET_VERSION, WEBSOCKET_VERSION);
+        if !self.subprotocols.is_empty() {
+            request.headers_mut().set(names::SEC_WEBSOCKET_PROTOCOL, &self.subprotocols.join(", "));
+        }
+        if let Some(deflate) = &self.permessage_deflate {
+            request.headers_mut().set(names::SEC_WEBSOCKET_EXTENSIONS, &deflate.offer());
+        }
+        request
+    }
+
+    ///
+    /// 校验升级响应, 记录server 选择的子协议和接受的扩展
+    ///
+    pub fn finish_handshake(&mut self, response: &FullHttpResponse) -> Result<(), RettyErrorKind> {
+        if *response.status() != HttpResponseStatus::SWITCHING_PROTOCOLS {
+            return Err(handshake_error(format!("invalid handshake response status: {}", response.status())));
+        }
+        let headers = response.headers();
+        if !headers.contains_value(names::UPGRADE, values::WEBSOCKET) {
+            return Err(handshake_error(format!("invalid handshake response upgrade: {:?}", headers.get(names::UPGRADE))));
+        }
+        if !headers.contains_value(names::CONNECTION, values::UPGRADE) {
+            return Err(handshake_error(format!("invalid handshake response connection: {:?}", headers.get(names::CONNECTION))));
+        }
+        let accept = headers.get(names::SEC_WEBSOCKET_ACCEPT).map(|a| a.trim());
+        if self.expected_accept.is_none() || accept != self.expected_accept.as_deref() {
+            return Err(handshake_error(format!("invalid handshake response Sec-WebSocket-Accept: {:?}", accept)));
+        }
+        let subprotocol = headers.get(names::SEC_WEBSOCKET_PROTOCOL).map(|p| p.trim().to_owned());
+        if let Some(subprotocol) = &subprotocol {
+            if !self.subprotocols.contains(subprotocol) {
+                return Err(handshake_error(format!("server selected a subprotocol that was not requested: {}", subprotocol)));
+            }
+        }
+        let deflate_params = match &self.permessage_deflate {
+            Some(deflate) => deflate.accept_response(headers).map_err(handshake_error)?,
+            None if headers.contains(names::SEC_WEBSOCKET_EXTENSIONS) => {
+                return Err(handshake_error(format!("server accepted an extension that was not requested: {:?}", headers.get(names::SEC_WEBSOCKET_EXTENSIONS))));
+            }
+            None => None,
+        };
+        self.expected_accept = None;
+        self.subprotocol = subprotocol;
+        self.deflate_params = deflate_params;
+        Ok(())
+    }
+}
+
+fn handshake_error(message: String) -> RettyErrorKind {
+    RettyErrorKind::new(ErrorKind::InvalidData, format!("WebSocketHandshakeException: {}", message))
+}
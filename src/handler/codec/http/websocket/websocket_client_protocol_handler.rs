@@ -0,0 +1,161 @@
+use std::any::Any;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoderHandler;
+use crate::handler::codec::http::http_message::{FullHttpResponse, HttpMessage, HttpResponseStatus};
+use crate::handler::codec::http::websocket::websocket_client_handshaker::WebSocketClientHandshaker;
+use crate::handler::codec::http::websocket::websocket_permessage_deflate::PerMessageDeflate;
+use crate::handler::codec::http::websocket::websocket_protocol::{new_state, SharedWebSocketState, WebSocketFrameDecoder, WebSocketFrameEncoder, WebSocketHandshakeComplete};
+use crate::handler::combined_channel_duplex_handler::CombinedChannelDuplexHandler;
+use crate::handler::handler::ChannelInboundHandler;
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+
+///
+/// 连接建立后发送 WebSocket 升级请求, 放在 HttpClientCodec 和 HttpObjectAggregator 后面
+///
+/// 握手成功后下一个handler 先收到 `WebSocketHandshakeComplete`, 之后收到 `WebSocketFrame`;
+/// 发送的帧使用随机掩码, 收到 `WebSocketHandshakeComplete` 之后才能写出 `WebSocketFrame`;
+/// 握手失败时触发 `WebSocketHandshakeException` 异常并关闭连接
+///
+/// 前面的HTTP handler 不会从pipeline 中移除: 在handler 里修改pipeline 会死锁, 所以 HttpClientCodec
+/// 在101 之后把字节原样传过来, 由这个handler 内部的帧解码器解码
+///
+/// ```ignore
+/// let mut client = Bootstrap::new_client_bootstrap();
+/// client.worker_group(1).initialize_pipeline(|inbound, outbound| {
+///     HttpClientCodec::new().add_last(inbound, outbound);
+///     inbound.add_last(Box::new(HttpObjectAggregator::new(64 * 1024)));
+///     WebSocketClientProtocolHandler::new("ws://127.0.0.1:8080/ws")
+///         .permessage_deflate(PerMessageDeflate::new())
+///         .add_last(inbound, outbound);
+///     inbound.add_last(Box::new(ChatClientHandler::new()));
+/// });
+/// let channel = client.connect("127.0.0.1", 8080).unwrap();
+/// ```
+///
+pub struct WebSocketClientProtocolHandler {
+    handshaker: WebSocketClientHandshaker,
+    max_frame_size: usize,
+}
+
+impl WebSocketClientProtocolHandler {
+    ///
+    /// 默认消息最大 64KB
+    ///
+    pub fn new(uri: &str) -> Self {
+        WebSocketClientProtocolHandler {
+            handshaker: WebSocketClientHandshaker::new(uri),
+            max_frame_size: 65536,
+        }
+    }
+
+    pub fn subprotocols(mut self, subprotocols: &[&str]) -> Self {
+        self.handshaker = self.handshaker.subprotocols(subprotocols);
+        self
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.handshaker = self.handshaker.header(name, value);
+        self
+    }
+
+    pub fn permessage_deflate(mut self, permessage_deflate: PerMessageDeflate) -> Self {
+        self.handshaker = self.handshaker.permessage_deflate(permessage_deflate);
+        self
+    }
+
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        assert!(max_frame_size > 0, "max_frame_size must be a positive integer: {}", max_frame_size);
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn into_handler(self) -> CombinedChannelDuplexHandler {
+        let state = new_state();
+        let encoder = WebSocketFrameEncoder::new(true, state.clone());
+        let handler = WebSocketClientProtocolInboundHandler {
+            handshaker: self.handshaker,
+            max_frame_size: self.max_frame_size,
+            state,
+            decoder: None,
+        };
+        CombinedChannelDuplexHandler::new("WebSocketClientProtocolHandler", Box::new(handler), Box::new(encoder))
+    }
+
+    pub fn add_last(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_last(inbound_pipe, outbound_pipe);
+    }
+
+    pub fn add_first(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_first(inbound_pipe, outbound_pipe);
+    }
+}
+
+
+struct WebSocketClientProtocolInboundHandler {
+    handshaker: WebSocketClientHandshaker,
+    max_frame_size: usize,
+    state: SharedWebSocketState,
+    ///
+    /// 握手完成后创建, 帧解码器不替换进pipeline, 由这个handler 转发
+    ///
+    decoder: Option<ByteToMessageDecoderHandler<WebSocketFrameDecoder>>,
+}
+
+impl WebSocketClientProtocolInboundHandler {
+    fn finish_handshake(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, response: &FullHttpResponse) {
+        if let Err(e) = self.handshaker.finish_handshake(response) {
+            channel_handler_ctx.fire_channel_exception(e);
+            channel_handler_ctx.close();
+            return;
+        }
+        let inflater = self.handshaker.deflate_params.map(|params| {
+            let (deflater, inflater) = params.codec(false);
+            self.state.lock().unwrap().deflater = Some(deflater);
+            inflater
+        });
+        self.decoder = Some(ByteToMessageDecoderHandler::new(WebSocketFrameDecoder::new(false, self.max_frame_size, self.state.clone(), inflater)));
+        let subprotocol = self.handshaker.subprotocol().map(|p| p.to_owned());
+        let mut complete = WebSocketHandshakeComplete::new(self.handshaker.path(), response.headers().clone(), subprotocol);
+        channel_handler_ctx.fire_channel_read(&mut complete);
+    }
+}
+
+impl ChannelInboundHandler for WebSocketClientProtocolInboundHandler {
+    fn id(&self) -> String {
+        return "WebSocketClientProtocolHandler".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        let mut request = self.handshaker.new_handshake_request();
+        channel_handler_ctx.write_and_flush(&mut request);
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        match self.decoder.take() {
+            Some(mut decoder) => decoder.channel_inactive(channel_handler_ctx),
+            None => channel_handler_ctx.fire_channel_inactive(),
+        }
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.channel_read(channel_handler_ctx, message);
+            return;
+        }
+        if let Some(response) = message.downcast_ref::<FullHttpResponse>() {
+            // 1xx 之后还有升级响应
+            if !response.status().is_informational() || *response.status() == HttpResponseStatus::SWITCHING_PROTOCOLS {
+                self.finish_handshake(channel_handler_ctx, response);
+            }
+            return;
+        }
+        channel_handler_ctx.fire_channel_read(message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
@@ -0,0 +1,338 @@
+///
+/// RFC 6455 7.4.1 定义的关闭状态码
+///
+pub mod close_codes {
+    pub const NORMAL_CLOSURE: u16 = 1000;
+    pub const GOING_AWAY: u16 = 1001;
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    ///
+    /// 关闭帧没有状态码, 不能出现在发送的关闭帧里, 发送时写成空的关闭帧
+    ///
+    pub const NO_STATUS: u16 = 1005;
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    pub const POLICY_VIOLATION: u16 = 1008;
+    pub const MESSAGE_TOO_BIG: u16 = 1009;
+    pub const MANDATORY_EXTENSION: u16 = 1010;
+    pub const INTERNAL_ERROR: u16 = 1011;
+}
+
+pub(crate) const OPCODE_CONTINUATION: u8 = 0x0;
+pub(crate) const OPCODE_TEXT: u8 = 0x1;
+pub(crate) const OPCODE_BINARY: u8 = 0x2;
+pub(crate) const OPCODE_CLOSE: u8 = 0x8;
+pub(crate) const OPCODE_PING: u8 = 0x9;
+pub(crate) const OPCODE_PONG: u8 = 0xA;
+
+///
+/// permessage-deflate 用RSV1 标记压缩的消息
+///
+pub(crate) const RSV1: u8 = 0x4;
+
+///
+/// 控制帧的负载最多125 字节
+///
+pub(crate) const MAX_CONTROL_PAYLOAD: usize = 125;
+
+
+///
+/// WebSocket 消息, 分片的文本和二进制消息收齐后才交给handler
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WebSocketFrame {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong(Vec<u8>),
+    ///
+    /// 状态码和原因, 对端发送空的关闭帧时状态码是 `NO_STATUS`
+    ///
+    Close(u16, String),
+}
+
+impl WebSocketFrame {
+    pub fn close(code: u16, reason: &str) -> WebSocketFrame {
+        WebSocketFrame::Close(code, reason.to_owned())
+    }
+
+    pub fn is_control(&self) -> bool {
+        matches!(self, WebSocketFrame::Ping(_) | WebSocketFrame::Pong(_) | WebSocketFrame::Close(..))
+    }
+
+    pub(crate) fn opcode(&self) -> u8 {
+        match self {
+            WebSocketFrame::Text(_) => OPCODE_TEXT,
+            WebSocketFrame::Binary(_) => OPCODE_BINARY,
+            WebSocketFrame::Ping(_) => OPCODE_PING,
+            WebSocketFrame::Pong(_) => OPCODE_PONG,
+            WebSocketFrame::Close(..) => OPCODE_CLOSE,
+        }
+    }
+
+    ///
+    /// 帧的负载, 控制帧超过125 字节时截断
+    ///
+    pub(crate) fn payload(&self) -> Vec<u8> {
+        let mut payload = match self {
+            WebSocketFrame::Text(text) => return text.as_bytes().to_vec(),
+            WebSocketFrame::Binary(data) => return data.clone(),
+            WebSocketFrame::Ping(data) | WebSocketFrame::Pong(data) => data.clone(),
+            WebSocketFrame::Close(code, _) if *code == close_codes::NO_STATUS => Vec::new(),
+            WebSocketFrame::Close(code, reason) => {
+                let mut payload = code.to_be_bytes().to_vec();
+                payload.extend_from_slice(reason.as_bytes());
+                payload
+            }
+        };
+        if payload.len() > MAX_CONTROL_PAYLOAD {
+            let mut end = MAX_CONTROL_PAYLOAD;
+            if let WebSocketFrame::Close(..) = self {
+                // 原因必须是完整的UTF-8
+                while end > 2 && (payload[end] & 0xC0) == 0x80 {
+                    end -= 1;
+                }
+            }
+            payload.truncate(end);
+        }
+        payload
+    }
+}
+
+
+///
+/// 线路上的一帧
+///
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct RawFrame {
+    pub(crate) fin: bool,
+    ///
+    /// RSV1-3, 最高位是RSV1
+    ///
+    pub(crate) rsv: u8,
+    pub(crate) opcode: u8,
+    pub(crate) payload: Vec<u8>,
+}
+
+///
+/// 写出一帧, mask 不为空时按客户端的要求掩码负载
+///
+pub(crate) fn encode_frame(fin: bool, rsv: u8, opcode: u8, payload: &[u8], mask: Option<[u8; 4]>, out: &mut Vec<u8>) {
+    out.push(if fin { 0x80 } else { 0 } | (rsv & 0x7) << 4 | (opcode & 0xF));
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    let len = payload.len();
+    if len < 126 {
+        out.push(mask_bit | len as u8);
+    } else if len <= u16::MAX as usize {
+        out.push(mask_bit | 126);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(mask_bit | 127);
+        out.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+    match mask {
+        Some(mask) => {
+            out.extend_from_slice(&mask);
+            out.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        }
+        None => out.extend_from_slice(payload),
+    }
+}
+
+///
+/// 违反协议, 关闭连接时发送code
+///
+#[derive(Debug)]
+pub(crate) struct ProtocolViolation {
+    pub(crate) code: u16,
+    pub(crate) message: String,
+}
+
+impl ProtocolViolation {
+    pub(crate) fn new(code: u16, message: String) -> ProtocolViolation {
+        ProtocolViolation { code, message }
+    }
+}
+
+
+///
+/// 解析帧并把分片拼成完整的消息, 不保存字节, 半包由 ByteToMessageDecoderHandler 累积
+///
+/// 只在收齐一整帧后解析, 帧头里的长度超过 max_frame_size 时立即报错, 不等待负载
+///
+pub(crate) struct FrameReader {
+    ///
+    /// server 要求客户端的帧有掩码, client 要求服务端的帧没有掩码
+    ///
+    expect_masked: bool,
+    pub(crate) max_frame_size: usize,
+    ///
+    /// 协商的扩展允许使用的RSV 位
+    ///
+    pub(crate) allowed_rsv: u8,
+    ///
+    /// 正在拼接的分片消息的第一帧, 负载是已经收到的所有分片
+    ///
+    fragmented: Option<RawFrame>,
+}
+
+impl FrameReader {
+    pub(crate) fn new(expect_masked: bool, max_frame_size: usize) -> FrameReader {
+        FrameReader {
+            expect_masked,
+            max_frame_size,
+            allowed_rsv: 0,
+            fragmented: None,
+        }
+    }
+
+    ///
+    /// 从bytes 头部解析一帧, 返回消费的字节数, 不够一帧时返回 0;
+    /// 控制帧原样放进out, 数据帧拼好后以第一帧的opcode 和RSV 放入
+    ///
+    pub(crate) fn read(&mut self, bytes: &[u8], out: &mut Vec<RawFrame>) -> Result<usize, ProtocolViolation> {
+        let result = match self.parse_frame(bytes) {
+            Ok(Some((frame, consumed))) => self.assemble(frame, out).map(|_| consumed),
+            Ok(None) => Ok(0),
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            self.fragmented = None;
+        }
+        result
+    }
+
+    fn parse_frame(&self, bytes: &[u8]) -> Result<Option<(RawFrame, usize)>, ProtocolViolation> {
+        if bytes.len() < 2 {
+            return Ok(None);
+        }
+        let fin = bytes[0] & 0x80 != 0;
+        let rsv = (bytes[0] >> 4) & 0x7;
+        let opcode = bytes[0] & 0xF;
+        let masked = bytes[1] & 0x80 != 0;
+        if rsv & !self.allowed_rsv != 0 {
+            return Err(protocol_error(format!("RSV bits must be 0: {:#x}", rsv)));
+        }
+        if masked != self.expect_masked {
+            return Err(protocol_error(if masked { "received a masked frame from server" } else { "received an unmasked frame from client" }.to_string()));
+        }
+        if !matches!(opcode, OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY | OPCODE_CLOSE | OPCODE_PING | OPCODE_PONG) {
+            return Err(protocol_error(format!("reserved opcode: {}", opcode)));
+        }
+        let (len, mut pos) = match bytes[1] & 0x7F {
+            126 if bytes.len() < 4 => return Ok(None),
+            126 => (u16::from_be_bytes([bytes[2], bytes[3]]) as u64, 4),
+            127 if bytes.len() < 10 => return Ok(None),
+            127 => {
+                let mut len = [0u8; 8];
+                len.copy_from_slice(&bytes[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            len => (len as u64, 2),
+        };
+        if opcode >= OPCODE_CLOSE {
+            if !fin {
+                return Err(protocol_error("fragmented control frame".to_string()));
+            }
+            if rsv != 0 {
+                return Err(protocol_error(format!("RSV bits must be 0 on control frames: {:#x}", rsv)));
+            }
+            if len > MAX_CONTROL_PAYLOAD as u64 {
+                return Err(protocol_error(format!("control frame payload is longer than 125 bytes: {}", len)));
+            }
+        }
+        if len > self.max_frame_size as u64 {
+            return Err(ProtocolViolation::new(close_codes::MESSAGE_TOO_BIG,
+                                              format!("max frame length of {} has been exceeded", self.max_frame_size)));
+        }
+        let mask = if masked {
+            if bytes.len() < pos + 4 {
+                return Ok(None);
+            }
+            pos += 4;
+            Some([bytes[pos - 4], bytes[pos - 3], bytes[pos - 2], bytes[pos - 1]])
+        } else {
+            None
+        };
+        let len = len as usize;
+        if bytes.len() < pos + len {
+            return Ok(None);
+        }
+        let payload = match mask {
+            Some(mask) => bytes[pos..pos + len].iter().enumerate().map(|(i, b)| b ^ mask[i % 4]).collect(),
+            None => bytes[pos..pos + len].to_vec(),
+        };
+        Ok(Some((RawFrame { fin, rsv, opcode, payload }, pos + len)))
+    }
+
+    fn assemble(&mut self, frame: RawFrame, out: &mut Vec<RawFrame>) -> Result<(), ProtocolViolation> {
+        if frame.opcode >= OPCODE_CLOSE {
+            // 控制帧可以插在分片中间
+            out.push(frame);
+            return Ok(());
+        }
+        match (self.fragmented.as_mut(), frame.opcode) {
+            (None, OPCODE_CONTINUATION) => Err(protocol_error("continuation frame without a started message".to_string())),
+            (Some(_), OPCODE_TEXT) | (Some(_), OPCODE_BINARY) => Err(protocol_error("new data frame while a fragmented message is in progress".to_string())),
+            (None, _) if frame.fin => {
+                out.push(frame);
+                Ok(())
+            }
+            (None, _) => {
+                self.fragmented = Some(frame);
+                Ok(())
+            }
+            (Some(first), _) => {
+                if frame.rsv != 0 {
+                    return Err(protocol_error("RSV bits must be 0 on continuation frames".to_string()));
+                }
+                if first.payload.len() + frame.payload.len() > self.max_frame_size {
+                    return Err(ProtocolViolation::new(close_codes::MESSAGE_TOO_BIG,
+                                                      format!("max message length of {} has been exceeded", self.max_frame_size)));
+                }
+                first.payload.extend_from_slice(&frame.payload);
+                if frame.fin {
+                    let mut message = self.fragmented.take().unwrap();
+                    message.fin = true;
+                    out.push(message);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+///
+/// 把完整的帧转换成消息
+///
+pub(crate) fn to_websocket_frame(frame: RawFrame) -> Result<WebSocketFrame, ProtocolViolation> {
+    match frame.opcode {
+        OPCODE_TEXT => String::from_utf8(frame.payload)
+            .map(WebSocketFrame::Text)
+            .map_err(|_| ProtocolViolation::new(close_codes::INVALID_PAYLOAD, "text message is not valid UTF-8".to_string())),
+        OPCODE_BINARY => Ok(WebSocketFrame::Binary(frame.payload)),
+        OPCODE_PING => Ok(WebSocketFrame::Ping(frame.payload)),
+        OPCODE_PONG => Ok(WebSocketFrame::Pong(frame.payload)),
+        _ => parse_close(&frame.payload),
+    }
+}
+
+fn parse_close(payload: &[u8]) -> Result<WebSocketFrame, ProtocolViolation> {
+    if payload.is_empty() {
+        return Ok(WebSocketFrame::Close(close_codes::NO_STATUS, String::new()));
+    }
+    if payload.len() == 1 {
+        return Err(protocol_error("close frame payload is 1 byte".to_string()));
+    }
+    let code = u16::from_be_bytes([payload[0], payload[1]]);
+    let valid = matches!(code, 1000..=1003 | 1007..=1011 | 3000..=4999);
+    if !valid {
+        return Err(protocol_error(format!("invalid close status code: {}", code)));
+    }
+    let reason = String::from_utf8(payload[2..].to_vec())
+        .map_err(|_| ProtocolViolation::new(close_codes::INVALID_PAYLOAD, "close reason is not valid UTF-8".to_string()))?;
+    Ok(WebSocketFrame::Close(code, reason))
+}
+
+fn protocol_error(message: String) -> ProtocolViolation {
+    ProtocolViolation::new(close_codes::PROTOCOL_ERROR, message)
+}
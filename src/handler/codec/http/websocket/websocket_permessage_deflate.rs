@@ -0,0 +1,318 @@
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+
+use crate::handler::codec::http::http_headers::{names, HttpHeaders};
+use crate::handler::codec::http::websocket::websocket_frame::{close_codes, ProtocolViolation};
+
+pub(crate) const PERMESSAGE_DEFLATE: &str = "permessage-deflate";
+const SERVER_NO_CONTEXT_TAKEOVER: &str = "server_no_context_takeover";
+const CLIENT_NO_CONTEXT_TAKEOVER: &str = "client_no_context_takeover";
+const SERVER_MAX_WINDOW_BITS: &str = "server_max_window_bits";
+const CLIENT_MAX_WINDOW_BITS: &str = "client_max_window_bits";
+
+///
+/// 每条消息压缩后去掉、解压前补上的结尾
+///
+const DEFLATE_TAIL: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+type ExtensionParam = (String, Option<String>);
+
+
+///
+/// RFC 7692 `permessage-deflate` 扩展的配置, client 和 server 共用
+///
+/// 压缩使用32KB 的窗口, 不支持对端要求更小的 `server_max_window_bits` / `client_max_window_bits`:
+/// server 拒绝这样的提议, client 不提议 `client_max_window_bits`
+///
+/// ```ignore
+/// WebSocketServerProtocolHandler::new("/ws")
+///     .permessage_deflate(PerMessageDeflate::new().server_no_context_takeover(true))
+///     .add_last(inbound, outbound);
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct PerMessageDeflate {
+    compression_level: u32,
+    server_no_context_takeover: bool,
+    client_no_context_takeover: bool,
+}
+
+impl PerMessageDeflate {
+    ///
+    /// 默认压缩级别6, 双方都保留压缩上下文
+    ///
+    pub fn new() -> Self {
+        PerMessageDeflate {
+            compression_level: 6,
+            server_no_context_takeover: false,
+            client_no_context_takeover: false,
+        }
+    }
+
+    pub fn compression_level(mut self, compression_level: u32) -> Self {
+        assert!(compression_level <= 9, "compression_level must be in 0..=9: {}", compression_level);
+        self.compression_level = compression_level;
+        self
+    }
+
+    ///
+    /// server 每条消息重新开始压缩: client 在提议中请求, server 在响应中要求
+    ///
+    pub fn server_no_context_takeover(mut self, server_no_context_takeover: bool) -> Self {
+        self.server_no_context_takeover = server_no_context_takeover;
+        self
+    }
+
+    ///
+    /// client 每条消息重新开始压缩: client 在提议中声明, server 在响应中要求
+    ///
+    pub fn client_no_context_takeover(mut self, client_no_context_takeover: bool) -> Self {
+        self.client_no_context_takeover = client_no_context_takeover;
+        self
+    }
+
+    ///
+    /// client 握手请求的 Sec-WebSocket-Extensions
+    ///
+    pub(crate) fn offer(&self) -> String {
+        let mut offer = PERMESSAGE_DEFLATE.to_string();
+        if self.server_no_context_takeover {
+            offer.push_str("; ");
+            offer.push_str(SERVER_NO_CONTEXT_TAKEOVER);
+        }
+        if self.client_no_context_takeover {
+            offer.push_str("; ");
+            offer.push_str(CLIENT_NO_CONTEXT_TAKEOVER);
+        }
+        offer
+    }
+
+    ///
+    /// server 从client 的提议中选择第一个可以接受的 permessage-deflate
+    ///
+    pub(crate) fn accept_offer(&self, request_headers: &HttpHeaders) -> Option<DeflateParams> {
+        parse_extensions(request_headers).into_iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case(PERMESSAGE_DEFLATE))
+            .find_map(|(_, params)| self.accept_params(&params))
+    }
+
+    fn accept_params(&self, params: &[ExtensionParam]) -> Option<DeflateParams> {
+        let mut accepted = DeflateParams {
+            compression_level: self.compression_level,
+            server_no_context_takeover: self.server_no_context_takeover,
+            client_no_context_takeover: self.client_no_context_takeover,
+        };
+        if has_duplicates(params) {
+            return None;
+        }
+        for (name, value) in params {
+            match (name.as_str(), value) {
+                (SERVER_NO_CONTEXT_TAKEOVER, None) => accepted.server_no_context_takeover = true,
+                // client 允许server 要求它不保留上下文
+                (CLIENT_NO_CONTEXT_TAKEOVER, None) => accepted.client_no_context_takeover = true,
+                (SERVER_MAX_WINDOW_BITS, Some(bits)) if parse_window_bits(bits) == Some(15) => {}
+                // 不回应时client 仍然使用32KB 的窗口, 可以正常解压
+                (CLIENT_MAX_WINDOW_BITS, None) => {}
+                (CLIENT_MAX_WINDOW_BITS, Some(bits)) if parse_window_bits(bits).is_some() => {}
+                _ => return None,
+            }
+        }
+        Some(accepted)
+    }
+
+    ///
+    /// client 校验server 接受的参数, 不能接受时返回错误原因
+    ///
+    pub(crate) fn accept_response(&self, response_headers: &HttpHeaders) -> Result<Option<DeflateParams>, String> {
+        let extensions = parse_extensions(response_headers);
+        let (name, params) = match extensions.as_slice() {
+            [] => return Ok(None),
+            [extension] => extension,
+            _ => return Err(format!("server accepted more than one extension: {:?}", response_headers.get_all(names::SEC_WEBSOCKET_EXTENSIONS))),
+        };
+        if !name.eq_ignore_ascii_case(PERMESSAGE_DEFLATE) {
+            return Err(format!("server accepted an extension that was not offered: {}", name));
+        }
+        if has_duplicates(params) {
+            return Err("duplicate permessage-deflate parameter".to_string());
+        }
+        let mut accepted = DeflateParams {
+            compression_level: self.compression_level,
+            server_no_context_takeover: false,
+            client_no_context_takeover: self.client_no_context_takeover,
+        };
+        for (name, value) in params {
+            match (name.as_str(), value) {
+                (SERVER_NO_CONTEXT_TAKEOVER, None) => accepted.server_no_context_takeover = true,
+                (CLIENT_NO_CONTEXT_TAKEOVER, None) => accepted.client_no_context_takeover = true,
+                // 解压使用32KB 的窗口, server 使用更小的窗口也能解压
+                (SERVER_MAX_WINDOW_BITS, Some(bits)) if parse_window_bits(bits).is_some() => {}
+                _ => return Err(format!("unsupported permessage-deflate parameter: {}", name)),
+            }
+        }
+        if self.server_no_context_takeover && !accepted.server_no_context_takeover {
+            return Err("server did not accept server_no_context_takeover".to_string());
+        }
+        Ok(Some(accepted))
+    }
+}
+
+
+///
+/// 协商后的参数
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct DeflateParams {
+    compression_level: u32,
+    pub(crate) server_no_context_takeover: bool,
+    pub(crate) client_no_context_takeover: bool,
+}
+
+impl DeflateParams {
+    ///
+    /// server 握手响应的 Sec-WebSocket-Extensions
+    ///
+    pub(crate) fn response(&self) -> String {
+        let mut response = PERMESSAGE_DEFLATE.to_string();
+        if self.server_no_context_takeover {
+            response.push_str("; ");
+            response.push_str(SERVER_NO_CONTEXT_TAKEOVER);
+        }
+        if self.client_no_context_takeover {
+            response.push_str("; ");
+            response.push_str(CLIENT_NO_CONTEXT_TAKEOVER);
+        }
+        response
+    }
+
+    ///
+    /// 本端发送消息使用的压缩器和接收消息使用的解压器
+    ///
+    pub(crate) fn codec(&self, server: bool) -> (Deflater, Inflater) {
+        let (local_no_context, remote_no_context) = if server {
+            (self.server_no_context_takeover, self.client_no_context_takeover)
+        } else {
+            (self.client_no_context_takeover, self.server_no_context_takeover)
+        };
+        let deflater = Deflater {
+            compress: Compress::new(Compression::new(self.compression_level), false),
+            no_context_takeover: local_no_context,
+        };
+        let inflater = Inflater {
+            decompress: Decompress::new(false),
+            no_context_takeover: remote_no_context,
+        };
+        (deflater, inflater)
+    }
+}
+
+
+pub(crate) struct Deflater {
+    compress: Compress,
+    no_context_takeover: bool,
+}
+
+impl Deflater {
+    ///
+    /// 压缩一条消息, 以sync flush 结束并去掉结尾的 00 00 ff ff
+    ///
+    pub(crate) fn compress(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() / 2 + 64);
+        let start = self.compress.total_in();
+        loop {
+            if out.capacity() - out.len() < 64 {
+                out.reserve(out.capacity());
+            }
+            let consumed = (self.compress.total_in() - start) as usize;
+            // 输出缓冲区足够时不会失败
+            self.compress.compress_vec(&data[consumed..], &mut out, FlushCompress::Sync).unwrap();
+            let consumed = (self.compress.total_in() - start) as usize;
+            if consumed == data.len() && out.len() < out.capacity() {
+                break;
+            }
+        }
+        if out.ends_with(&DEFLATE_TAIL) {
+            out.truncate(out.len() - DEFLATE_TAIL.len());
+        }
+        if self.no_context_takeover {
+            self.compress.reset();
+        }
+        out
+    }
+}
+
+
+pub(crate) struct Inflater {
+    decompress: Decompress,
+    no_context_takeover: bool,
+}
+
+impl Inflater {
+    ///
+    /// 解压一条消息, 解压后超过 max_size 时返回1009
+    ///
+    pub(crate) fn decompress(&mut self, data: &[u8], max_size: usize) -> Result<Vec<u8>, ProtocolViolation> {
+        let mut input = Vec::with_capacity(data.len() + DEFLATE_TAIL.len());
+        input.extend_from_slice(data);
+        input.extend_from_slice(&DEFLATE_TAIL);
+        let mut out = Vec::with_capacity(data.len() * 2 + 64);
+        let start = self.decompress.total_in();
+        loop {
+            if out.len() > max_size {
+                return Err(ProtocolViolation::new(close_codes::MESSAGE_TOO_BIG,
+                                                  format!("max message length of {} has been exceeded after decompression", max_size)));
+            }
+            if out.capacity() - out.len() < 64 {
+                out.reserve(out.capacity());
+            }
+            let (before_in, before_out) = (self.decompress.total_in(), out.len());
+            let consumed = (before_in - start) as usize;
+            let status = self.decompress.decompress_vec(&input[consumed..], &mut out, FlushDecompress::Sync)
+                .map_err(|e| ProtocolViolation::new(close_codes::PROTOCOL_ERROR, format!("invalid deflate data: {}", e)))?;
+            let consumed = (self.decompress.total_in() - start) as usize;
+            if status == Status::StreamEnd || (consumed == input.len() && out.len() < out.capacity()) {
+                break;
+            }
+            if self.decompress.total_in() == before_in && out.len() == before_out {
+                return Err(ProtocolViolation::new(close_codes::PROTOCOL_ERROR, "invalid deflate data: no progress".to_string()));
+            }
+        }
+        if out.len() > max_size {
+            return Err(ProtocolViolation::new(close_codes::MESSAGE_TOO_BIG,
+                                              format!("max message length of {} has been exceeded after decompression", max_size)));
+        }
+        if self.no_context_takeover {
+            self.decompress.reset(false);
+        }
+        Ok(out)
+    }
+}
+
+
+///
+/// 解析 Sec-WebSocket-Extensions: `ext; param; param=value, ext2`, 参数名转成小写, 去掉值两边的引号
+///
+fn parse_extensions(headers: &HttpHeaders) -> Vec<(String, Vec<ExtensionParam>)> {
+    headers.get_all(names::SEC_WEBSOCKET_EXTENSIONS).iter()
+        .flat_map(|v| v.split(','))
+        .filter(|e| !e.trim().is_empty())
+        .map(|extension| {
+            let mut parts = extension.split(';').map(|p| p.trim());
+            let name = parts.next().unwrap_or("").to_owned();
+            let params = parts.filter(|p| !p.is_empty())
+                .map(|p| match p.find('=') {
+                    Some(i) => (p[..i].trim().to_ascii_lowercase(), Some(p[i + 1..].trim().trim_matches('"').to_owned())),
+                    None => (p.to_ascii_lowercase(), None),
+                })
+                .collect();
+            (name, params)
+        })
+        .collect()
+}
+
+fn has_duplicates(params: &[ExtensionParam]) -> bool {
+    params.iter().enumerate().any(|(i, (name, _))| params[..i].iter().any(|(n, _)| n == name))
+}
+
+fn parse_window_bits(bits: &str) -> Option<u8> {
+    bits.parse::<u8>().ok().filter(|b| (8..=15).contains(b))
+}
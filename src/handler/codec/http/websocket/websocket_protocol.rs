@@ -0,0 +1,244 @@
+use std::any::Any;
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoder;
+use crate::handler::codec::http::http_headers::HttpHeaders;
+use crate::handler::codec::http::websocket::websocket_frame::{close_codes, encode_frame, to_websocket_frame, FrameReader, ProtocolViolation, RawFrame, WebSocketFrame, RSV1};
+use crate::handler::codec::http::websocket::websocket_permessage_deflate::{Deflater, Inflater};
+use crate::handler::handler::ChannelOutboundHandler;
+
+///
+/// 握手完成后传给下一个handler, 之后收到的都是 WebSocketFrame
+///
+#[derive(Clone, Debug)]
+pub struct WebSocketHandshakeComplete {
+    uri: String,
+    headers: HttpHeaders,
+    subprotocol: Option<String>,
+}
+
+impl WebSocketHandshakeComplete {
+    pub(crate) fn new(uri: &str, headers: HttpHeaders, subprotocol: Option<String>) -> WebSocketHandshakeComplete {
+        WebSocketHandshakeComplete {
+            uri: uri.to_owned(),
+            headers,
+            subprotocol,
+        }
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    ///
+    /// server 端是握手请求的header, client 端是握手响应的header
+    ///
+    pub fn headers(&self) -> &HttpHeaders {
+        &self.headers
+    }
+
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+}
+
+
+///
+/// 入站和出站handler 共享的连接状态
+///
+pub(crate) struct WebSocketState {
+    ///
+    /// 已经发送了关闭帧, 之后不再发送数据帧
+    ///
+    pub(crate) close_sent: bool,
+    ///
+    /// 协商了 permessage-deflate 时压缩发送的文本和二进制消息
+    ///
+    pub(crate) deflater: Option<Deflater>,
+}
+
+pub(crate) type SharedWebSocketState = Arc<Mutex<WebSocketState>>;
+
+pub(crate) fn new_state() -> SharedWebSocketState {
+    Arc::new(Mutex::new(WebSocketState { close_sent: false, deflater: None }))
+}
+
+
+///
+/// 握手之后解码帧: 自动回复 Ping, 收到关闭帧时回复关闭帧并关闭连接, 违反协议时发送对应状态码的关闭帧并关闭连接
+///
+pub(crate) struct WebSocketFrameDecoder {
+    reader: FrameReader,
+    inflater: Option<Inflater>,
+    state: SharedWebSocketState,
+    ///
+    /// 收到关闭帧或者违反协议之后丢弃所有数据
+    ///
+    closed: bool,
+}
+
+impl WebSocketFrameDecoder {
+    pub(crate) fn new(expect_masked: bool, max_frame_size: usize, state: SharedWebSocketState, inflater: Option<Inflater>) -> WebSocketFrameDecoder {
+        let mut reader = FrameReader::new(expect_masked, max_frame_size);
+        if inflater.is_some() {
+            reader.allowed_rsv = RSV1;
+        }
+        WebSocketFrameDecoder {
+            reader,
+            inflater,
+            state,
+            closed: false,
+        }
+    }
+
+    fn decode_frame(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, mut frame: RawFrame) -> Result<(), ProtocolViolation> {
+        if frame.rsv & RSV1 != 0 {
+            frame.payload = match self.inflater.as_mut() {
+                Some(inflater) => inflater.decompress(&frame.payload, self.reader.max_frame_size)?,
+                None => return Err(ProtocolViolation::new(close_codes::PROTOCOL_ERROR, "RSV1 is set without permessage-deflate".to_string())),
+            };
+        }
+        let frame = to_websocket_frame(frame)?;
+        self.deliver(channel_handler_ctx, frame);
+        Ok(())
+    }
+
+    fn deliver(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, mut frame: WebSocketFrame) {
+        let close_sent = self.state.lock().unwrap().close_sent;
+        match &frame {
+            WebSocketFrame::Ping(data) if !close_sent => {
+                channel_handler_ctx.write_and_flush(&mut WebSocketFrame::Pong(data.clone()));
+            }
+            WebSocketFrame::Close(code, _) => {
+                let code = *code;
+                self.closed = true;
+                channel_handler_ctx.fire_channel_read(&mut frame);
+                if !close_sent {
+                    channel_handler_ctx.write_and_flush(&mut WebSocketFrame::Close(code, String::new()));
+                }
+                channel_handler_ctx.close();
+                return;
+            }
+            _ => {}
+        }
+        channel_handler_ctx.fire_channel_read(&mut frame);
+    }
+
+    fn fail(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, violation: ProtocolViolation) {
+        self.closed = true;
+        let close_sent = self.state.lock().unwrap().close_sent;
+        if !close_sent {
+            channel_handler_ctx.write_and_flush(&mut WebSocketFrame::Close(violation.code, violation.message.clone()));
+        }
+        let message = if violation.code == close_codes::MESSAGE_TOO_BIG {
+            format!("TooLongFrame: {}", violation.message)
+        } else {
+            format!("CorruptedWebSocketFrame: {}", violation.message)
+        };
+        channel_handler_ctx.fire_channel_exception(RettyErrorKind::new(ErrorKind::InvalidData, message));
+        channel_handler_ctx.close();
+    }
+}
+
+impl ByteToMessageDecoder for WebSocketFrameDecoder {
+    fn id(&self) -> String {
+        return "WebSocketFrameDecoder".to_string();
+    }
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        let consumed = self.decode_bytes(channel_handler_ctx, buf.available_bytes(), out).unwrap_or(0);
+        buf.skip_index(consumed);
+    }
+
+    ///
+    /// 每次解析一帧, 解出的消息直接交给后面的handler: 关闭帧要先交出去再关闭连接
+    ///
+    fn decode_bytes(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, bytes: &[u8], _out: &mut Vec<Box<dyn Any>>) -> Option<usize> {
+        if self.closed {
+            return Some(bytes.len());
+        }
+        let mut frames = Vec::new();
+        let result = self.reader.read(bytes, &mut frames)
+            .and_then(|consumed| {
+                for frame in frames {
+                    self.decode_frame(channel_handler_ctx, frame)?;
+                }
+                Ok(consumed)
+            });
+        match result {
+            Ok(_) if self.closed => Some(bytes.len()),
+            Ok(consumed) => Some(consumed),
+            Err(e) => {
+                self.fail(channel_handler_ctx, e);
+                Some(bytes.len())
+            }
+        }
+    }
+}
+
+
+///
+/// 把 WebSocketFrame 编码成ByteBuf, 其它消息原样传递; client 端的帧使用随机掩码,
+/// 协商了 permessage-deflate 时压缩文本和二进制消息并设置RSV1
+///
+/// 发送关闭帧之后的帧全部丢弃
+///
+pub(crate) struct WebSocketFrameEncoder {
+    masked: bool,
+    state: SharedWebSocketState,
+}
+
+impl WebSocketFrameEncoder {
+    pub(crate) fn new(masked: bool, state: SharedWebSocketState) -> WebSocketFrameEncoder {
+        WebSocketFrameEncoder {
+            masked,
+            state,
+        }
+    }
+}
+
+impl ChannelOutboundHandler for WebSocketFrameEncoder {
+    fn id(&self) -> String {
+        return "WebSocketFrameEncoder".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        let frame = match message.downcast_ref::<WebSocketFrame>() {
+            Some(frame) => frame,
+            None => {
+                channel_handler_ctx.fire_channel_write(message);
+                return;
+            }
+        };
+        let (rsv, payload) = {
+            let mut state = self.state.lock().unwrap();
+            if state.close_sent {
+                log::debug!(target: trace::TARGET_PIPELINE, "WebSocketFrameEncoder frame after close is discarded, channel_id:{}", channel_handler_ctx.channel().id());
+                return;
+            }
+            if let WebSocketFrame::Close(..) = frame {
+                state.close_sent = true;
+            }
+            match state.deflater.as_mut() {
+                Some(deflater) if !frame.is_control() => (RSV1, deflater.compress(&frame.payload())),
+                _ => (0, frame.payload()),
+            }
+        };
+        let mask = if self.masked {
+            let mut mask = [0u8; 4];
+            mask.copy_from_slice(&uuid::Uuid::new_v4().as_bytes()[..4]);
+            Some(mask)
+        } else {
+            None
+        };
+        let mut out = channel_handler_ctx.alloc_buffer(payload.len() + 14);
+        encode_frame(true, rsv, frame.opcode(), &payload, mask, &mut out);
+        channel_handler_ctx.fire_channel_write(&mut out);
+    }
+}
@@ -0,0 +1,123 @@
+use crate::handler::codec::http::http_headers::{names, values};
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpMessage, HttpMethod, HttpResponseStatus, HttpVersion};
+use crate::handler::codec::http::websocket::websocket_permessage_deflate::{DeflateParams, PerMessageDeflate};
+
+///
+/// RFC 6455 计算 Sec-WebSocket-Accept 时拼在key 后面的GUID
+///
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+///
+/// 唯一支持的协议版本
+///
+pub(crate) const WEBSOCKET_VERSION: &str = "13";
+
+///
+/// Sec-WebSocket-Key 对应的 Sec-WebSocket-Accept: base64(sha1(key + GUID))
+///
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = sha1_smol::Sha1::new();
+    sha1.update(key.trim().as_bytes());
+    sha1.update(WEBSOCKET_GUID.as_bytes());
+    base64::encode(sha1.digest().bytes())
+}
+
+
+///
+/// 校验 WebSocket 升级请求并生成响应
+///
+/// 请求必须是 HTTP/1.1 的GET, 带有 `Upgrade: websocket`、`Connection: Upgrade`、`Sec-WebSocket-Version: 13`
+/// 和base64 编码的16 字节 `Sec-WebSocket-Key`; 版本不对时响应 `426 Upgrade Required`, 其它错误响应400
+///
+pub struct WebSocketServerHandshaker {
+    subprotocols: Vec<String>,
+    permessage_deflate: Option<PerMessageDeflate>,
+}
+
+impl WebSocketServerHandshaker {
+    pub fn new() -> Self {
+        WebSocketServerHandshaker {
+            subprotocols: Vec::new(),
+            permessage_deflate: None,
+        }
+    }
+
+    ///
+    /// 支持的子协议, 选择客户端列出的第一个支持的子协议
+    ///
+    pub fn subprotocols(mut self, subprotocols: &[&str]) -> Self {
+        self.subprotocols = subprotocols.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    ///
+    /// 接受client 提议的 permessage-deflate, client 没有提议或者提议的参数不能接受时不压缩
+    ///
+    pub fn permessage_deflate(mut self, permessage_deflate: PerMessageDeflate) -> Self {
+        self.permessage_deflate = Some(permessage_deflate);
+        self
+    }
+
+    ///
+    /// 成功时返回 `101 Switching Protocols`, 失败时返回错误响应
+    ///
+    pub fn handshake(&self, request: &FullHttpRequest) -> Result<FullHttpResponse, FullHttpResponse> {
+        self.handshake_with_extensions(request).map(|(response, _)| response)
+    }
+
+    ///
+    /// 同时返回协商的 permessage-deflate 参数
+    ///
+    pub(crate) fn handshake_with_extensions(&self, request: &FullHttpRequest) -> Result<(FullHttpResponse, Option<DeflateParams>), FullHttpResponse> {
+        if *request.method() != HttpMethod::Get {
+            let mut response = error_response(HttpResponseStatus::METHOD_NOT_ALLOWED, "websocket handshake must be a GET request");
+            response.headers_mut().set(names::ALLOW, HttpMethod::Get.as_str());
+            return Err(response);
+        }
+        let headers = request.headers();
+        if request.version() != HttpVersion::Http11
+            || !headers.contains_value(names::UPGRADE, values::WEBSOCKET)
+            || !headers.contains_value(names::CONNECTION, values::UPGRADE) {
+            return Err(error_response(HttpResponseStatus::BAD_REQUEST, "not a websocket upgrade request"));
+        }
+        if headers.get(names::SEC_WEBSOCKET_VERSION).map(|v| v.trim()) != Some(WEBSOCKET_VERSION) {
+            let mut response = error_response(HttpResponseStatus::UPGRADE_REQUIRED, "unsupported websocket version");
+            response.headers_mut().set(names::SEC_WEBSOCKET_VERSION, WEBSOCKET_VERSION);
+            return Err(response);
+        }
+        let key = match headers.get(names::SEC_WEBSOCKET_KEY) {
+            Some(key) if base64::decode(key.trim()).map_or(false, |k| k.len() == 16) => key,
+            _ => return Err(error_response(HttpResponseStatus::BAD_REQUEST, "invalid Sec-WebSocket-Key")),
+        };
+
+        let mut response = FullHttpResponse::new(HttpVersion::Http11, HttpResponseStatus::SWITCHING_PROTOCOLS, Vec::new());
+        response.headers_mut()
+            .set(names::UPGRADE, values::WEBSOCKET)
+            .set(names::CONNECTION, "Upgrade")
+            .set(names::SEC_WEBSOCKET_ACCEPT, &accept_key(key));
+        if let Some(subprotocol) = self.select_subprotocol(request) {
+            response.headers_mut().set(names::SEC_WEBSOCKET_PROTOCOL, &subprotocol);
+        }
+        let deflate = self.permessage_deflate.as_ref().and_then(|d| d.accept_offer(headers));
+        if let Some(params) = &deflate {
+            response.headers_mut().set(names::SEC_WEBSOCKET_EXTENSIONS, &params.response());
+        }
+        Ok((response, deflate))
+    }
+
+    fn select_subprotocol(&self, request: &FullHttpRequest) -> Option<String> {
+        request.headers().get_all(names::SEC_WEBSOCKET_PROTOCOL).iter()
+            .flat_map(|v| v.split(','))
+            .map(|p| p.trim())
+            .find(|p| self.subprotocols.iter().any(|s| s == p))
+            .map(|p| p.to_owned())
+    }
+}
+
+fn error_response(status: HttpResponseStatus, reason: &str) -> FullHttpResponse {
+    let mut response = FullHttpResponse::new(HttpVersion::Http11, status, reason.as_bytes().to_vec());
+    response.headers_mut().set(names::CONTENT_TYPE, "text/plain; charset=UTF-8");
+    response.set_content_length(reason.len() as u64);
+    response.set_keep_alive(false);
+    response
+}
@@ -0,0 +1,167 @@
+use std::any::Any;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::byte_to_message_decoder::ByteToMessageDecoderHandler;
+use crate::handler::codec::http::http_headers::names;
+use crate::handler::codec::http::http_message::{FullHttpRequest, HttpMessage};
+use crate::handler::codec::http::http_request_decoder::HttpDecoderState;
+use crate::handler::codec::http::query_string_decoder::QueryStringDecoder;
+use crate::handler::codec::http::websocket::websocket_permessage_deflate::PerMessageDeflate;
+use crate::handler::codec::http::websocket::websocket_protocol::{new_state, SharedWebSocketState, WebSocketFrameDecoder, WebSocketFrameEncoder, WebSocketHandshakeComplete};
+use crate::handler::codec::http::websocket::websocket_server_handshaker::WebSocketServerHandshaker;
+use crate::handler::combined_channel_duplex_handler::CombinedChannelDuplexHandler;
+use crate::handler::handler::ChannelInboundHandler;
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+
+///
+/// 在 websocket_path 上接受 WebSocket 升级, 放在 HttpServerCodec 和 HttpObjectAggregator 后面
+///
+/// 握手成功后响应 `101 Switching Protocols`, HttpServerCodec 不再解码HTTP, 下一个handler 先收到
+/// `WebSocketHandshakeComplete`, 之后收到 `WebSocketFrame`, 写出 `WebSocketFrame` 即可发送;
+/// 其它路径的请求原样传递
+///
+/// 前面的HTTP handler 不会从pipeline 中移除: 在handler 里修改pipeline 会死锁, 所以 HttpServerCodec
+/// 升级之后把字节原样传过来, 由这个handler 内部的帧解码器解码
+///
+/// ```ignore
+/// bootstrap.initialize_pipeline(|inbound, outbound| {
+///     HttpServerCodec::new().add_last(inbound, outbound);
+///     inbound.add_last(Box::new(HttpObjectAggregator::new(64 * 1024)));
+///     WebSocketServerProtocolHandler::new("/ws").subprotocols(&["chat"]).add_last(inbound, outbound);
+///     inbound.add_last(Box::new(ChatHandler::new()));
+/// });
+/// ```
+///
+pub struct WebSocketServerProtocolHandler {
+    websocket_path: String,
+    handshaker: WebSocketServerHandshaker,
+    max_frame_size: usize,
+}
+
+impl WebSocketServerProtocolHandler {
+    ///
+    /// 默认消息最大 64KB
+    ///
+    pub fn new(websocket_path: &str) -> Self {
+        WebSocketServerProtocolHandler {
+            websocket_path: websocket_path.to_owned(),
+            handshaker: WebSocketServerHandshaker::new(),
+            max_frame_size: 65536,
+        }
+    }
+
+    pub fn subprotocols(mut self, subprotocols: &[&str]) -> Self {
+        self.handshaker = self.handshaker.subprotocols(subprotocols);
+        self
+    }
+
+    ///
+    /// 支持 permessage-deflate 压缩扩展
+    ///
+    pub fn permessage_deflate(mut self, permessage_deflate: PerMessageDeflate) -> Self {
+        self.handshaker = self.handshaker.permessage_deflate(permessage_deflate);
+        self
+    }
+
+    ///
+    /// 单个帧和分片拼成的消息的最大长度, 超过时以1009 关闭连接
+    ///
+    pub fn max_frame_size(mut self, max_frame_size: usize) -> Self {
+        assert!(max_frame_size > 0, "max_frame_size must be a positive integer: {}", max_frame_size);
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    pub fn into_handler(self) -> CombinedChannelDuplexHandler {
+        let state = new_state();
+        let encoder = WebSocketFrameEncoder::new(false, state.clone());
+        let handler = WebSocketServerProtocolInboundHandler {
+            websocket_path: self.websocket_path,
+            handshaker: self.handshaker,
+            max_frame_size: self.max_frame_size,
+            state,
+            decoder: None,
+        };
+        CombinedChannelDuplexHandler::new("WebSocketServerProtocolHandler", Box::new(handler), Box::new(encoder))
+    }
+
+    pub fn add_last(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_last(inbound_pipe, outbound_pipe);
+    }
+
+    pub fn add_first(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        self.into_handler().add_first(inbound_pipe, outbound_pipe);
+    }
+}
+
+
+struct WebSocketServerProtocolInboundHandler {
+    websocket_path: String,
+    handshaker: WebSocketServerHandshaker,
+    max_frame_size: usize,
+    state: SharedWebSocketState,
+    ///
+    /// 握手完成后创建, 帧解码器不替换进pipeline, 由这个handler 转发
+    ///
+    decoder: Option<ByteToMessageDecoderHandler<WebSocketFrameDecoder>>,
+}
+
+impl WebSocketServerProtocolInboundHandler {
+    fn handshake(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, request: &FullHttpRequest) {
+        match self.handshaker.handshake_with_extensions(request) {
+            Ok((mut response, deflate)) => {
+                let subprotocol = response.headers().get(names::SEC_WEBSOCKET_PROTOCOL).map(|p| p.to_owned());
+                channel_handler_ctx.write_and_flush(&mut response);
+                HttpDecoderState::set_upgraded(&channel_handler_ctx.channel());
+                let inflater = deflate.map(|params| {
+                    let (deflater, inflater) = params.codec(true);
+                    self.state.lock().unwrap().deflater = Some(deflater);
+                    inflater
+                });
+                self.decoder = Some(ByteToMessageDecoderHandler::new(WebSocketFrameDecoder::new(true, self.max_frame_size, self.state.clone(), inflater)));
+                let mut complete = WebSocketHandshakeComplete::new(request.uri(), request.headers().clone(), subprotocol);
+                channel_handler_ctx.fire_channel_read(&mut complete);
+            }
+            Err(mut response) => {
+                // 错误响应不保持连接, 写完后由 HttpServerCodec 关闭
+                channel_handler_ctx.write_and_flush(&mut response);
+            }
+        }
+    }
+}
+
+impl ChannelInboundHandler for WebSocketServerProtocolInboundHandler {
+    fn id(&self) -> String {
+        return "WebSocketServerProtocolHandler".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        match self.decoder.take() {
+            Some(mut decoder) => decoder.channel_inactive(channel_handler_ctx),
+            None => channel_handler_ctx.fire_channel_inactive(),
+        }
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(decoder) = self.decoder.as_mut() {
+            decoder.channel_read(channel_handler_ctx, message);
+            return;
+        }
+        if let Some(request) = message.downcast_ref::<FullHttpRequest>() {
+            if QueryStringDecoder::new(request.uri()).path() == self.websocket_path {
+                self.handshake(channel_handler_ctx, request);
+                return;
+            }
+        }
+        channel_handler_ctx.fire_channel_read(message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
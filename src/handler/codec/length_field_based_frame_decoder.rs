@@ -0,0 +1,181 @@
+use std::io::ErrorKind;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::codec::frame::{self, FrameDecode};
+
+///
+/// 长度字段的字节序
+///
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ByteOrder {
+    BigEndian,
+    LittleEndian,
+}
+
+impl ByteOrder {
+    ///
+    /// 按字节序读取 1/2/3/4/8 字节的无符号整数
+    ///
+    pub(crate) fn read_uint(&self, bytes: &[u8]) -> u64 {
+        match self {
+            ByteOrder::BigEndian => bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64),
+            ByteOrder::LittleEndian => bytes.iter().rev().fold(0u64, |acc, b| (acc << 8) | *b as u64),
+        }
+    }
+
+    ///
+    /// 按字节序把value 写成 length 个字节
+    ///
+    pub(crate) fn write_uint(&self, value: u64, length: usize, out: &mut Vec<u8>) {
+        for i in 0..length {
+            let shift = match self {
+                ByteOrder::BigEndian => (length - 1 - i) * 8,
+                ByteOrder::LittleEndian => i * 8,
+            };
+            out.push((value >> shift) as u8);
+        }
+    }
+}
+
+
+///
+/// 按长度字段拆帧的通用解码器, 每一帧以单独的ByteBuf 传给下一个handler
+///
+/// ```text
+/// 帧长度 = 长度字段的值 + length_adjustment + length_field_offset + length_field_length
+/// ```
+///
+/// 帧长度超过 max_frame_length 时丢弃整帧并触发 `InvalidData` 异常,
+/// fail_fast 为true(默认) 时一读到长度字段就触发, 否则丢弃完整帧之后再触发
+///
+/// ```ignore
+/// // 4 字节大端长度, 长度不包含自身, 去掉长度字段
+/// handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(1024 * 1024, 0, 4, 0, 4))));
+/// ```
+///
+pub struct LengthFieldBasedFrameDecoder {
+    max_frame_length: usize,
+    length_field_offset: usize,
+    length_field_length: usize,
+    length_adjustment: i64,
+    initial_bytes_to_strip: usize,
+    byte_order: ByteOrder,
+    fail_fast: bool,
+    ///
+    /// 正在丢弃超长帧
+    ///
+    discarding_too_long_frame: bool,
+    too_long_frame_length: u64,
+    bytes_to_discard: u64,
+}
+
+impl LengthFieldBasedFrameDecoder {
+    pub fn new(max_frame_length: usize,
+               length_field_offset: usize,
+               length_field_length: usize,
+               length_adjustment: i64,
+               initial_bytes_to_strip: usize) -> Self {
+        assert!(max_frame_length > 0, "max_frame_length must be a positive integer: {}", max_frame_length);
+        assert!(matches!(length_field_length, 1 | 2 | 3 | 4 | 8), "length_field_length must be either 1, 2, 3, 4, or 8: {}", length_field_length);
+        assert!(length_field_offset + length_field_length <= max_frame_length,
+                "max_frame_length ({}) must be equal to or greater than length_field_offset ({}) + length_field_length ({})",
+                max_frame_length, length_field_offset, length_field_length);
+        LengthFieldBasedFrameDecoder {
+            max_frame_length,
+            length_field_offset,
+            length_field_length,
+            length_adjustment,
+            initial_bytes_to_strip,
+            byte_order: ByteOrder::BigEndian,
+            fail_fast: true,
+            discarding_too_long_frame: false,
+            too_long_frame_length: 0,
+            bytes_to_discard: 0,
+        }
+    }
+
+    pub fn byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    fn fail_if_necessary(&mut self, first_detection: bool, errors: &mut Vec<RettyErrorKind>) {
+        if self.bytes_to_discard == 0 {
+            // 超长帧已经丢弃完, 恢复正常解码
+            let too_long_frame_length = self.too_long_frame_length;
+            self.too_long_frame_length = 0;
+            self.discarding_too_long_frame = false;
+            if !self.fail_fast || first_detection {
+                errors.push(self.too_long_frame(too_long_frame_length));
+            }
+        } else if self.fail_fast && first_detection {
+            errors.push(self.too_long_frame(self.too_long_frame_length));
+        }
+    }
+
+    fn too_long_frame(&self, frame_length: u64) -> RettyErrorKind {
+        frame::too_long_frame(frame_length, self.max_frame_length)
+    }
+}
+
+
+impl FrameDecode for LengthFieldBasedFrameDecoder {
+    fn id(&self) -> String {
+        return "LengthFieldBasedFrameDecoder".to_string();
+    }
+
+    fn decode_frame(&mut self, bytes: &[u8], errors: &mut Vec<RettyErrorKind>) -> Option<(usize, Option<(usize, usize)>)> {
+        if self.discarding_too_long_frame {
+            if bytes.is_empty() {
+                return None;
+            }
+            let discard = self.bytes_to_discard.min(bytes.len() as u64) as usize;
+            self.bytes_to_discard -= discard as u64;
+            self.fail_if_necessary(false, errors);
+            return Some((discard, None));
+        }
+
+        let length_field_end = self.length_field_offset + self.length_field_length;
+        if bytes.len() < length_field_end {
+            return None;
+        }
+        let unadjusted = self.byte_order.read_uint(&bytes[self.length_field_offset..length_field_end]);
+        let frame_length = unadjusted as i128 + self.length_adjustment as i128 + length_field_end as i128;
+
+        if frame_length < length_field_end as i128 {
+            errors.push(RettyErrorKind::new(ErrorKind::InvalidData,
+                                            format!("CorruptedFrame: adjusted frame length ({}) is less than length_field_end: {}", frame_length, length_field_end)));
+            return Some((length_field_end, None));
+        }
+
+        if frame_length > self.max_frame_length as i128 {
+            let frame_length = frame_length.min(u64::MAX as i128) as u64;
+            self.too_long_frame_length = frame_length;
+            if frame_length <= bytes.len() as u64 {
+                // 整帧都在缓冲区里, 直接跳过
+                self.fail_if_necessary(true, errors);
+                return Some((frame_length as usize, None));
+            }
+            self.discarding_too_long_frame = true;
+            self.bytes_to_discard = frame_length - bytes.len() as u64;
+            self.fail_if_necessary(true, errors);
+            return Some((bytes.len(), None));
+        }
+
+        let frame_length = frame_length as usize;
+        if bytes.len() < frame_length {
+            return None;
+        }
+        if self.initial_bytes_to_strip > frame_length {
+            errors.push(RettyErrorKind::new(ErrorKind::InvalidData,
+                                            format!("CorruptedFrame: adjusted frame length ({}) is less than initial_bytes_to_strip: {}", frame_length, self.initial_bytes_to_strip)));
+            return Some((frame_length, None));
+        }
+        Some((frame_length, Some((self.initial_bytes_to_strip, frame_length))))
+    }
+}
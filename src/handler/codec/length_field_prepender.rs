@@ -0,0 +1,100 @@
+use std::any::Any;
+
+use crate::core::trace;
+use crate::handler::channel_handler_ctx::ChannelOutboundHandlerCtx;
+use crate::handler::codec::length_field_based_frame_decoder::ByteOrder;
+use crate::handler::handler::ChannelOutboundHandler;
+use crate::transport::message;
+
+///
+/// 在出站的ByteBuf 前面加上长度字段, 与 LengthFieldBasedFrameDecoder 配对使用
+///
+/// ```text
+/// 长度字段的值 = 消息长度 + length_adjustment (+ length_field_length, 如果包含长度字段本身)
+/// ```
+///
+/// 长度为负数或者超出长度字段能表示的范围时丢弃消息
+///
+/// ```ignore
+/// handler_pipe.add_last(Box::new(LengthFieldPrepender::new(4)));
+/// ```
+///
+pub struct LengthFieldPrepender {
+    length_field_length: usize,
+    byte_order: ByteOrder,
+    length_includes_length_field_length: bool,
+    length_adjustment: i64,
+}
+
+impl LengthFieldPrepender {
+    pub fn new(length_field_length: usize) -> Self {
+        assert!(matches!(length_field_length, 1 | 2 | 3 | 4 | 8), "length_field_length must be either 1, 2, 3, 4, or 8: {}", length_field_length);
+        LengthFieldPrepender {
+            length_field_length,
+            byte_order: ByteOrder::BigEndian,
+            length_includes_length_field_length: false,
+            length_adjustment: 0,
+        }
+    }
+
+    pub fn byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+
+    ///
+    /// 长度字段的值是否包含长度字段本身
+    ///
+    pub fn length_includes_length_field_length(mut self, includes: bool) -> Self {
+        self.length_includes_length_field_length = includes;
+        self
+    }
+
+    pub fn length_adjustment(mut self, length_adjustment: i64) -> Self {
+        self.length_adjustment = length_adjustment;
+        self
+    }
+
+    ///
+    /// 计算长度字段的值, 不合法时返回None
+    ///
+    fn length_field_value(&self, payload_len: usize) -> Option<u64> {
+        let mut length = payload_len as i128 + self.length_adjustment as i128;
+        if self.length_includes_length_field_length {
+            length += self.length_field_length as i128;
+        }
+        let max = if self.length_field_length == 8 { u64::MAX as i128 } else { (1i128 << (self.length_field_length * 8)) - 1 };
+        if length < 0 || length > max {
+            return None;
+        }
+        Some(length as u64)
+    }
+}
+
+impl ChannelOutboundHandler for LengthFieldPrepender {
+    fn id(&self) -> String {
+        return "LengthFieldPrepender".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        let payload = match message::as_bytes(message) {
+            Some(bytes) => bytes,
+            None => {
+                channel_handler_ctx.fire_channel_write(message);
+                return;
+            }
+        };
+        let length = match self.length_field_value(payload.len()) {
+            Some(length) => length,
+            None => {
+                log::warn!(target: trace::TARGET_PIPELINE, "LengthFieldPrepender length of message ({}) does not fit into {} bytes, message dropped, channel_id:{}",
+                           payload.len(), self.length_field_length, channel_handler_ctx.channel().id());
+                return;
+            }
+        };
+        let mut frame = channel_handler_ctx.alloc_buffer(self.length_field_length + payload.len());
+        self.byte_order.write_uint(length, self.length_field_length, &mut frame);
+        frame.extend_from_slice(payload);
+        channel_handler_ctx.fire_channel_write(&mut frame);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::errors::RettyErrorKind;
+use crate::handler::codec::frame::{self, FrameDecode};
+
+///
+/// 按 `\n` 或 `\r\n` 拆帧的解码器, 每一行以单独的ByteBuf 传给下一个handler
+///
+/// 一行超过 max_length 时丢弃到下一个换行符并触发 `InvalidData` 异常,
+/// fail_fast 为true 时发现超长立即触发, 否则(默认) 丢弃完整行之后再触发
+///
+/// ```ignore
+/// handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(LineBasedFrameDecoder::new(8192))));
+/// ```
+///
+pub struct LineBasedFrameDecoder {
+    max_length: usize,
+    strip_delimiter: bool,
+    fail_fast: bool,
+    ///
+    /// 正在丢弃超长的行
+    ///
+    discarding: bool,
+    discarded_bytes: usize,
+}
+
+impl LineBasedFrameDecoder {
+    pub fn new(max_length: usize) -> Self {
+        LineBasedFrameDecoder {
+            max_length,
+            strip_delimiter: true,
+            fail_fast: false,
+            discarding: false,
+            discarded_bytes: 0,
+        }
+    }
+
+    ///
+    /// 是否去掉行尾的换行符, 默认去掉
+    ///
+    pub fn strip_delimiter(mut self, strip_delimiter: bool) -> Self {
+        self.strip_delimiter = strip_delimiter;
+        self
+    }
+
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    ///
+    /// 返回行尾的位置, `\r\n` 时指向 `\r`
+    ///
+    fn find_end_of_line(bytes: &[u8]) -> Option<usize> {
+        let i = bytes.iter().position(|b| *b == b'\n')?;
+        if i > 0 && bytes[i - 1] == b'\r' {
+            Some(i - 1)
+        } else {
+            Some(i)
+        }
+    }
+}
+
+impl FrameDecode for LineBasedFrameDecoder {
+    fn id(&self) -> String {
+        return "LineBasedFrameDecoder".to_string();
+    }
+
+    fn decode_frame(&mut self, bytes: &[u8], errors: &mut Vec<RettyErrorKind>) -> Option<(usize, Option<(usize, usize)>)> {
+        let eol = LineBasedFrameDecoder::find_end_of_line(bytes);
+        if !self.discarding {
+            match eol {
+                Some(eol) => {
+                    let delimiter_length = if bytes[eol] == b'\r' { 2 } else { 1 };
+                    if eol > self.max_length {
+                        errors.push(frame::too_long_frame(eol as u64, self.max_length));
+                        return Some((eol + delimiter_length, None));
+                    }
+                    let end = if self.strip_delimiter { eol } else { eol + delimiter_length };
+                    Some((eol + delimiter_length, Some((0, end))))
+                }
+                None if bytes.len() > self.max_length => {
+                    self.discarding = true;
+                    self.discarded_bytes = bytes.len();
+                    if self.fail_fast {
+                        errors.push(frame::too_long_frame(self.discarded_bytes as u64, self.max_length));
+                    }
+                    Some((bytes.len(), None))
+                }
+                None => None,
+            }
+        } else {
+            match eol {
+                Some(eol) => {
+                    let delimiter_length = if bytes[eol] == b'\r' { 2 } else { 1 };
+                    let length = self.discarded_bytes + eol;
+                    self.discarding = false;
+                    self.discarded_bytes = 0;
+                    if !self.fail_fast {
+                        errors.push(frame::too_long_frame(length as u64, self.max_length));
+                    }
+                    Some((eol + delimiter_length, None))
+                }
+                None if bytes.is_empty() => None,
+                None => {
+                    self.discarded_bytes += bytes.len();
+                    Some((bytes.len(), None))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use std::any::Any;
+use std::marker::PhantomData;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::handler::ChannelInboundHandler;
+
+///
+/// 把一种入站消息转换成零个或多个其它消息
+///
+/// ```ignore
+/// struct CommandDecoder;
+///
+/// impl MessageToMessageDecoder<String> for CommandDecoder {
+///     fn id(&self) -> String { "CommandDecoder".to_string() }
+///
+///     fn decode(&mut self, ctx: &mut ChannelInboundHandlerCtx, message: &mut String, out: &mut Vec<Box<dyn Any>>) {
+///         out.push(Box::new(Command::parse(message)));
+///     }
+/// }
+///
+/// handler_pipe.add_last(Box::new(MessageToMessageDecoderHandler::new(CommandDecoder)));
+/// ```
+///
+pub trait MessageToMessageDecoder<I: Any> {
+    fn id(&self) -> String;
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut I, out: &mut Vec<Box<dyn Any>>);
+}
+
+
+///
+/// 把 MessageToMessageDecoder 适配成入站handler, 类型不是 I 的消息原样传给下一个handler
+///
+pub struct MessageToMessageDecoderHandler<I: Any, D: MessageToMessageDecoder<I>> {
+    decoder: D,
+    _message: PhantomData<fn() -> I>,
+}
+
+impl<I: Any, D: MessageToMessageDecoder<I>> MessageToMessageDecoderHandler<I, D> {
+    pub fn new(decoder: D) -> Self {
+        MessageToMessageDecoderHandler {
+            decoder,
+            _message: PhantomData,
+        }
+    }
+
+    pub fn decoder(&mut self) -> &mut D {
+        &mut self.decoder
+    }
+}
+
+impl<I: Any, D: MessageToMessageDecoder<I>> ChannelInboundHandler for MessageToMessageDecoderHandler<I, D> {
+    fn id(&self) -> String {
+        self.decoder.id()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if !message.is::<I>() {
+            channel_handler_ctx.fire_channel_read(message);
+            return;
+        }
+        let message = message.downcast_mut::<I>().unwrap();
+        let mut out: Vec<Box<dyn Any>> = Vec::new();
+        self.decoder.decode(channel_handler_ctx, message, &mut out);
+        for mut message in out.drain(..) {
+            channel_handler_ctx.fire_channel_read(&mut *message);
+        }
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
@@ -0,0 +1,69 @@
+use std::any::Any;
+use std::marker::PhantomData;
+
+use crate::handler::channel_handler_ctx::ChannelOutboundHandlerCtx;
+use crate::handler::handler::ChannelOutboundHandler;
+
+///
+/// 把一种出站消息转换成零个或多个其它消息
+///
+/// ```ignore
+/// struct CommandEncoder;
+///
+/// impl MessageToMessageEncoder<Command> for CommandEncoder {
+///     fn id(&self) -> String { "CommandEncoder".to_string() }
+///
+///     fn encode(&mut self, ctx: &mut ChannelOutboundHandlerCtx, message: &mut Command, out: &mut Vec<Box<dyn Any>>) {
+///         out.push(Box::new(message.to_string()));
+///     }
+/// }
+///
+/// handler_pipe.add_last(Box::new(MessageToMessageEncoderHandler::new(CommandEncoder)));
+/// ```
+///
+pub trait MessageToMessageEncoder<I: Any> {
+    fn id(&self) -> String;
+
+    fn encode(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut I, out: &mut Vec<Box<dyn Any>>);
+}
+
+
+///
+/// 把 MessageToMessageEncoder 适配成出站handler, 类型不是 I 的消息原样传给下一个handler
+///
+pub struct MessageToMessageEncoderHandler<I: Any, E: MessageToMessageEncoder<I>> {
+    encoder: E,
+    _message: PhantomData<fn() -> I>,
+}
+
+impl<I: Any, E: MessageToMessageEncoder<I>> MessageToMessageEncoderHandler<I, E> {
+    pub fn new(encoder: E) -> Self {
+        MessageToMessageEncoderHandler {
+            encoder,
+            _message: PhantomData,
+        }
+    }
+
+    pub fn encoder(&mut self) -> &mut E {
+        &mut self.encoder
+    }
+}
+
+impl<I: Any, E: MessageToMessageEncoder<I>> ChannelOutboundHandler for MessageToMessageEncoderHandler<I, E> {
+    fn id(&self) -> String {
+        self.encoder.id()
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        if !message.is::<I>() {
+            channel_handler_ctx.fire_channel_write(message);
+            return;
+        }
+        let message = message.downcast_mut::<I>().unwrap();
+        let mut out: Vec<Box<dyn Any>> = Vec::new();
+        self.encoder.encode(channel_handler_ctx, message, &mut out);
+        for mut message in out.drain(..) {
+            channel_handler_ctx.fire_channel_write(&mut *message);
+        }
+    }
+}
@@ -1 +1,16 @@
 pub mod first_integer_length_field_decoder;
+pub mod length_field_based_frame_decoder;
+pub mod length_field_prepender;
+pub mod line_based_frame_decoder;
+pub mod delimiter_based_frame_decoder;
+pub(crate) mod frame;
+pub mod fixed_length_frame_decoder;
+pub mod protobuf_varint32_frame_decoder;
+pub mod protobuf_varint32_length_field_prepender;
+pub mod charset;
+pub mod string_decoder;
+pub mod string_encoder;
+pub mod byte_to_message_decoder;
+pub mod message_to_message_decoder;
+pub mod message_to_message_encoder;
+pub mod http;
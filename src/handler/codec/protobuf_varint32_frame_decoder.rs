@@ -0,0 +1,101 @@
+use std::io::ErrorKind;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::codec::frame::FrameDecode;
+
+///
+/// varint32 最多占用的字节数
+///
+pub(crate) const MAX_VARINT32_BYTES: usize = 5;
+
+///
+/// 读取varint32 的结果
+///
+pub(crate) enum Varint32 {
+    ///
+    /// (值, 占用的字节数)
+    ///
+    Value(u32, usize),
+    ///
+    /// 字节不够, 可能被拆在两次读里
+    ///
+    Incomplete,
+    ///
+    /// 超过5 个字节或者超出32 位
+    ///
+    Malformed,
+}
+
+pub(crate) fn read_varint32(bytes: &[u8]) -> Varint32 {
+    let mut value: u64 = 0;
+    for i in 0..MAX_VARINT32_BYTES {
+        let b = match bytes.get(i) {
+            Some(b) => *b,
+            None => return Varint32::Incomplete,
+        };
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            if value > u32::MAX as u64 {
+                return Varint32::Malformed;
+            }
+            return Varint32::Value(value as u32, i + 1);
+        }
+    }
+    Varint32::Malformed
+}
+
+pub(crate) fn write_varint32(mut value: u32, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+
+///
+/// 按 protobuf varint32 长度前缀拆帧的解码器, 传给下一个handler 的帧不包含长度前缀
+///
+/// ```text
+/// +--------+---------------+      +---------------+
+/// | Length | Protobuf Data |----->| Protobuf Data |
+/// | 0xAC02 |  (300 bytes)  |      |  (300 bytes)  |
+/// +--------+---------------+      +---------------+
+/// ```
+///
+/// 长度前缀超过5 个字节或者是负数时触发 `InvalidData` 异常并丢弃已经收到的数据
+///
+pub struct ProtobufVarint32FrameDecoder {}
+
+impl ProtobufVarint32FrameDecoder {
+    pub fn new() -> Self {
+        ProtobufVarint32FrameDecoder {}
+    }
+}
+
+impl FrameDecode for ProtobufVarint32FrameDecoder {
+    fn id(&self) -> String {
+        return "ProtobufVarint32FrameDecoder".to_string();
+    }
+
+    fn decode_frame(&mut self, bytes: &[u8], errors: &mut Vec<RettyErrorKind>) -> Option<(usize, Option<(usize, usize)>)> {
+        let (length, header_length) = match read_varint32(bytes) {
+            Varint32::Value(length, header_length) => (length, header_length),
+            Varint32::Incomplete => return None,
+            Varint32::Malformed => {
+                errors.push(RettyErrorKind::new(ErrorKind::InvalidData, String::from("CorruptedFrame: length wider than 32-bit")));
+                // 流已经错位, 丢弃已经收到的数据
+                return Some((bytes.len(), None));
+            }
+        };
+        if length > i32::MAX as u32 {
+            errors.push(RettyErrorKind::new(ErrorKind::InvalidData, format!("CorruptedFrame: negative length: {}", length as i32)));
+            return Some((bytes.len(), None));
+        }
+        let frame_end = header_length + length as usize;
+        if bytes.len() < frame_end {
+            return None;
+        }
+        Some((frame_end, Some((header_length, frame_end))))
+    }
+}
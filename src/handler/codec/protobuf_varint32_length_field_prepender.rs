@@ -0,0 +1,50 @@
+use std::any::Any;
+
+use crate::core::trace;
+use crate::handler::channel_handler_ctx::ChannelOutboundHandlerCtx;
+use crate::handler::codec::protobuf_varint32_frame_decoder::{MAX_VARINT32_BYTES, write_varint32};
+use crate::handler::handler::ChannelOutboundHandler;
+use crate::transport::message;
+
+///
+/// 在出站的ByteBuf 前面加上 varint32 长度前缀, 与 ProtobufVarint32FrameDecoder 配对使用
+///
+/// ```text
+/// +---------------+      +--------+---------------+
+/// | Protobuf Data |----->| Length | Protobuf Data |
+/// |  (300 bytes)  |      | 0xAC02 |  (300 bytes)  |
+/// +---------------+      +--------+---------------+
+/// ```
+///
+pub struct ProtobufVarint32LengthFieldPrepender {}
+
+impl ProtobufVarint32LengthFieldPrepender {
+    pub fn new() -> Self {
+        ProtobufVarint32LengthFieldPrepender {}
+    }
+}
+
+impl ChannelOutboundHandler for ProtobufVarint32LengthFieldPrepender {
+    fn id(&self) -> String {
+        return "ProtobufVarint32LengthFieldPrepender".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        let payload = match message::as_bytes(message) {
+            Some(bytes) => bytes,
+            None => {
+                channel_handler_ctx.fire_channel_write(message);
+                return;
+            }
+        };
+        if payload.len() > i32::MAX as usize {
+            log::warn!(target: trace::TARGET_PIPELINE, "ProtobufVarint32LengthFieldPrepender length of message ({}) exceeds varint32, message dropped, channel_id:{}",
+                       payload.len(), channel_handler_ctx.channel().id());
+            return;
+        }
+        let mut frame = channel_handler_ctx.alloc_buffer(MAX_VARINT32_BYTES + payload.len());
+        write_varint32(payload.len() as u32, &mut frame);
+        frame.extend_from_slice(payload);
+        channel_handler_ctx.fire_channel_write(&mut frame);
+    }
+}
@@ -0,0 +1,74 @@
+use std::any::Any;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::codec::charset::{Charset, CodingErrorAction};
+use crate::handler::handler::ChannelInboundHandler;
+use crate::transport::message;
+
+///
+/// 把ByteBuf 按字符集解码成String 传给下一个handler, 其它类型的消息原样传递
+///
+/// 需要放在帧解码器后面, 否则一个字符可能被拆在两个ByteBuf 里
+///
+/// ```ignore
+/// handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(LineBasedFrameDecoder::new(8192))));
+/// handler_pipe.add_last(Box::new(StringDecoder::new(Charset::Gb18030)));
+/// ```
+///
+pub struct StringDecoder {
+    charset: Charset,
+    action: CodingErrorAction,
+}
+
+impl StringDecoder {
+    ///
+    /// 默认遇到非法字节时替换为 U+FFFD
+    ///
+    pub fn new(charset: Charset) -> Self {
+        StringDecoder {
+            charset,
+            action: CodingErrorAction::Replace,
+        }
+    }
+
+    ///
+    /// 遇到非法字节时触发 `InvalidData` 异常并丢弃这条消息
+    ///
+    pub fn strict(mut self) -> Self {
+        self.action = CodingErrorAction::Strict;
+        self
+    }
+}
+
+impl ChannelInboundHandler for StringDecoder {
+    fn id(&self) -> String {
+        return "StringDecoder".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        let decoded = match message::as_bytes(message) {
+            Some(bytes) => self.charset.decode(bytes, self.action),
+            None => {
+                channel_handler_ctx.fire_channel_read(message);
+                return;
+            }
+        };
+        match decoded {
+            Ok(mut s) => channel_handler_ctx.fire_channel_read(&mut s),
+            Err(e) => channel_handler_ctx.fire_channel_exception(e),
+        }
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
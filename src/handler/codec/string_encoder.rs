@@ -0,0 +1,64 @@
+use std::any::Any;
+
+use crate::core::trace;
+use crate::handler::channel_handler_ctx::ChannelOutboundHandlerCtx;
+use crate::handler::codec::charset::{Charset, CodingErrorAction};
+use crate::handler::handler::ChannelOutboundHandler;
+
+///
+/// 把出站的String 按字符集编码成ByteBuf, 其它类型的消息原样传递
+///
+/// ```ignore
+/// handler_pipe.add_last(Box::new(StringEncoder::new(Charset::Gbk)));
+/// ```
+///
+pub struct StringEncoder {
+    charset: Charset,
+    action: CodingErrorAction,
+}
+
+impl StringEncoder {
+    ///
+    /// 默认无法编码的字符替换为 `?`
+    ///
+    pub fn new(charset: Charset) -> Self {
+        StringEncoder {
+            charset,
+            action: CodingErrorAction::Replace,
+        }
+    }
+
+    ///
+    /// 遇到无法编码的字符时丢弃这条消息
+    ///
+    pub fn strict(mut self) -> Self {
+        self.action = CodingErrorAction::Strict;
+        self
+    }
+}
+
+impl ChannelOutboundHandler for StringEncoder {
+    fn id(&self) -> String {
+        return "StringEncoder".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        let encoded = match message.downcast_ref::<String>() {
+            Some(s) => self.charset.encode(s, self.action),
+            None => {
+                channel_handler_ctx.fire_channel_write(message);
+                return;
+            }
+        };
+        match encoded {
+            Ok(bytes) => {
+                let mut buf = channel_handler_ctx.alloc_buffer(bytes.len());
+                buf.extend_from_slice(&bytes);
+                channel_handler_ctx.fire_channel_write(&mut buf);
+            }
+            Err(e) => {
+                log::warn!(target: trace::TARGET_PIPELINE, "StringEncoder {}, message dropped, channel_id:{}", e, channel_handler_ctx.channel().id());
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::any::Any;
+
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
+use crate::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+
+///
+/// 把一对入站/出站handler 作为一个整体, 用同一个名字同时加入入站和出站pipeline
+///
+/// ```ignore
+/// bootstrap.initialize_pipeline(|inbound, outbound| {
+///     CombinedChannelDuplexHandler::new("CommandCodec",
+///                                       Box::new(MessageToMessageDecoderHandler::new(CommandDecoder)),
+///                                       Box::new(MessageToMessageEncoderHandler::new(CommandEncoder)))
+///         .add_last(inbound, outbound);
+/// });
+/// ```
+///
+pub struct CombinedChannelDuplexHandler {
+    name: String,
+    inbound_handler: Box<dyn ChannelInboundHandler + Send + Sync>,
+    outbound_handler: Box<dyn ChannelOutboundHandler + Send + Sync>,
+}
+
+impl CombinedChannelDuplexHandler {
+    pub fn new(name: &str,
+               inbound_handler: Box<dyn ChannelInboundHandler + Send + Sync>,
+               outbound_handler: Box<dyn ChannelOutboundHandler + Send + Sync>) -> CombinedChannelDuplexHandler {
+        CombinedChannelDuplexHandler {
+            name: name.to_owned(),
+            inbound_handler,
+            outbound_handler,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    ///
+    /// 加到入站和出站pipeline 的末尾
+    ///
+    pub fn add_last(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        let (inbound, outbound) = self.split();
+        inbound_pipe.add_last(inbound);
+        outbound_pipe.add_last(outbound);
+    }
+
+    ///
+    /// 加到入站和出站pipeline 的开头
+    ///
+    pub fn add_first(self, inbound_pipe: &mut ChannelInboundHandlerPipe, outbound_pipe: &mut ChannelOutboundHandlerPipe) {
+        let (inbound, outbound) = self.split();
+        inbound_pipe.add_first(inbound);
+        outbound_pipe.add_first(outbound);
+    }
+
+    fn split(self) -> (Box<dyn ChannelInboundHandler + Send + Sync>, Box<dyn ChannelOutboundHandler + Send + Sync>) {
+        let inbound = CombinedInboundHandler {
+            name: self.name.clone(),
+            handler: self.inbound_handler,
+        };
+        let outbound = CombinedOutboundHandler {
+            name: self.name,
+            handler: self.outbound_handler,
+        };
+        (Box::new(inbound), Box::new(outbound))
+    }
+}
+
+
+///
+/// 入站的一半, id 使用组合的名字
+///
+struct CombinedInboundHandler {
+    name: String,
+    handler: Box<dyn ChannelInboundHandler + Send + Sync>,
+}
+
+impl ChannelInboundHandler for CombinedInboundHandler {
+    fn id(&self) -> String {
+        self.name.clone()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        self.handler.channel_active(channel_handler_ctx);
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        self.handler.channel_inactive(channel_handler_ctx);
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        self.handler.channel_read(channel_handler_ctx, message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        self.handler.channel_exception(channel_handler_ctx, error);
+    }
+}
+
+
+///
+/// 出站的一半, id 使用组合的名字
+///
+struct CombinedOutboundHandler {
+    name: String,
+    handler: Box<dyn ChannelOutboundHandler + Send + Sync>,
+}
+
+impl ChannelOutboundHandler for CombinedOutboundHandler {
+    fn id(&self) -> String {
+        self.name.clone()
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        self.handler.channel_write(channel_handler_ctx, message);
+    }
+}
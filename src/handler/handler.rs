@@ -2,8 +2,8 @@ use std::any::Any;
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
-use bytebuf_rs::bytebuf::ByteBuf;
 
+use crate::core::trace;
 use crate::errors::RettyErrorKind;
 use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
 
@@ -30,7 +30,8 @@ impl ChannelInboundHandler for HeadHandler {
     }
 
     fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
-        channel_handler_ctx.channel().set_last_read_time(chrono::Local::now().timestamp_millis() as u64);
+        let now_ms = channel_handler_ctx.event_loop().now_ms();
+        channel_handler_ctx.channel().set_last_read_time(now_ms);
         channel_handler_ctx.fire_channel_active();
     }
 
@@ -39,7 +40,9 @@ impl ChannelInboundHandler for HeadHandler {
     }
 
     fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
-        channel_handler_ctx.channel().set_last_read_time(chrono::Local::now().timestamp_millis() as u64);
+        let now_ms = channel_handler_ctx.event_loop().now_ms();
+        channel_handler_ctx.channel().set_last_read_time(now_ms);
+        channel_handler_ctx.channel().record_message_read();
         channel_handler_ctx.fire_channel_read(message);
     }
 
@@ -64,14 +67,10 @@ impl ChannelOutboundHandler for TailHandler {
     }
 
     fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
-        let bytes = message.downcast_ref::<ByteBuf>();
-        match bytes {
-            Some(buf) => {
-                channel_handler_ctx.channel().write_bytebuf(buf);
-            },
-            None => {
-                println!("TailHandler message is not bytebuf");
-            }
+        // socket 只接受ByteBuf, local channel 直接把消息交给对端
+        if !channel_handler_ctx.channel().write_message(message) {
+            channel_handler_ctx.event_loop().metrics.record_exception("UnsupportedMessage");
+            log::warn!(target: trace::TARGET_PIPELINE, "TailHandler message is not supported by transport, channel_id:{}", channel_handler_ctx.channel().id());
         }
     }
 }
@@ -0,0 +1,171 @@
+use std::any::Any;
+use std::fmt::{Debug, Write};
+use std::sync::Arc;
+
+use log::Level;
+
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
+use crate::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
+use crate::transport::file_region::FileRegion;
+use crate::transport::message;
+
+type MessageFormatter = Arc<dyn Fn(&dyn Any) -> Option<String> + Send + Sync>;
+
+///
+/// 打印所有事件和消息的handler, 可以同时加到入站和出站pipeline
+///
+/// ByteBuf 按 偏移/十六进制/ASCII 打印, FileRegion 打印位置和长度, 其它类型通过 `with_debug::<T>()` 注册后用Debug 打印
+///
+/// ```ignore
+/// handler_pipe.add_first(Box::new(LoggingHandler::new(Level::Debug)));
+/// ```
+///
+#[derive(Clone)]
+pub struct LoggingHandler {
+    level: Level,
+    max_dump_length: usize,
+    formatters: Vec<MessageFormatter>,
+}
+
+impl LoggingHandler {
+    pub fn new(level: Level) -> Self {
+        LoggingHandler {
+            level,
+            max_dump_length: 4096,
+            formatters: Vec::new(),
+        }
+    }
+
+    ///
+    /// 最多dump 的字节数，超过部分省略
+    ///
+    pub fn max_dump_length(mut self, max_dump_length: usize) -> Self {
+        self.max_dump_length = max_dump_length;
+        self
+    }
+
+    ///
+    /// 注册一个可以用Debug 打印的消息类型
+    ///
+    pub fn with_debug<T: Any + Debug>(mut self) -> Self {
+        self.formatters.push(Arc::new(|message: &dyn Any| {
+            message.downcast_ref::<T>().map(|m| format!("{:?}", m))
+        }));
+        self
+    }
+
+    fn format_message(&self, message: &dyn Any) -> String {
+        if let Some(bytes) = message::as_bytes(message) {
+            return format!("{}B\n{}", bytes.len(), hex_dump(bytes, self.max_dump_length));
+        }
+        if let Some(s) = message.downcast_ref::<String>() {
+            return format!("{:?}", s);
+        }
+        if let Some(bytes) = message.downcast_ref::<Vec<u8>>() {
+            return format!("{}B\n{}", bytes.len(), hex_dump(bytes, self.max_dump_length));
+        }
+        for formatter in self.formatters.iter() {
+            if let Some(s) = formatter(message) {
+                return s;
+            }
+        }
+        if let Some(region) = message.downcast_ref::<FileRegion>() {
+            return format!("FileRegion(position: {}, count: {})", region.position(), region.count());
+        }
+        "<unregistered message>".to_string()
+    }
+
+    fn log(&self, channel_id: String, event: &str, detail: Option<String>) {
+        match detail {
+            Some(detail) => log::log!(target: trace::TARGET_LOGGING, self.level, "[id: {}] {}: {}", channel_id, event, detail),
+            None => log::log!(target: trace::TARGET_LOGGING, self.level, "[id: {}] {}", channel_id, event),
+        }
+    }
+}
+
+impl ChannelInboundHandler for LoggingHandler {
+    fn id(&self) -> String {
+        return "LoggingHandler".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        if log::log_enabled!(target: trace::TARGET_LOGGING, self.level) {
+            let remote = channel_handler_ctx.channel().remote_addr().map(|a| a.to_string()).unwrap_or_default();
+            self.log(channel_handler_ctx.channel().id().to_string(), "ACTIVE", Some(remote));
+        }
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        if log::log_enabled!(target: trace::TARGET_LOGGING, self.level) {
+            self.log(channel_handler_ctx.channel().id().to_string(), "INACTIVE", None);
+        }
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if log::log_enabled!(target: trace::TARGET_LOGGING, self.level) {
+            let detail = self.format_message(message);
+            self.log(channel_handler_ctx.channel().id().to_string(), "READ", Some(detail));
+        }
+        channel_handler_ctx.fire_channel_read(message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        if log::log_enabled!(target: trace::TARGET_LOGGING, self.level) {
+            self.log(channel_handler_ctx.channel().id().to_string(), "EXCEPTION", Some(error.to_string()));
+        }
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
+
+impl ChannelOutboundHandler for LoggingHandler {
+    fn id(&self) -> String {
+        return "LoggingHandler".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        if log::log_enabled!(target: trace::TARGET_LOGGING, self.level) {
+            let detail = self.format_message(message);
+            self.log(channel_handler_ctx.channel().id().to_string(), "WRITE", Some(detail));
+        }
+        channel_handler_ctx.fire_channel_write(message);
+    }
+}
+
+
+///
+/// 仿照Netty 的格式输出 偏移/十六进制/ASCII, 超过max_length 的部分省略
+///
+pub fn hex_dump(bytes: &[u8], max_length: usize) -> String {
+    let n = bytes.len().min(max_length);
+    let mut out = String::new();
+    out.push_str("         +-------------------------------------------------+\n");
+    out.push_str("         |  0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f |\n");
+    out.push_str("+--------+-------------------------------------------------+----------------+\n");
+    for (row, chunk) in bytes[..n].chunks(16).enumerate() {
+        write!(out, "|{:08x}|", row * 16).unwrap();
+        for i in 0..16 {
+            match chunk.get(i) {
+                Some(b) => write!(out, " {:02x}", b).unwrap(),
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str(" |");
+        for i in 0..16 {
+            match chunk.get(i) {
+                Some(b) if *b >= 0x20 && *b < 0x7f => out.push(*b as char),
+                Some(_) => out.push('.'),
+                None => out.push(' '),
+            }
+        }
+        out.push_str("|\n");
+    }
+    out.push_str("+--------+-------------------------------------------------+----------------+");
+    if n < bytes.len() {
+        write!(out, "\n... {} more bytes", bytes.len() - n).unwrap();
+    }
+    out
+}
@@ -0,0 +1,82 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::core::metrics::MetricsRegistry;
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::ChannelInboundHandlerCtx;
+use crate::handler::handler::ChannelInboundHandler;
+use crate::transport::message;
+
+///
+/// 请求头的最大长度, 超过时直接关闭连接
+///
+const MAX_REQUEST_HEADER_SIZE: usize = 8192;
+
+///
+/// 内置指标监听器的handler, 收到一个完整的HTTP 请求头后返回 Prometheus 文本格式的指标并关闭连接
+///
+pub struct PrometheusMetricsHandler {
+    registry: Arc<MetricsRegistry>,
+    request: Vec<u8>,
+    ///
+    /// 已经响应, 之后收到的数据丢弃
+    ///
+    responded: bool,
+}
+
+impl PrometheusMetricsHandler {
+    pub fn new(registry: Arc<MetricsRegistry>) -> Self {
+        PrometheusMetricsHandler {
+            registry,
+            request: Vec::new(),
+            responded: false,
+        }
+    }
+}
+
+impl ChannelInboundHandler for PrometheusMetricsHandler {
+    fn id(&self) -> String {
+        return "PrometheusMetricsHandler".to_string();
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if self.responded {
+            return;
+        }
+        if let Some(bytes) = message::as_bytes(message) {
+            self.request.extend_from_slice(bytes);
+        }
+        // 请求头没收完
+        if !self.request.windows(4).any(|w| w == b"\r\n\r\n") {
+            if self.request.len() > MAX_REQUEST_HEADER_SIZE {
+                self.request = Vec::new();
+                channel_handler_ctx.close();
+            }
+            return;
+        }
+        self.responded = true;
+        self.request = Vec::new();
+        let body = self.registry.render_prometheus();
+        let response = format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+        let mut buf = ByteBuf::new_from(response.as_bytes());
+        channel_handler_ctx.write_and_flush(&mut buf);
+        // 响应写完后再关闭
+        channel_handler_ctx.close();
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        log::debug!(target: trace::TARGET_PIPELINE, "PrometheusMetricsHandler closing channel on error, channel_id:{}, error:{:?}", channel_handler_ctx.channel().id(), error);
+        channel_handler_ctx.close();
+    }
+}
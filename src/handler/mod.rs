@@ -3,3 +3,6 @@ pub mod channel_handler_ctx;
 pub mod channel_handler_ctx_pipe;
 pub mod handler_pipe;
 pub mod codec;
+pub mod metrics_handler;
+pub mod logging_handler;
+pub mod combined_channel_duplex_handler;
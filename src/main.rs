@@ -12,7 +12,8 @@ use retty::core::bootstrap::Bootstrap;
 use retty::core::eventloop::EventLoopGroup;
 use retty::errors::RettyErrorKind;
 use retty::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
-use retty::handler::codec::first_integer_length_field_decoder::FirstIntegerLengthFieldDecoder;
+use retty::handler::codec::byte_to_message_decoder::ByteToMessageDecoderHandler;
+use retty::handler::codec::length_field_based_frame_decoder::LengthFieldBasedFrameDecoder;
 use retty::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
 use retty::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
 
@@ -37,10 +38,11 @@ impl ChannelInboundHandler for BizHandler {
         let addr = channel_handler_ctx.channel().remote_addr().unwrap();
         println!("业务处理 Handler --> : channel_active 新连接上线: {}", addr);
         channel_handler_ctx.write_and_flush(&mut format!("::: 欢迎你:==>{}", addr));
-        let attr = channel_handler_ctx.channel().get_attribute("User".to_string());
-        let attr = attr.lock().unwrap();
-        let attr = attr.downcast_ref::<String>().unwrap();
-        println!("========================================================:att:::: {}", attr);
+        if let Some(attr) = channel_handler_ctx.channel().get_attribute("User".to_string()) {
+            let attr = attr.lock().unwrap();
+            let attr = attr.downcast_ref::<String>().unwrap();
+            println!("========================================================:att:::: {}", attr);
+        }
     }
 
     fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
@@ -53,10 +55,11 @@ impl ChannelInboundHandler for BizHandler {
         println!("业务处理 Handler  --> :收到消息:{}", msg);
         println!("reactor-excutor :{}", thread::current().name().unwrap());
         channel_handler_ctx.write_and_flush(&mut format!("::: I Love You !!!! :==>{}", msg));
-        let attr = channel_handler_ctx.channel().get_attribute("User".to_string());
-        let attr = attr.lock().unwrap();
-        let attr = attr.downcast_ref::<String>().unwrap();
-        println!("========================================================:att:::: {}", attr);
+        if let Some(attr) = channel_handler_ctx.channel().get_attribute("User".to_string()) {
+            let attr = attr.lock().unwrap();
+            let attr = attr.downcast_ref::<String>().unwrap();
+            println!("========================================================:att:::: {}", attr);
+        }
     }
 
     fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
@@ -188,7 +191,8 @@ fn main() {
             let decoder_handler = Box::new(Decoder::new());
             let biz_handler = Box::new(BizHandler::new());
             let excetion_handler = Box::new(InboundExceptionHandler::new());
-            handler_pipe.add_last(Box::new(FirstIntegerLengthFieldDecoder::new()));
+            // 长度字段是包含自身的大端 u32, 帧保留长度字段, 最大 8MB
+            handler_pipe.add_last(Box::new(ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(8 * 1024 * 1024, 0, 4, -4, 0))));
             handler_pipe.add_last(decoder_handler);
             handler_pipe.add_last(biz_handler);
             handler_pipe.add_last(excetion_handler);
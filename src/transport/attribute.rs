@@ -0,0 +1,128 @@
+use std::any::Any;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use crate::transport::channel::Channel;
+
+///
+/// 带类型的channel 属性key, 一般声明为常量:
+///
+/// ```ignore
+/// const USER: AttributeKey<String> = AttributeKey::new("User");
+///
+/// channel_handler_ctx.channel().attr(&USER).set("lgphp".to_string());
+/// let user = channel_handler_ctx.channel().attr(&USER).get();
+/// ```
+///
+/// 与字符串key 的 set_attribute / get_attribute 共用同一个属性表, 两边写入的值互相可见:
+///
+/// - 通过AttributeKey 写入的值以 `Arc<T>` 的形式保存, get_attribute 读到后要 downcast 成 `Arc<T>`
+/// - set_attribute 写入的 `T` 第一次被 `AttributeKey<T>` 读取时原地包装成 `Arc<T>`, 之后同上
+///
+pub struct AttributeKey<T> {
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> AttributeKey<T> {
+    pub const fn new(name: &'static str) -> AttributeKey<T> {
+        AttributeKey {
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+
+///
+/// 某个channel 上某个AttributeKey 对应的属性
+///
+pub struct Attribute<T> {
+    channel: Arc<Mutex<Channel>>,
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Any + Send + Sync> Attribute<T> {
+    pub(crate) fn new(channel: Arc<Mutex<Channel>>, key: &AttributeKey<T>) -> Attribute<T> {
+        Attribute {
+            channel,
+            name: key.name,
+            _marker: PhantomData,
+        }
+    }
+
+    ///
+    /// 获取属性值，不存在或者类型不匹配返回None
+    ///
+    pub fn get(&self) -> Option<Arc<T>> {
+        let channel = self.channel.lock().unwrap();
+        Attribute::<T>::get_value(&channel, self.name)
+    }
+
+    pub fn set(&self, value: T) {
+        let channel = self.channel.lock().unwrap();
+        channel.attributes().insert(self.name.to_string(), Attribute::<T>::boxed(value));
+    }
+
+    ///
+    /// 不存在时才设置, 返回已经存在的值, 设置成功返回None
+    ///
+    pub fn set_if_absent(&self, value: T) -> Option<Arc<T>> {
+        let channel = self.channel.lock().unwrap();
+        if channel.attributes().contains_key(self.name) {
+            return Attribute::<T>::get_value(&channel, self.name);
+        }
+        channel.attributes().insert(self.name.to_string(), Attribute::<T>::boxed(value));
+        None
+    }
+
+    ///
+    /// 删除属性，返回删除前的值
+    ///
+    pub fn remove(&self) -> Option<Arc<T>> {
+        let channel = self.channel.lock().unwrap();
+        let old = Attribute::<T>::get_value(&channel, self.name);
+        channel.attributes().remove(self.name);
+        old
+    }
+
+    ///
+    /// 当前值等于expected 时替换为new_value, 返回是否替换成功
+    ///
+    pub fn compare_and_set(&self, expected: &T, new_value: T) -> bool where T: PartialEq {
+        let channel = self.channel.lock().unwrap();
+        match Attribute::<T>::get_value(&channel, self.name) {
+            Some(current) if *current == *expected => {
+                channel.attributes().insert(self.name.to_string(), Attribute::<T>::boxed(new_value));
+                true
+            }
+            _ => false
+        }
+    }
+
+    fn get_value(channel: &Channel, name: &str) -> Option<Arc<T>> {
+        let value = channel.attributes().get(name)?;
+        let mut value = value.lock().unwrap();
+        if let Some(typed) = value.downcast_ref::<Arc<T>>() {
+            return Some(typed.clone());
+        }
+        if !value.is::<T>() {
+            return None;
+        }
+        // set_attribute 写入的裸值, 换成和AttributeKey 相同的保存形式
+        let raw = std::mem::replace(&mut *value, Box::new(()));
+        let typed = Arc::new(*raw.downcast::<T>().ok()?);
+        *value = Box::new(typed.clone());
+        Some(typed)
+    }
+
+    fn boxed(value: T) -> Arc<Mutex<Box<dyn Any + Send + Sync>>> {
+        let value: Box<dyn Any + Send + Sync> = Box::new(Arc::new(value));
+        Arc::new(Mutex::new(value))
+    }
+}
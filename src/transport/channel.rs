@@ -1,12 +1,11 @@
 use std::any::Any;
 use std::collections::HashMap;
-use std::io::{Read, Result, Write};
+use std::io::{ErrorKind, Read, Result, Write};
 use std::net::Shutdown;
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use bytebuf_rs::bytebuf::ByteBuf;
 use chashmap::{CHashMap, ReadGuard};
 use crossbeam::channel::{bounded, Receiver, select, Sender, tick};
 use mio::{Poll, PollOpt, Ready, Token};
@@ -14,8 +13,40 @@ use mio::net::TcpStream;
 use rayon_core::ThreadPool;
 
 use crate::core::eventloop::EventLoop;
+use crate::core::trace;
+use crate::core::trace::ChannelSpan;
+use crate::transport::attribute::{Attribute, AttributeKey};
+use crate::transport::channel_id::ChannelId;
+use crate::transport::channel_stats::ChannelStats;
+use crate::transport::file_region::FileRegion;
+use crate::transport::local::{self, LocalAddress, LocalLink};
+use crate::transport::message;
+use crate::transport::outbound_buffer::{ChannelOutboundBuffer, DEFAULT_WRITE_BUFFER_HIGH_WATER_MARK, PendingWrite};
 use crate::handler::channel_handler_ctx_pipe::ChannelOutboundHandlerCtxPipe;
 
+///
+/// channel 底层的传输
+///
+pub(crate) enum Transport {
+    Tcp(TcpStream),
+    ///
+    /// 没有socket, 出入站消息留在内存里, 用于 EmbeddedChannel
+    ///
+    Embedded {
+        local_addr: SocketAddr,
+        remote_addr: SocketAddr,
+    },
+    ///
+    /// 进程内按名字连接, 消息通过 LocalLink 直接交给对端pipeline
+    ///
+    Local {
+        local_addr: LocalAddress,
+        remote_addr: LocalAddress,
+        link: Arc<LocalLink>,
+        client: bool,
+    },
+}
+
 #[derive(Clone)]
 pub enum ChannelOptions {
     NUMBER(usize),
@@ -24,42 +55,59 @@ pub enum ChannelOptions {
 
 
 pub struct Channel {
-    id: Token,
-    stream: TcpStream,
+    id: ChannelId,
+    ///
+    /// 在EventLoop selector 上注册的槽位，channel 关闭后回收复用
+    ///
+    token: Token,
+    stream: Transport,
     closed: bool,
     eventloop: Arc<EventLoop>,
     attribute: CHashMap<String, Arc<Mutex<Box<dyn Any + Send + Sync>>>>,
     inner_ch: (Sender<bool>, Receiver<bool>),
     last_read_time_ms: u64,
     read_idle_timeout_ms: u64,
-
+    ///
+    /// 是否自动读，关闭后不再向selector 注册读事件
+    ///
+    auto_read: bool,
+    ///
+    /// 关闭自动读时，handler 通过 read() 请求的单次读
+    ///
+    read_requested: bool,
+    ///
+    /// 对端已经关闭写, 读到的数据交给pipeline 后关闭channel
+    ///
+    input_shutdown: bool,
+    ///
+    /// socket 写不下时暂存的出站数据和文件区域, 以及高水位和 close_after_flush 的状态
+    ///
+    outbound_buffer: ChannelOutboundBuffer,
+    ///
+    /// 写socket 失败的错误, EventLoop 回收channel 时交给pipeline
+    ///
+    write_error: Option<std::io::Error>,
+    stats: ChannelStats,
+    span: ChannelSpan,
 }
 
+///
+/// 每次read 系统调用读取的最大字节数
+///
+const READ_CHUNK_SIZE: usize = 16384;
 
-impl Clone for Channel {
-    fn clone(&self) -> Self {
-        Channel {
-            id: self.id.clone(),
-            stream: self.stream.try_clone().unwrap(),
-            closed: self.closed.clone(),
-            eventloop: self.eventloop.clone(),
-            attribute: self.attribute.clone(),
-            inner_ch: self.inner_ch.clone(),
-            last_read_time_ms: self.last_read_time_ms,
-            read_idle_timeout_ms: self.read_idle_timeout_ms.clone(),
-        }
-    }
+///
+/// 一次可读事件最多读取的字节数, 超过后重新排队一次读, 其它channel 不会被一个连接饿死
+///
+const MAX_READ_BYTES_PER_EVENT: usize = 16 * READ_CHUNK_SIZE;
 
-    fn clone_from(&mut self, source: &Self) {
-        *self = source.clone()
-    }
-}
 
 impl Channel {
-    pub fn create(id: Token, opts: HashMap<String, ChannelOptions>, eventloop: Arc<EventLoop>, stream: TcpStream,
+    pub fn create(id: ChannelId, token: Token, opts: HashMap<String, ChannelOptions>, eventloop: Arc<EventLoop>, stream: TcpStream,
     ) -> Channel {
         let tcp_stream = stream.try_clone().unwrap();
         let mut read_idle_timeout_ms = 50000u64;// 50 secs
+        let mut write_buffer_high_water_mark = DEFAULT_WRITE_BUFFER_HIGH_WATER_MARK;
         for (k, ref v) in opts.iter() {
             match k.as_ref() {
                 "read_idle_timeout_ms" => {
@@ -70,6 +118,14 @@ impl Channel {
                         ChannelOptions::BOOL(_) => {}
                     }
                 }
+                "write_buffer_high_water_mark" => {
+                    match v {
+                        ChannelOptions::NUMBER(mark) => {
+                            write_buffer_high_water_mark = *mark;
+                        }
+                        ChannelOptions::BOOL(_) => {}
+                    }
+                }
                 "ttl" => {
                     match v {
                         ChannelOptions::NUMBER(ttl) => {
@@ -121,35 +177,356 @@ impl Channel {
                 _ => {}
             }
         }
+        let span = trace::channel_span(id, tcp_stream.peer_addr().ok());
+        let created_time_ms = eventloop.now_ms();
         Channel {
             id,
-            stream: tcp_stream,
+            token,
+            stream: Transport::Tcp(tcp_stream),
             closed: false,
             eventloop,
             attribute: CHashMap::new(),
             inner_ch: bounded(1024),
             last_read_time_ms: 0,
             read_idle_timeout_ms: read_idle_timeout_ms.clone(),
+            auto_read: true,
+            read_requested: false,
+            input_shutdown: false,
+            outbound_buffer: ChannelOutboundBuffer::new(write_buffer_high_water_mark),
+            write_error: None,
+            stats: ChannelStats {
+                created_time_ms,
+                ..ChannelStats::default()
+            },
+            span,
+        }
+    }
+
+    ///
+    /// 创建没有socket 的channel, 供 EmbeddedChannel 使用
+    ///
+    pub(crate) fn create_embedded(id: ChannelId, token: Token, eventloop: Arc<EventLoop>, local_addr: SocketAddr, remote_addr: SocketAddr) -> Channel {
+        let created_time_ms = eventloop.now_ms();
+        Channel {
+            id,
+            token,
+            stream: Transport::Embedded {
+                local_addr,
+                remote_addr,
+            },
+            closed: false,
+            eventloop,
+            attribute: CHashMap::new(),
+            inner_ch: bounded(1024),
+            last_read_time_ms: 0,
+            read_idle_timeout_ms: 50000u64,
+            auto_read: true,
+            read_requested: false,
+            input_shutdown: false,
+            outbound_buffer: ChannelOutboundBuffer::new(DEFAULT_WRITE_BUFFER_HIGH_WATER_MARK),
+            write_error: None,
+            stats: ChannelStats {
+                created_time_ms,
+                ..ChannelStats::default()
+            },
+            span: trace::channel_span(id, Some(remote_addr)),
+        }
+    }
+
+    ///
+    /// 创建进程内的 local channel, 不注册到selector
+    ///
+    pub(crate) fn create_local(id: ChannelId, eventloop: Arc<EventLoop>, local_addr: LocalAddress, remote_addr: LocalAddress, link: Arc<LocalLink>, client: bool) -> Channel {
+        let created_time_ms = eventloop.now_ms();
+        Channel {
+            id,
+            token: local::LOCAL_TOKEN,
+            stream: Transport::Local {
+                local_addr,
+                remote_addr,
+                link,
+                client,
+            },
+            closed: false,
+            eventloop,
+            attribute: CHashMap::new(),
+            inner_ch: bounded(1024),
+            last_read_time_ms: 0,
+            read_idle_timeout_ms: 50000u64,
+            auto_read: true,
+            read_requested: false,
+            input_shutdown: false,
+            outbound_buffer: ChannelOutboundBuffer::new(DEFAULT_WRITE_BUFFER_HIGH_WATER_MARK),
+            write_error: None,
+            stats: ChannelStats {
+                created_time_ms,
+                ..ChannelStats::default()
+            },
+            span: trace::channel_span(id, None),
         }
     }
 
+    pub fn id(&self) -> ChannelId {
+        self.id
+    }
+
+    pub(crate) fn token(&self) -> Token {
+        self.token
+    }
+
+    pub(crate) fn span(&self) -> ChannelSpan {
+        self.span.clone()
+    }
+
     pub(crate) fn remote_addr(&self) -> Result<SocketAddr> {
-        self.stream.peer_addr()
+        match &self.stream {
+            Transport::Tcp(stream) => stream.peer_addr(),
+            Transport::Embedded { remote_addr, .. } => Ok(*remote_addr),
+            Transport::Local { .. } => Err(std::io::Error::new(ErrorKind::AddrNotAvailable, "local channel has no socket address")),
+        }
     }
 
     pub(crate) fn local_addr(&self) -> Result<SocketAddr> {
-        self.stream.local_addr()
+        match &self.stream {
+            Transport::Tcp(stream) => stream.local_addr(),
+            Transport::Embedded { local_addr, .. } => Ok(*local_addr),
+            Transport::Local { .. } => Err(std::io::Error::new(ErrorKind::AddrNotAvailable, "local channel has no socket address")),
+        }
+    }
+
+    ///
+    /// local channel 的 (本端, 对端) 地址
+    ///
+    pub(crate) fn local_addresses(&self) -> Option<(LocalAddress, LocalAddress)> {
+        match &self.stream {
+            Transport::Local { local_addr, remote_addr, .. } => Some((local_addr.clone(), remote_addr.clone())),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn set_read_idle_timeout_ms(&mut self, ms: u64) {
+        self.read_idle_timeout_ms = ms;
+    }
+
+    ///
+    /// 出站pipeline 末端写出消息, socket 只接受ByteBuf、PooledBuffer 和FileRegion, local channel 把消息交给对端,
+    /// 不支持的消息类型返回false
+    ///
+    pub(crate) fn write_message(&mut self, message: &mut dyn Any) -> bool {
+        let (link, client) = match &self.stream {
+            Transport::Local { link, client, .. } => (link.clone(), *client),
+            _ => {
+                if let Some(region) = message.downcast_ref::<FileRegion>() {
+                    self.write_file_region(region.clone());
+                    return true;
+                }
+                return match message::as_bytes(message) {
+                    Some(bytes) => {
+                        self.write_bytes(bytes);
+                        true
+                    }
+                    None => false,
+                };
+            }
+        };
+        let message = match link.take_message(message) {
+            Some(message) => message,
+            None => return false,
+        };
+        if self.closed {
+            return true;
+        }
+        self.stats.messages_written += 1;
+        self.stats.last_write_time_ms = self.eventloop.now_ms();
+        self.eventloop.stats.record_message_written();
+        link.deliver(client, message);
+        true
+    }
+
+    ///
+    /// 写出一条消息, socket 写不下的部分放进出站缓冲区
+    ///
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
+        if self.closed {
+            return;
+        }
+        self.stats.messages_written += 1;
+        self.eventloop.stats.record_message_written();
+        if !self.outbound_buffer.is_empty() {
+            // 保证顺序，先排队
+            self.outbound_buffer.push_bytes(bytes);
+            self.flush_outbound();
+            return;
+        }
+        let written = self.write_to_socket(bytes);
+        if self.closed {
+            return;
+        }
+        if written < bytes.len() {
+            self.outbound_buffer.push_bytes(&bytes[written..]);
+            self.update_write_suspended();
+            self.reregister();
+        }
+        if let Transport::Tcp(stream) = &mut self.stream {
+            stream.flush();
+        }
+    }
+
+    ///
+    /// 写出文件区域, socket 写不下的部分放进出站缓冲区
+    ///
+    pub(crate) fn write_file_region(&mut self, mut region: FileRegion) {
+        if self.closed {
+            return;
+        }
+        self.stats.messages_written += 1;
+        self.eventloop.stats.record_message_written();
+        if !self.outbound_buffer.is_empty() {
+            self.outbound_buffer.push_file(region);
+            self.flush_outbound();
+            return;
+        }
+        if !self.transfer_file_region(&mut region) {
+            self.outbound_buffer.push_file(region);
+            self.update_write_suspended();
+            self.reregister();
+        }
+    }
+
+    ///
+    /// 可写事件到达时，把出站缓冲区的数据写进socket
+    ///
+    pub(crate) fn flush_outbound(&mut self) {
+        if self.outbound_buffer.is_empty() {
+            return;
+        }
+        while let Some(pending) = self.outbound_buffer.pop_front() {
+            match pending {
+                PendingWrite::Bytes(mut bytes) => {
+                    let written = self.write_to_socket(&bytes);
+                    if written < bytes.len() {
+                        bytes.drain(..written);
+                        self.outbound_buffer.push_front(PendingWrite::Bytes(bytes));
+                        break;
+                    }
+                }
+                PendingWrite::File(mut region) => {
+                    if !self.transfer_file_region(&mut region) {
+                        self.outbound_buffer.push_front(PendingWrite::File(region));
+                        break;
+                    }
+                }
+            }
+        }
+        if self.closed {
+            return;
+        }
+        let (suspended, write_suspended) = self.update_write_suspended();
+        if self.outbound_buffer.is_empty() {
+            if self.outbound_buffer.is_close_pending() {
+                self.close();
+                return;
+            }
+            // 写完了，取消写事件
+            self.reregister();
+        } else if suspended != write_suspended {
+            self.reregister();
+        }
+    }
+
+    ///
+    /// 出站缓冲区超过高水位时暂停读, 对端不读时不再继续读请求、堆积响应
+    ///
+    fn update_write_suspended(&mut self) -> (bool, bool) {
+        let (before, after) = self.outbound_buffer.update_suspended();
+        if before && !after && self.read_requested {
+            self.eventloop.request_read(self.token, self.id);
+        }
+        (before, after)
     }
 
-    pub(crate) fn write_bytebuf(&mut self, buf: &ByteBuf) {
-        self.stream.write(buf.available_bytes());
-        self.stream.flush();
+    ///
+    /// 返回区域是否已经写完, 文件读取失败时区域剩下的部分没法补发, 直接关闭channel
+    ///
+    fn transfer_file_region(&mut self, region: &mut FileRegion) -> bool {
+        let stream = match &self.stream {
+            Transport::Tcp(stream) => stream,
+            Transport::Embedded { .. } | Transport::Local { .. } => {
+                region.skip_remaining();
+                return true;
+            }
+        };
+        match region.transfer_to(stream) {
+            Ok(n) if n > 0 => {
+                self.stats.write_count += 1;
+                self.stats.bytes_written += n as u64;
+                self.stats.last_write_time_ms = self.eventloop.now_ms();
+                self.eventloop.stats.record_write(n);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!(target: trace::TARGET_CHANNEL, "file region transfer failed, channel_id:{}, error:{}", self.id, e);
+                self.write_error = Some(e);
+                self.close();
+                return true;
+            }
+        }
+        region.is_complete()
+    }
+
+    fn write_to_socket(&mut self, bytes: &[u8]) -> usize {
+        let stream = match &mut self.stream {
+            Transport::Tcp(stream) => stream,
+            Transport::Embedded { .. } | Transport::Local { .. } => return bytes.len(),
+        };
+        let mut written = 0;
+        let mut error = None;
+        while written < bytes.len() {
+            let ret = stream.write(&bytes[written..]);
+            match ret {
+                Ok(0) => break,
+                Ok(n) => {
+                    written += n;
+                    self.stats.write_count += 1;
+                    self.stats.bytes_written += n as u64;
+                    self.eventloop.stats.record_write(n);
+                }
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+        if written > 0 {
+            self.stats.last_write_time_ms = self.eventloop.now_ms();
+        }
+        if let Some(e) = error {
+            // EPIPE / ECONNRESET 之后写不出去了, 剩下的数据丢弃
+            log::debug!(target: trace::TARGET_CHANNEL, "write failed, channel_id:{}, error:{}", self.id, e);
+            self.write_error = Some(e);
+            self.close();
+        }
+        written
     }
 
     pub(crate) fn set_last_read_time(&mut self, ms: u64) {
         self.last_read_time_ms = ms;
     }
 
+    pub(crate) fn record_message_read(&mut self) {
+        self.stats.messages_read += 1;
+        self.eventloop.stats.record_message_read();
+    }
+
+    pub fn stats(&self) -> ChannelStats {
+        let mut stats = self.stats.clone();
+        stats.last_read_time_ms = self.last_read_time_ms;
+        stats.outbound_buffer_size = self.outbound_buffer.pending_bytes();
+        stats
+    }
+
     pub(crate) fn last_read_time_ms(&self) -> u64 {
         self.last_read_time_ms
     }
@@ -158,28 +535,210 @@ impl Channel {
         self.read_idle_timeout_ms
     }
 
+    ///
+    /// 按EventLoop 的时钟判断是否读空闲超时
+    ///
+    pub(crate) fn is_read_idle(&self) -> bool {
+        self.eventloop.now_ms().saturating_sub(self.last_read_time_ms) > self.read_idle_timeout_ms
+    }
+
+    pub(crate) fn attributes(&self) -> &CHashMap<String, Arc<Mutex<Box<dyn Any + Send + Sync>>>> {
+        &self.attribute
+    }
+
     pub fn register(&self, poll: &Poll) {
-        poll.register(
-            &self.stream,
-            self.id,
-            Ready::readable(),
-            PollOpt::edge(),
-        );
+        if let Transport::Tcp(stream) = &self.stream {
+            poll.register(
+                stream,
+                self.token,
+                self.interest(),
+                PollOpt::edge(),
+            );
+        }
+    }
+
+    ///
+    /// 根据auto_read 重新注册感兴趣的事件,
+    /// epoll 在修改事件时会重新检查就绪状态，所以已经在内核缓冲区里的数据不会丢失唤醒
+    ///
+    fn reregister(&self) {
+        if let Transport::Tcp(stream) = &self.stream {
+            self.eventloop.selector.reregister(
+                stream,
+                self.token,
+                self.interest(),
+                PollOpt::edge(),
+            );
+        }
+    }
+
+    ///
+    /// 从selector 上注销, 之后token 才能安全的分配给新的channel
+    ///
+    pub(crate) fn deregister(&self, poll: &Poll) {
+        if let Transport::Tcp(stream) = &self.stream {
+            poll.deregister(stream);
+        }
+    }
+
+    #[inline]
+    fn interest(&self) -> Ready {
+        let mut interest = Ready::empty();
+        if self.auto_read && !self.outbound_buffer.is_suspended() {
+            interest = interest | Ready::readable();
+        }
+        if !self.outbound_buffer.is_empty() {
+            interest = interest | Ready::writable();
+        }
+        interest
+    }
+
+    pub(crate) fn set_auto_read(&mut self, auto_read: bool) {
+        if self.auto_read == auto_read {
+            return;
+        }
+        self.auto_read = auto_read;
+        self.reregister();
+        if auto_read {
+            // 暂停期间到达的数据, 主动读一次
+            self.eventloop.request_read(self.token, self.id);
+        }
     }
 
+    pub(crate) fn is_auto_read(&self) -> bool {
+        self.auto_read
+    }
+
+    ///
+    /// 请求读一次，auto_read 关闭时也会读
+    ///
+    pub(crate) fn request_read(&mut self) {
+        self.read_requested = true;
+        self.eventloop.request_read(self.token, self.id);
+    }
+
+    ///
+    /// EventLoop 读之前调用, 返回这次是否允许读
+    ///
+    pub(crate) fn take_read_permit(&mut self) -> bool {
+        if self.outbound_buffer.is_suspended() {
+            return false;
+        }
+        if self.auto_read {
+            self.read_requested = false;
+            return true;
+        }
+        let permit = self.read_requested;
+        self.read_requested = false;
+        permit
+    }
+
+    ///
+    /// 读到 WouldBlock、EOF 或者读满 MAX_READ_BYTES_PER_EVENT 为止;
+    /// 读满时socket 里可能还有数据, 边缘触发不会再通知, 自动读时和 request_read 一样排队一次读,
+    /// 关闭自动读时等下一次读请求
+    ///
     pub fn read(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
-        self.stream.read_to_end(buf)
+        let stream = match &mut self.stream {
+            Transport::Tcp(stream) => stream,
+            Transport::Embedded { .. } | Transport::Local { .. } => return Err(std::io::Error::from(ErrorKind::WouldBlock)),
+        };
+        let start = buf.len();
+        loop {
+            let len = buf.len();
+            if len - start >= MAX_READ_BYTES_PER_EVENT {
+                if self.auto_read {
+                    self.eventloop.request_read(self.token, self.id);
+                }
+                return Ok(len - start);
+            }
+            buf.resize(len + READ_CHUNK_SIZE.min(MAX_READ_BYTES_PER_EVENT - (len - start)), 0);
+            let ret = stream.read(&mut buf[len..]);
+            self.stats.read_count += 1;
+            match ret {
+                Ok(0) => {
+                    buf.truncate(len);
+                    self.eventloop.stats.record_read(0);
+                    self.input_shutdown = true;
+                    return Ok(len - start);
+                }
+                Ok(n) => {
+                    buf.truncate(len + n);
+                    self.stats.bytes_read += n as u64;
+                    self.eventloop.stats.record_read(n);
+                }
+                Err(e) => {
+                    buf.truncate(len);
+                    self.eventloop.stats.record_read(0);
+                    if e.kind() != ErrorKind::Interrupted {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+
+    ///
+    /// 上一次read 读到了EOF
+    ///
+    pub(crate) fn is_input_shutdown(&self) -> bool {
+        self.input_shutdown
     }
 
+    ///
+    /// 出站缓冲区写完后再关闭, 关闭前写出的数据不会被截断
+    ///
+    pub fn close_after_flush(&mut self) {
+        if self.outbound_buffer.is_empty() {
+            self.close();
+        } else {
+            self.outbound_buffer.set_close_pending();
+        }
+    }
 
+    ///
+    /// 立即关闭, 没写完的出站数据丢弃
+    ///
     pub fn close(&mut self) {
-        self.stream.shutdown(Shutdown::Both);
+        match &self.stream {
+            Transport::Tcp(stream) => {
+                stream.shutdown(Shutdown::Both);
+                if !self.closed {
+                    // 由EventLoop 注销并触发 channel_inactive
+                    self.eventloop.request_close(self.token, self.id);
+                }
+            }
+            Transport::Local { link, .. } if !self.closed => link.close(),
+            _ => {}
+        }
+        self.closed = true;
+    }
+
+    ///
+    /// 只标记关闭, local channel 的对端关闭时使用
+    ///
+    pub(crate) fn mark_closed(&mut self) {
         self.closed = true;
     }
 
     pub fn is_closed(&self) -> bool {
         self.closed
     }
+
+    ///
+    /// 已经关闭, 或者调用了 close_after_flush 正在等待出站数据写完
+    ///
+    pub(crate) fn is_closing(&self) -> bool {
+        self.closed || self.outbound_buffer.is_close_pending()
+    }
+
+    ///
+    /// 取出写socket 失败的错误
+    ///
+    pub(crate) fn take_write_error(&mut self) -> Option<std::io::Error> {
+        self.write_error.take()
+    }
 }
 
 ///
@@ -196,19 +755,39 @@ impl InboundChannelCtx {
         }
     }
 
-    pub fn id(&self) -> String {
+    pub fn id(&self) -> ChannelId {
         let channel = self.channel.lock().unwrap();
-        format!("{}", channel.id.0).clone()
+        channel.id()
+    }
+
+    ///
+    /// 字符串形式的id, 即 ChannelId 的长格式, 给原来按 String 使用 id() 的代码迁移用
+    ///
+    pub fn id_string(&self) -> String {
+        self.id().as_long_text()
     }
+    ///
+    /// 按字符串key 设置属性, 值的保存形式见 AttributeKey
+    ///
     pub fn set_attribute(&mut self, key: String, value: Box<dyn Any + Send + Sync>) {
         let channel = self.channel.lock().unwrap();
         channel.attribute.insert(key, Arc::new(Mutex::new(value)));
     }
 
-    pub fn get_attribute(&self, key: String) -> Arc<Mutex<Box<dyn Any + Send + Sync>>> {
+    ///
+    /// 按字符串key 获取属性, 不存在返回None
+    ///
+    pub fn get_attribute(&self, key: String) -> Option<Arc<Mutex<Box<dyn Any + Send + Sync>>>> {
         let channel = self.channel.lock().unwrap();
-        let v = channel.attribute.get(key.as_str()).unwrap();
-        v.clone()
+        let v = channel.attribute.get(key.as_str())?;
+        Some(v.clone())
+    }
+
+    ///
+    /// 带类型的属性
+    ///
+    pub fn attr<T: Any + Send + Sync>(&self, key: &AttributeKey<T>) -> Attribute<T> {
+        Attribute::new(self.channel.clone(), key)
     }
 
     pub fn remote_addr(&self) -> Result<SocketAddr> {
@@ -221,15 +800,34 @@ impl InboundChannelCtx {
         channel.local_addr()
     }
 
+    ///
+    /// local channel 的 (本端, 对端) 地址, socket channel 返回None
+    ///
+    pub fn local_addresses(&self) -> Option<(LocalAddress, LocalAddress)> {
+        let channel = self.channel.lock().unwrap();
+        channel.local_addresses()
+    }
+
 
     pub fn is_active(&self) -> bool {
         let channel = self.channel.lock().unwrap();
         !channel.is_closed()
     }
 
+    ///
+    /// 调用过 close() 之后为true, 出站数据还没写完时 is_active 仍然为true
+    ///
+    pub(crate) fn is_closing(&self) -> bool {
+        let channel = self.channel.lock().unwrap();
+        channel.is_closing()
+    }
+
+    ///
+    /// 已经写出的数据发完后关闭
+    ///
     pub fn close(&mut self) {
         let mut channel = self.channel.lock().unwrap();
-        channel.close()
+        channel.close_after_flush()
     }
 
     pub(crate) fn set_last_read_time(&mut self, ms: u64) {
@@ -237,6 +835,19 @@ impl InboundChannelCtx {
         channel.last_read_time_ms = ms;
     }
 
+    pub(crate) fn record_message_read(&mut self) {
+        let mut channel = self.channel.lock().unwrap();
+        channel.record_message_read();
+    }
+
+    ///
+    /// 当前channel 的流量统计
+    ///
+    pub fn stats(&self) -> ChannelStats {
+        let channel = self.channel.lock().unwrap();
+        channel.stats()
+    }
+
     pub(crate) fn last_read_time_ms(&self) -> u64 {
         let channel = self.channel.lock().unwrap();
         channel.last_read_time_ms()
@@ -246,6 +857,27 @@ impl InboundChannelCtx {
         let channel = self.channel.lock().unwrap();
         channel.read_idle_timeout_ms()
     }
+
+    ///
+    /// 开关自动读, 关闭后EventLoop 不再读取这个channel, 直到重新打开或者调用 read()
+    ///
+    pub fn set_auto_read(&mut self, auto_read: bool) {
+        let mut channel = self.channel.lock().unwrap();
+        channel.set_auto_read(auto_read)
+    }
+
+    pub fn is_auto_read(&self) -> bool {
+        let channel = self.channel.lock().unwrap();
+        channel.is_auto_read()
+    }
+
+    ///
+    /// 请求EventLoop 读一次
+    ///
+    pub fn read(&mut self) {
+        let mut channel = self.channel.lock().unwrap();
+        channel.request_read()
+    }
 }
 
 pub struct OutboundChannelCtx {
@@ -259,14 +891,33 @@ impl OutboundChannelCtx {
         }
     }
 
-    pub fn id(&self) -> String {
+    pub fn id(&self) -> ChannelId {
         let channel = self.channel.lock().unwrap();
-        format!("{}", channel.id.0).clone()
+        channel.id()
+    }
+
+    ///
+    /// 字符串形式的id, 即 ChannelId 的长格式, 给原来按 String 使用 id() 的代码迁移用
+    ///
+    pub fn id_string(&self) -> String {
+        self.id().as_long_text()
     }
 
-    pub(crate) fn write_bytebuf(&mut self, buf: &ByteBuf) {
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) {
         let mut channel = self.channel.lock().unwrap();
-        channel.write_bytebuf(buf);
+        channel.write_bytes(bytes);
+    }
+
+    pub(crate) fn write_message(&mut self, message: &mut dyn Any) -> bool {
+        let mut channel = self.channel.lock().unwrap();
+        channel.write_message(message)
+    }
+
+    ///
+    /// 带类型的属性, 与入站共享同一个属性表
+    ///
+    pub fn attr<T: Any + Send + Sync>(&self, key: &AttributeKey<T>) -> Attribute<T> {
+        Attribute::new(self.channel.clone(), key)
     }
 
     pub fn remote_addr(&self) -> Result<SocketAddr> {
@@ -283,4 +934,20 @@ impl OutboundChannelCtx {
         let channel = self.channel.lock().unwrap();
         !channel.is_closed()
     }
-}
\ No newline at end of file
+
+    ///
+    /// 已经写出的数据发完后关闭
+    ///
+    pub fn close(&mut self) {
+        let mut channel = self.channel.lock().unwrap();
+        channel.close_after_flush()
+    }
+
+    ///
+    /// 当前channel 的流量统计
+    ///
+    pub fn stats(&self) -> ChannelStats {
+        let channel = self.channel.lock().unwrap();
+        channel.stats()
+    }
+}
@@ -0,0 +1,58 @@
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use uuid::Uuid;
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+///
+/// 全局唯一的channel 标识, 由 进程id、创建时间戳、自增序号、随机数 组成，不会复用
+///
+/// 与mio 的 Token 无关, Token 只是EventLoop 里的注册槽位，会被回收复用
+///
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ChannelId {
+    process_id: u32,
+    timestamp_ms: u64,
+    sequence: u64,
+    random: u32,
+}
+
+impl ChannelId {
+    pub fn new_instance() -> ChannelId {
+        ChannelId {
+            process_id: std::process::id(),
+            timestamp_ms: chrono::Local::now().timestamp_millis() as u64,
+            sequence: SEQUENCE.fetch_add(1, Ordering::Relaxed),
+            random: Uuid::new_v4().as_u128() as u32,
+        }
+    }
+
+    ///
+    /// 短格式，8位十六进制随机数, 用于日志
+    ///
+    pub fn as_short_text(&self) -> String {
+        format!("{:08x}", self.random)
+    }
+
+    ///
+    /// 长格式: 进程id-序号-时间戳-随机数
+    ///
+    pub fn as_long_text(&self) -> String {
+        format!("{:08x}-{:016x}-{:016x}-{:08x}", self.process_id, self.sequence, self.timestamp_ms, self.random)
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+}
+
+impl Display for ChannelId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_short_text())
+    }
+}
@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+///
+/// 单个channel 的流量统计快照
+///
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ChannelStats {
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// read 系统调用次数
+    pub read_count: u64,
+    /// write 系统调用次数
+    pub write_count: u64,
+    /// 进入入站pipeline 的消息数
+    pub messages_read: u64,
+    /// 到达出站pipeline 末尾写出的消息数
+    pub messages_written: u64,
+    pub created_time_ms: u64,
+    pub last_read_time_ms: u64,
+    pub last_write_time_ms: u64,
+    /// 还没写进socket 的出站字节数, 包括没发完的文件区域
+    pub outbound_buffer_size: usize,
+}
+
+
+///
+/// EventLoop 上所有channel 的累计统计
+///
+pub struct EventLoopStats {
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    read_count: AtomicU64,
+    write_count: AtomicU64,
+    messages_read: AtomicU64,
+    messages_written: AtomicU64,
+}
+
+impl EventLoopStats {
+    pub fn new() -> EventLoopStats {
+        EventLoopStats {
+            bytes_read: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            read_count: AtomicU64::new(0),
+            write_count: AtomicU64::new(0),
+            messages_read: AtomicU64::new(0),
+            messages_written: AtomicU64::new(0),
+        }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(Ordering::Relaxed)
+    }
+
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Relaxed)
+    }
+
+    pub fn read_count(&self) -> u64 {
+        self.read_count.load(Ordering::Relaxed)
+    }
+
+    pub fn write_count(&self) -> u64 {
+        self.write_count.load(Ordering::Relaxed)
+    }
+
+    pub fn messages_read(&self) -> u64 {
+        self.messages_read.load(Ordering::Relaxed)
+    }
+
+    pub fn messages_written(&self) -> u64 {
+        self.messages_written.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_read(&self, n: usize) {
+        self.read_count.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_write(&self, n: usize) {
+        self.write_count.fetch_add(1, Ordering::Relaxed);
+        self.bytes_written.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_message_read(&self) {
+        self.messages_read.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_message_written(&self) {
+        self.messages_written.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,303 @@
+use std::any::Any;
+use std::collections::VecDeque;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::core::bootstrap::Bootstrap;
+use crate::core::clock::FakeClock;
+use crate::core::eventloop::EventLoop;
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
+use crate::handler::channel_handler_ctx_pipe::{ChannelInboundHandlerCtxPipe, ChannelOutboundHandlerCtxPipe};
+use crate::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+use crate::transport::channel::{Channel, InboundChannelCtx};
+use crate::transport::channel_id::ChannelId;
+use crate::transport::file_region::FileRegion;
+use crate::transport::message::{self, MessageTaker};
+
+type MessageQueue = Arc<Mutex<VecDeque<Box<dyn Any + Send>>>>;
+
+
+///
+/// 不需要socket 的内存channel, 用于测试handler
+///
+/// 与Bootstrap 创建相同的入站/出站ctx pipeline, 从入站末端流出的消息可以用 `read_inbound` 读取,
+/// 从出站末端流出的消息可以用 `read_outbound` 读取
+///
+/// 默认可以取出 `ByteBuf`、`String`、`Vec<u8>` 和 `Box<dyn Any + Send>` 类型的消息,
+/// 其它类型需要用 `register_message_type::<T>()` 注册; 写到出站末端的 `FileRegion` 读成 `ByteBuf`
+///
+/// ```ignore
+/// let mut channel = EmbeddedChannel::new(inbound_pipe, ChannelOutboundHandlerPipe::new());
+/// channel.write_inbound(ByteBuf::new_from(&[0, 0, 0, 4]));
+/// let frame = channel.read_inbound::<ByteBuf>().unwrap();
+/// ```
+///
+pub struct EmbeddedChannel {
+    event_loop: Arc<EventLoop>,
+    clock: Arc<FakeClock>,
+    channel: Arc<Mutex<Channel>>,
+    inbound_pipe: ChannelInboundHandlerCtxPipe,
+    outbound_pipe: Arc<Mutex<ChannelOutboundHandlerCtxPipe>>,
+    takers: Arc<Mutex<Vec<MessageTaker>>>,
+    inbound_messages: MessageQueue,
+    outbound_messages: MessageQueue,
+    exceptions: Arc<Mutex<VecDeque<RettyErrorKind>>>,
+}
+
+impl EmbeddedChannel {
+    pub fn new(inbound: ChannelInboundHandlerPipe, outbound: ChannelOutboundHandlerPipe) -> EmbeddedChannel {
+        let clock = Arc::new(FakeClock::new(chrono::Local::now().timestamp_millis() as u64));
+        let event_loop = Arc::new(EventLoop::new_with_clock(0, clock.clone()));
+        // 任务队列和定时器由 run_pending_tasks / advance_time_ms 推进
+        event_loop.driven.store(true, std::sync::atomic::Ordering::Relaxed);
+
+        let local_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let remote_addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        let token = event_loop.allocate_token();
+        let channel = Channel::create_embedded(ChannelId::new_instance(), token, event_loop.clone(), local_addr, remote_addr);
+        let channel = Arc::new(Mutex::new(channel));
+
+        let takers = Arc::new(Mutex::new(message::default_takers()));
+        let inbound_messages: MessageQueue = Arc::new(Mutex::new(VecDeque::new()));
+        let outbound_messages: MessageQueue = Arc::new(Mutex::new(VecDeque::new()));
+        let exceptions = Arc::new(Mutex::new(VecDeque::new()));
+
+        let outbound_tail = EmbeddedOutboundCollector {
+            takers: takers.clone(),
+            messages: outbound_messages.clone(),
+        };
+        let outbound_pipe = Bootstrap::create_channel_outbound_ctx_pipe(outbound, event_loop.clone(), channel.clone(), Box::new(outbound_tail));
+        let outbound_pipe = Arc::new(Mutex::new(outbound_pipe));
+
+        let inbound_tail = EmbeddedInboundCollector {
+            takers: takers.clone(),
+            messages: inbound_messages.clone(),
+            exceptions: exceptions.clone(),
+        };
+        let mut inbound = inbound;
+        inbound.add_last(Box::new(inbound_tail));
+        let inbound_pipe = Bootstrap::create_channel_inbound_ctx_pipe(inbound, event_loop.clone(), channel.clone(), outbound_pipe.clone());
+
+        EmbeddedChannel {
+            event_loop,
+            clock,
+            channel,
+            inbound_pipe,
+            outbound_pipe,
+            takers,
+            inbound_messages,
+            outbound_messages,
+            exceptions,
+        }
+    }
+
+    ///
+    /// 注册一个可以从pipeline 末端取出的消息类型, 取出时clone 一份
+    ///
+    pub fn register_message_type<T: Any + Send + Clone>(&mut self) -> &mut Self {
+        self.takers.lock().unwrap().push(Arc::new(|message: &mut dyn Any| {
+            message.downcast_ref::<T>().map(|m| {
+                let boxed: Box<dyn Any + Send> = Box::new(m.clone());
+                boxed
+            })
+        }));
+        self
+    }
+
+    pub fn fire_channel_active(&mut self) {
+        self.inbound_pipe.head_channel_active();
+    }
+
+    pub fn fire_channel_inactive(&mut self) {
+        self.inbound_pipe.head_channel_inactive();
+    }
+
+    pub fn fire_channel_exception(&mut self, error: RettyErrorKind) {
+        self.inbound_pipe.head_channel_exception(error);
+    }
+
+    ///
+    /// 从入站pipeline 头部写入一条消息
+    ///
+    pub fn write_inbound<T: Any>(&mut self, mut message: T) {
+        self.inbound_pipe.head_channel_read(&mut message);
+    }
+
+    ///
+    /// 从出站pipeline 头部写入一条消息
+    ///
+    pub fn write_outbound<T: Any>(&mut self, mut message: T) {
+        let pipe = self.outbound_pipe.lock().unwrap();
+        pipe.head_channel_write(&mut message);
+    }
+
+    ///
+    /// 读取从入站pipeline 末端流出的消息，类型不匹配时返回None, 消息保留在队列里
+    ///
+    pub fn read_inbound<T: Any>(&mut self) -> Option<T> {
+        EmbeddedChannel::pop_message(&self.inbound_messages)
+    }
+
+    ///
+    /// 读取从出站pipeline 末端流出的消息
+    ///
+    pub fn read_outbound<T: Any>(&mut self) -> Option<T> {
+        EmbeddedChannel::pop_message(&self.outbound_messages)
+    }
+
+    ///
+    /// 读取传到入站pipeline 末端没有被处理的异常
+    ///
+    pub fn read_exception(&mut self) -> Option<RettyErrorKind> {
+        self.exceptions.lock().unwrap().pop_front()
+    }
+
+    pub fn inbound_messages_len(&self) -> usize {
+        self.inbound_messages.lock().unwrap().len()
+    }
+
+    pub fn outbound_messages_len(&self) -> usize {
+        self.outbound_messages.lock().unwrap().len()
+    }
+
+    ///
+    /// 执行通过 EventLoop::execute 提交的任务
+    ///
+    pub fn run_pending_tasks(&mut self) {
+        self.event_loop.run_pending_tasks();
+    }
+
+    ///
+    /// 推进时钟, 执行到期的定时任务并检测读空闲
+    ///
+    pub fn advance_time_ms(&mut self, ms: u64) {
+        self.clock.advance_ms(ms);
+        self.event_loop.run_pending_tasks();
+        self.event_loop.run_due_timers();
+        let idle = {
+            let channel = self.channel.lock().unwrap();
+            !channel.is_closed() && channel.is_read_idle()
+        };
+        if idle {
+            let read_timeout_err = RettyErrorKind::new(ErrorKind::TimedOut, "ReadIdleTimeout".to_string());
+            self.inbound_pipe.head_channel_exception(read_timeout_err);
+        }
+    }
+
+    pub fn set_read_idle_timeout_ms(&mut self, ms: u64) {
+        self.channel.lock().unwrap().set_read_idle_timeout_ms(ms);
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        self.event_loop.now_ms()
+    }
+
+    pub fn event_loop(&self) -> Arc<EventLoop> {
+        self.event_loop.clone()
+    }
+
+    pub fn channel(&self) -> InboundChannelCtx {
+        InboundChannelCtx::new(self.channel.clone())
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.channel.lock().unwrap().is_closed()
+    }
+
+    ///
+    /// 关闭channel 并触发 channel_inactive
+    ///
+    pub fn close(&mut self) {
+        self.channel.lock().unwrap().close();
+        self.inbound_pipe.head_channel_inactive();
+    }
+
+    fn pop_message<T: Any>(queue: &MessageQueue) -> Option<T> {
+        let mut queue = queue.lock().unwrap();
+        let message = queue.pop_front()?;
+        match message.downcast::<T>() {
+            Ok(m) => Some(*m),
+            Err(m) => {
+                queue.push_front(m);
+                None
+            }
+        }
+    }
+
+    fn take_message(takers: &Mutex<Vec<MessageTaker>>, message: &mut dyn Any) -> Option<Box<dyn Any + Send>> {
+        let takers = takers.lock().unwrap();
+        let taken = message::take_message(&takers, message);
+        if taken.is_none() {
+            log::warn!(target: trace::TARGET_EMBEDDED, "message type is not registered, dropped");
+        }
+        taken
+    }
+}
+
+
+///
+/// 入站pipeline 的最后一个handler, 收集流出的消息和异常
+///
+struct EmbeddedInboundCollector {
+    takers: Arc<Mutex<Vec<MessageTaker>>>,
+    messages: MessageQueue,
+    exceptions: Arc<Mutex<VecDeque<RettyErrorKind>>>,
+}
+
+impl ChannelInboundHandler for EmbeddedInboundCollector {
+    fn id(&self) -> String {
+        return "EMBEDDED_INBOUND_TAIL".to_string();
+    }
+
+    fn channel_active(&mut self, _channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_inactive(&mut self, _channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_read(&mut self, _channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(m) = EmbeddedChannel::take_message(&self.takers, message) {
+            self.messages.lock().unwrap().push_back(m);
+        }
+    }
+
+    fn channel_exception(&mut self, _channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        self.exceptions.lock().unwrap().push_back(error);
+    }
+}
+
+
+///
+/// 出站pipeline 的最后一个handler, 代替 TailHandler 收集流出的消息
+///
+struct EmbeddedOutboundCollector {
+    takers: Arc<Mutex<Vec<MessageTaker>>>,
+    messages: MessageQueue,
+}
+
+impl ChannelOutboundHandler for EmbeddedOutboundCollector {
+    fn id(&self) -> String {
+        return "EMBEDDED_OUTBOUND_TAIL".to_string();
+    }
+
+    fn channel_write(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut dyn Any) {
+        if let Some(region) = message.downcast_ref::<FileRegion>() {
+            // 与socket 上收到的字节一致
+            match region.read_remaining() {
+                Ok(bytes) => self.channel_write(channel_handler_ctx, &mut ByteBuf::new_from(&bytes)),
+                Err(e) => log::warn!(target: trace::TARGET_EMBEDDED, "read file region failed, error:{}", e),
+            }
+            return;
+        }
+        if let Some(bytes) = message::as_bytes(message) {
+            channel_handler_ctx.channel().write_bytes(bytes);
+        }
+        if let Some(m) = EmbeddedChannel::take_message(&self.takers, message) {
+            self.messages.lock().unwrap().push_back(m);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+use mio::net::TcpStream;
+
+///
+/// 每次 sendfile 最多发送的字节数
+///
+#[cfg(target_os = "linux")]
+const MAX_SENDFILE_CHUNK: u64 = 1 << 30;
+
+///
+/// 没有 sendfile 时每次读取的字节数
+///
+#[cfg(not(target_os = "linux"))]
+const COPY_CHUNK_SIZE: usize = 65536;
+
+///
+/// 文件的一段区域, 写到出站pipeline 末端后由 TailHandler 直接从文件发到socket,
+/// Linux 上使用 sendfile 零拷贝, 其它平台读出来再写
+///
+/// ```ignore
+/// let region = FileRegion::open("firmware.bin")?;
+/// channel_handler_ctx.write_and_flush(&mut region.clone());
+/// ```
+///
+/// 文件句柄共享, clone 出来的region 互不影响发送进度
+///
+#[derive(Clone, Debug)]
+pub struct FileRegion {
+    file: Arc<File>,
+    position: u64,
+    count: u64,
+    transferred: u64,
+}
+
+impl FileRegion {
+    pub fn new(file: File, position: u64, count: u64) -> FileRegion {
+        FileRegion::from_shared(Arc::new(file), position, count)
+    }
+
+    ///
+    /// 同一个文件的多个区域共享文件句柄
+    ///
+    pub fn from_shared(file: Arc<File>, position: u64, count: u64) -> FileRegion {
+        FileRegion {
+            file,
+            position,
+            count,
+            transferred: 0,
+        }
+    }
+
+    ///
+    /// 整个文件
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FileRegion> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(FileRegion::new(file, 0, len))
+    }
+
+    ///
+    /// 区域在文件中的起始位置
+    ///
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    ///
+    /// 已经写进socket 的字节数
+    ///
+    pub fn transferred(&self) -> u64 {
+        self.transferred
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.count - self.transferred
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.transferred >= self.count
+    }
+
+    ///
+    /// 读出还没发送的内容, 没有socket 的channel 使用
+    ///
+    pub(crate) fn read_remaining(&self) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; self.remaining() as usize];
+        let mut filled = 0;
+        while filled < buf.len() {
+            let n = read_at(&self.file, &mut buf[filled..], self.position + self.transferred + filled as u64)?;
+            if n == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "file is shorter than the region"));
+            }
+            filled += n;
+        }
+        Ok(buf)
+    }
+
+    ///
+    /// 按 chunk_size 分段读出还没发送的内容, 每次只占用一个分段大小的内存
+    ///
+    pub(crate) fn read_remaining_chunks<F: FnMut(&[u8])>(&self, chunk_size: usize, mut f: F) -> io::Result<()> {
+        let mut buf = vec![0u8; (self.remaining() as usize).min(chunk_size)];
+        let mut offset = self.position + self.transferred;
+        let end = self.position + self.count;
+        while offset < end {
+            let len = ((end - offset) as usize).min(buf.len());
+            let n = read_at(&self.file, &mut buf[..len], offset)?;
+            if n == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "file is shorter than the region"));
+            }
+            f(&buf[..n]);
+            offset += n as u64;
+        }
+        Ok(())
+    }
+
+    ///
+    /// 没有socket 时直接标记发送完成
+    ///
+    pub(crate) fn skip_remaining(&mut self) -> u64 {
+        let remaining = self.remaining();
+        self.transferred = self.count;
+        remaining
+    }
+
+    ///
+    /// 写到socket 写不下为止, 返回这次写出的字节数, 文件比区域短时返回 UnexpectedEof
+    ///
+    #[cfg(target_os = "linux")]
+    pub(crate) fn transfer_to(&mut self, stream: &TcpStream) -> io::Result<usize> {
+        use std::os::unix::io::AsRawFd;
+
+        let mut written = 0;
+        while !self.is_complete() {
+            let mut offset = (self.position + self.transferred) as libc::off_t;
+            let len = self.remaining().min(MAX_SENDFILE_CHUNK) as usize;
+            let n = unsafe { libc::sendfile(stream.as_raw_fd(), self.file.as_raw_fd(), &mut offset, len) };
+            if n < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    ErrorKind::WouldBlock => break,
+                    ErrorKind::Interrupted => continue,
+                    _ => return Err(err),
+                }
+            }
+            if n == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "file is shorter than the region"));
+            }
+            self.transferred += n as u64;
+            written += n as usize;
+        }
+        Ok(written)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn transfer_to(&mut self, stream: &TcpStream) -> io::Result<usize> {
+        use std::io::Write;
+
+        let mut stream = stream;
+        let mut buf = vec![0u8; COPY_CHUNK_SIZE];
+        let mut written = 0;
+        while !self.is_complete() {
+            let len = self.remaining().min(COPY_CHUNK_SIZE as u64) as usize;
+            let n = read_at(&self.file, &mut buf[..len], self.position + self.transferred)?;
+            if n == 0 {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "file is shorter than the region"));
+            }
+            // 写不完的部分下次重新读
+            match stream.write(&buf[..n]) {
+                Ok(w) => {
+                    self.transferred += w as u64;
+                    written += w;
+                    if w < n {
+                        break;
+                    }
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
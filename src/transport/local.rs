@@ -0,0 +1,310 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::ErrorKind;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use mio::Token;
+
+use crate::core::bootstrap::{Bootstrap, PipelineFn};
+use crate::core::eventloop::{EventLoop, EventLoopGroup};
+use crate::core::metrics::MetricsRegistry;
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx_pipe::{ChannelInboundHandlerCtxPipe, ChannelOutboundHandlerCtxPipe};
+use crate::handler::handler::TailHandler;
+use crate::transport::channel::{Channel, InboundChannelCtx};
+use crate::transport::channel_id::ChannelId;
+use crate::transport::message::{self, MessageTaker};
+
+///
+/// local channel 不注册到selector, 统一使用这个Token
+///
+pub(crate) const LOCAL_TOKEN: Token = Token(usize::MAX - 1);
+
+///
+/// 进程内按名字绑定的server
+///
+static LOCAL_SERVERS: Mutex<Option<HashMap<String, Arc<LocalServer>>>> = Mutex::new(None);
+
+static EPHEMERAL_ID: AtomicU64 = AtomicU64::new(1);
+
+
+///
+/// local channel 的地址, server 使用绑定的名字, client 使用自动分配的 `E` 开头的名字
+///
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct LocalAddress {
+    name: String,
+}
+
+impl LocalAddress {
+    pub fn new(name: &str) -> LocalAddress {
+        LocalAddress {
+            name: name.to_owned(),
+        }
+    }
+
+    fn ephemeral() -> LocalAddress {
+        LocalAddress {
+            name: format!("E{:06x}", EPHEMERAL_ID.fetch_add(1, Ordering::Relaxed)),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Display for LocalAddress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "local:{}", self.name)
+    }
+}
+
+
+///
+/// 用 bind_local 启动的server
+///
+pub(crate) struct LocalServer {
+    worker_group: Arc<EventLoopGroup>,
+    pipeline_fn: PipelineFn,
+    metrics: Arc<MetricsRegistry>,
+    next_loop: AtomicUsize,
+}
+
+impl LocalServer {
+    pub(crate) fn new(worker_group: Arc<EventLoopGroup>, pipeline_fn: PipelineFn, metrics: Arc<MetricsRegistry>) -> LocalServer {
+        LocalServer {
+            worker_group,
+            pipeline_fn,
+            metrics,
+            next_loop: AtomicUsize::new(0),
+        }
+    }
+
+    fn next_event_loop(&self) -> Arc<EventLoop> {
+        let group = self.worker_group.event_loop_group();
+        let i = self.next_loop.fetch_add(1, Ordering::Relaxed);
+        group[i % group.len()].clone()
+    }
+}
+
+
+pub(crate) fn bind(name: &str, server: LocalServer) -> Result<(), RettyErrorKind> {
+    let mut servers = LOCAL_SERVERS.lock().unwrap();
+    let servers = servers.get_or_insert_with(HashMap::new);
+    if servers.contains_key(name) {
+        return Err(RettyErrorKind::new(ErrorKind::AddrInUse, format!("local address {} is already bound", name)));
+    }
+    servers.insert(name.to_owned(), Arc::new(server));
+    Ok(())
+}
+
+pub(crate) fn unbind(name: &str) {
+    if let Some(servers) = LOCAL_SERVERS.lock().unwrap().as_mut() {
+        servers.remove(name);
+    }
+}
+
+fn lookup(name: &str) -> Option<Arc<LocalServer>> {
+    let servers = LOCAL_SERVERS.lock().unwrap();
+    servers.as_ref().and_then(|s| s.get(name).cloned())
+}
+
+
+///
+/// 连接的一端
+///
+#[derive(Clone)]
+struct LocalEndpoint {
+    event_loop: Arc<EventLoop>,
+    channel: Arc<Mutex<Channel>>,
+    inbound_pipe: ChannelInboundHandlerCtxPipe,
+}
+
+///
+/// 一对 local channel 共享的连接, 出站末端的消息通过对端的EventLoop 直接送进对端的入站pipeline
+///
+pub(crate) struct LocalLink {
+    takers: Vec<MessageTaker>,
+    client: Mutex<Option<LocalEndpoint>>,
+    server: Mutex<Option<LocalEndpoint>>,
+}
+
+impl LocalLink {
+    fn new() -> LocalLink {
+        LocalLink {
+            takers: message::default_takers(),
+            client: Mutex::new(None),
+            server: Mutex::new(None),
+        }
+    }
+
+    fn endpoint(&self, client: bool) -> &Mutex<Option<LocalEndpoint>> {
+        if client { &self.client } else { &self.server }
+    }
+
+    ///
+    /// 取得消息的所有权, 不支持的类型返回None
+    ///
+    pub(crate) fn take_message(&self, message: &mut dyn Any) -> Option<Box<dyn Any + Send>> {
+        message::take_message(&self.takers, message)
+    }
+
+    ///
+    /// 在对端的EventLoop 上触发 channel_read, 对端已经关闭时丢弃
+    ///
+    pub(crate) fn deliver(&self, from_client: bool, message: Box<dyn Any + Send>) {
+        let peer = match self.endpoint(!from_client).lock().unwrap().clone() {
+            Some(peer) => peer,
+            None => return,
+        };
+        let event_loop = peer.event_loop.clone();
+        event_loop.execute(move || {
+            let mut message = message;
+            if peer.channel.lock().unwrap().is_closed() {
+                return;
+            }
+            peer.inbound_pipe.head_channel_read(&mut *message);
+        });
+    }
+
+    ///
+    /// 任意一端关闭时两端都关闭, 各自在自己的EventLoop 上触发 channel_inactive
+    ///
+    pub(crate) fn close(&self) {
+        let endpoints = vec![self.client.lock().unwrap().take(), self.server.lock().unwrap().take()];
+        for endpoint in endpoints.into_iter().flatten() {
+            let event_loop = endpoint.event_loop.clone();
+            event_loop.execute(move || {
+                {
+                    let mut channel = endpoint.channel.lock().unwrap();
+                    channel.mark_closed();
+                    endpoint.event_loop.metrics.record_closed();
+                    log::debug!(target: trace::TARGET_CHANNEL, "local channel closed, channel_id:{}", channel.id());
+                }
+                endpoint.inbound_pipe.head_channel_inactive();
+            });
+        }
+    }
+}
+
+
+///
+/// 连接到进程内server 的client channel
+///
+/// ```ignore
+/// let mut server = Bootstrap::new_server_bootstrap();
+/// server.worker_group(1).bind_local("echo")...start();
+///
+/// let mut client = Bootstrap::new_client_bootstrap();
+/// let channel = client.worker_group(1)...connect_local("echo").unwrap();
+/// channel.write_and_flush("ping".to_string());
+/// ```
+///
+/// 消息不经过编解码直接交给对端, 支持 `ByteBuf`、`String`、`Vec<u8>`,
+/// 其它类型包装成 `Box<dyn Any + Send>` 写出, 对端收到的是拆箱后的类型
+///
+#[derive(Clone)]
+pub struct LocalChannel {
+    channel: Arc<Mutex<Channel>>,
+    event_loop: Arc<EventLoop>,
+    outbound_pipe: Arc<Mutex<ChannelOutboundHandlerCtxPipe>>,
+    local_addr: LocalAddress,
+    remote_addr: LocalAddress,
+}
+
+impl LocalChannel {
+    pub fn id(&self) -> ChannelId {
+        self.channel.lock().unwrap().id()
+    }
+
+    pub fn local_addr(&self) -> LocalAddress {
+        self.local_addr.clone()
+    }
+
+    pub fn remote_addr(&self) -> LocalAddress {
+        self.remote_addr.clone()
+    }
+
+    pub fn channel(&self) -> InboundChannelCtx {
+        InboundChannelCtx::new(self.channel.clone())
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.channel.lock().unwrap().is_closed()
+    }
+
+    ///
+    /// 在EventLoop 线程上从出站pipeline 头部写出消息
+    ///
+    pub fn write_and_flush<T: Any + Send>(&self, message: T) {
+        let outbound_pipe = self.outbound_pipe.clone();
+        self.event_loop.execute(move || {
+            let mut message = message;
+            let pipe = outbound_pipe.lock().unwrap();
+            pipe.head_channel_write(&mut message);
+        });
+    }
+
+    pub fn close(&self) {
+        self.channel.lock().unwrap().close();
+    }
+}
+
+
+///
+/// 连接到按名字绑定的server, 两端的pipeline 建好后各自在自己的EventLoop 上触发 channel_active
+///
+pub(crate) fn connect(name: &str, event_loop: Arc<EventLoop>, pipeline_fn: PipelineFn) -> Result<LocalChannel, RettyErrorKind> {
+    let server = match lookup(name) {
+        Some(server) => server,
+        None => return Err(RettyErrorKind::new(ErrorKind::ConnectionRefused, format!("local address {} is not bound", name))),
+    };
+    let server_loop = server.next_event_loop();
+    let link = Arc::new(LocalLink::new());
+    let server_addr = LocalAddress::new(name);
+    let client_addr = LocalAddress::ephemeral();
+
+    let client_channel = Channel::create_local(ChannelId::new_instance(), event_loop.clone(), client_addr.clone(), server_addr.clone(), link.clone(), true);
+    let client_channel = Arc::new(Mutex::new(client_channel));
+    let server_channel = Channel::create_local(ChannelId::new_instance(), server_loop.clone(), server_addr.clone(), client_addr.clone(), link.clone(), false);
+    let server_channel = Arc::new(Mutex::new(server_channel));
+
+    let (client_inbound_pipe, client_outbound_pipe) = (pipeline_fn)();
+    let client_outbound = Bootstrap::create_channel_outbound_ctx_pipe(client_outbound_pipe, event_loop.clone(), client_channel.clone(), Box::new(TailHandler::new()));
+    let client_outbound = Arc::new(Mutex::new(client_outbound));
+    let client_inbound = Bootstrap::create_channel_inbound_ctx_pipe(client_inbound_pipe, event_loop.clone(), client_channel.clone(), client_outbound.clone());
+
+    let (server_inbound_pipe, server_outbound_pipe) = (server.pipeline_fn)();
+    let server_outbound = Bootstrap::create_channel_outbound_ctx_pipe(server_outbound_pipe, server_loop.clone(), server_channel.clone(), Box::new(TailHandler::new()));
+    let server_outbound = Arc::new(Mutex::new(server_outbound));
+    let server_inbound = Bootstrap::create_channel_inbound_ctx_pipe(server_inbound_pipe, server_loop.clone(), server_channel.clone(), server_outbound);
+
+    *link.client.lock().unwrap() = Some(LocalEndpoint {
+        event_loop: event_loop.clone(),
+        channel: client_channel.clone(),
+        inbound_pipe: client_inbound.clone(),
+    });
+    *link.server.lock().unwrap() = Some(LocalEndpoint {
+        event_loop: server_loop.clone(),
+        channel: server_channel,
+        inbound_pipe: server_inbound.clone(),
+    });
+    server.metrics.record_accepted();
+    log::debug!(target: trace::TARGET_BOOTSTRAP, "local connection {} -> {}", client_addr, server_addr);
+
+    // server 端先排队触发 active, client 在 channel_active 里写出的消息会排在它后面
+    server_loop.execute(move || server_inbound.head_channel_active());
+    event_loop.execute(move || client_inbound.head_channel_active());
+
+    Ok(LocalChannel {
+        channel: client_channel,
+        event_loop,
+        outbound_pipe: client_outbound,
+        local_addr: client_addr,
+        remote_addr: server_addr,
+    })
+}
@@ -0,0 +1,69 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use bytebuf_rs::bytebuf::ByteBuf;
+
+use crate::core::allocator::PooledBuffer;
+
+///
+/// 把pipeline 里的 `&mut dyn Any` 消息取出来, 得到可以跨线程传递的所有权
+///
+pub(crate) type MessageTaker = Arc<dyn Fn(&mut dyn Any) -> Option<Box<dyn Any + Send>> + Send + Sync>;
+
+
+///
+/// 字节消息的内容: `ByteBuf` 的可读部分, 或者从内存池借出的 `PooledBuffer`, 其它类型返回None
+///
+/// EventLoop 读到的数据和内置编码器的输出都是 `PooledBuffer`, 直接处理字节的handler 用它兼容两种消息
+///
+pub fn as_bytes(message: &dyn Any) -> Option<&[u8]> {
+    if let Some(buf) = message.downcast_ref::<ByteBuf>() {
+        return Some(buf.available_bytes());
+    }
+    message.downcast_ref::<PooledBuffer>().map(|buf| &buf[..])
+}
+
+
+///
+/// 默认可以取出 `ByteBuf`、`PooledBuffer`(拷贝成ByteBuf)、`String`、`Vec<u8>` 和 `Box<dyn Any + Send>`(取走) 类型的消息
+///
+pub(crate) fn default_takers() -> Vec<MessageTaker> {
+    let bytebuf_taker: MessageTaker = Arc::new(|message: &mut dyn Any| {
+        as_bytes(message).map(|bytes| {
+            let boxed: Box<dyn Any + Send> = Box::new(ByteBuf::new_from(bytes));
+            boxed
+        })
+    });
+    let string_taker: MessageTaker = Arc::new(|message: &mut dyn Any| {
+        message.downcast_mut::<String>().map(|s| {
+            let boxed: Box<dyn Any + Send> = Box::new(std::mem::take(s));
+            boxed
+        })
+    });
+    let bytes_taker: MessageTaker = Arc::new(|message: &mut dyn Any| {
+        message.downcast_mut::<Vec<u8>>().map(|v| {
+            let boxed: Box<dyn Any + Send> = Box::new(std::mem::take(v));
+            boxed
+        })
+    });
+    let boxed_taker: MessageTaker = Arc::new(|message: &mut dyn Any| {
+        message.downcast_mut::<Box<dyn Any + Send>>().map(|b| {
+            let empty: Box<dyn Any + Send> = Box::new(());
+            std::mem::replace(b, empty)
+        })
+    });
+    vec![bytebuf_taker, string_taker, bytes_taker, boxed_taker]
+}
+
+
+///
+/// 后注册的taker 优先, 都不匹配时返回None
+///
+pub(crate) fn take_message(takers: &[MessageTaker], message: &mut dyn Any) -> Option<Box<dyn Any + Send>> {
+    for taker in takers.iter().rev() {
+        if let Some(m) = taker(&mut *message) {
+            return Some(m);
+        }
+    }
+    None
+}
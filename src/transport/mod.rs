@@ -1 +1,10 @@
 pub mod channel;
+pub mod attribute;
+pub mod channel_id;
+pub mod channel_stats;
+pub mod embedded;
+pub mod file_region;
+pub mod message;
+pub mod outbound_buffer;
+pub mod local;
+pub mod socket;
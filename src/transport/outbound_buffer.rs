@@ -0,0 +1,121 @@
+use std::collections::VecDeque;
+
+use crate::transport::file_region::FileRegion;
+
+///
+/// 出站缓冲区默认高水位
+///
+pub(crate) const DEFAULT_WRITE_BUFFER_HIGH_WATER_MARK: usize = 1024 * 1024;
+
+///
+/// 还没写进socket 的出站数据, 按写入顺序排队
+///
+pub(crate) enum PendingWrite {
+    Bytes(Vec<u8>),
+    File(FileRegion),
+}
+
+impl PendingWrite {
+    fn len(&self) -> usize {
+        match self {
+            PendingWrite::Bytes(bytes) => bytes.len(),
+            PendingWrite::File(region) => region.remaining() as usize,
+        }
+    }
+}
+
+
+///
+/// channel 的出站缓冲区, 包含三部分行为:
+///
+/// - socket 写不下的数据在这里排队, 注册写事件后由EventLoop 继续写
+/// - 排队的字节数超过高水位时暂停读, 降到高水位的一半以下再恢复
+/// - close_after_flush 之后等排队的数据写完再关闭
+///
+/// 只管理状态, 写socket 和注册事件由 Channel 负责
+///
+pub(crate) struct ChannelOutboundBuffer {
+    pending: VecDeque<PendingWrite>,
+    high_water_mark: usize,
+    suspended: bool,
+    close_pending: bool,
+}
+
+impl ChannelOutboundBuffer {
+    pub(crate) fn new(high_water_mark: usize) -> ChannelOutboundBuffer {
+        ChannelOutboundBuffer {
+            pending: VecDeque::new(),
+            high_water_mark,
+            suspended: false,
+            close_pending: false,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    ///
+    /// 排队中的字节数
+    ///
+    pub(crate) fn pending_bytes(&self) -> usize {
+        self.pending.iter().map(PendingWrite::len).sum()
+    }
+
+    ///
+    /// 追加到队尾, 与前一段字节合并
+    ///
+    pub(crate) fn push_bytes(&mut self, bytes: &[u8]) {
+        match self.pending.back_mut() {
+            Some(PendingWrite::Bytes(pending)) => pending.extend_from_slice(bytes),
+            _ => self.pending.push_back(PendingWrite::Bytes(bytes.to_vec())),
+        }
+    }
+
+    pub(crate) fn push_file(&mut self, region: FileRegion) {
+        self.pending.push_back(PendingWrite::File(region));
+    }
+
+    pub(crate) fn pop_front(&mut self) -> Option<PendingWrite> {
+        self.pending.pop_front()
+    }
+
+    ///
+    /// 没写完的部分放回队首
+    ///
+    pub(crate) fn push_front(&mut self, pending: PendingWrite) {
+        self.pending.push_front(pending);
+    }
+
+    ///
+    /// 超过高水位, 暂停读
+    ///
+    pub(crate) fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    ///
+    /// 按排队的字节数更新暂停状态, 返回 (之前是否暂停, 现在是否暂停)
+    ///
+    pub(crate) fn update_suspended(&mut self) -> (bool, bool) {
+        let before = self.suspended;
+        let pending = self.pending_bytes();
+        if !self.suspended && pending > self.high_water_mark {
+            self.suspended = true;
+        } else if self.suspended && pending <= self.high_water_mark / 2 {
+            self.suspended = false;
+        }
+        (before, self.suspended)
+    }
+
+    pub(crate) fn set_close_pending(&mut self) {
+        self.close_pending = true;
+    }
+
+    ///
+    /// 调用过 close_after_flush, 正在等排队的数据写完
+    ///
+    pub(crate) fn is_close_pending(&self) -> bool {
+        self.close_pending
+    }
+}
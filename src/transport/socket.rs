@@ -0,0 +1,139 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mio::net::TcpStream;
+
+use crate::core::bootstrap::{Bootstrap, PipelineFn};
+use crate::core::eventloop::EventLoop;
+use crate::core::trace;
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx_pipe::ChannelOutboundHandlerCtxPipe;
+use crate::handler::handler::TailHandler;
+use crate::transport::channel::{Channel, ChannelOptions, InboundChannelCtx};
+use crate::transport::channel_id::ChannelId;
+
+///
+/// 默认连接超时 30 秒
+///
+const DEFAULT_CONNECT_TIMEOUT_MS: usize = 30000;
+
+
+///
+/// 通过 Bootstrap::connect 建立的TCP client channel
+///
+/// ```ignore
+/// let mut client = Bootstrap::new_client_bootstrap();
+/// client.worker_group(1).initialize_pipeline(|inbound, outbound| {
+///     HttpClientCodec::new().add_last(inbound, outbound);
+///     inbound.add_last(Box::new(ResponseHandler::new()));
+/// });
+/// let channel = client.connect("127.0.0.1", 8080).unwrap();
+/// channel.write_and_flush(FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/", Vec::new()));
+/// ```
+///
+#[derive(Clone)]
+pub struct SocketChannel {
+    channel: Arc<Mutex<Channel>>,
+    event_loop: Arc<EventLoop>,
+    outbound_pipe: Arc<Mutex<ChannelOutboundHandlerCtxPipe>>,
+    local_addr: SocketAddr,
+    remote_addr: SocketAddr,
+}
+
+impl SocketChannel {
+    pub fn id(&self) -> ChannelId {
+        self.channel.lock().unwrap().id()
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    pub fn channel(&self) -> InboundChannelCtx {
+        InboundChannelCtx::new(self.channel.clone())
+    }
+
+    pub fn is_active(&self) -> bool {
+        !self.channel.lock().unwrap().is_closed()
+    }
+
+    ///
+    /// 在EventLoop 线程上从出站pipeline 头部写出消息
+    ///
+    pub fn write_and_flush<T: Any + Send>(&self, message: T) {
+        let outbound_pipe = self.outbound_pipe.clone();
+        self.event_loop.execute(move || {
+            let mut message = message;
+            let pipe = outbound_pipe.lock().unwrap();
+            pipe.head_channel_write(&mut message);
+        });
+    }
+
+    ///
+    /// 排在之前提交的写后面, 数据发完后关闭
+    ///
+    pub fn close(&self) {
+        let channel = self.channel.clone();
+        self.event_loop.execute(move || channel.lock().unwrap().close_after_flush());
+    }
+}
+
+
+///
+/// 依次尝试解析出的地址, 连接成功后在EventLoop 线程上注册并触发 channel_active,
+/// 之后 write_and_flush 提交的任务排在它后面
+///
+/// 用 std 的 connect_timeout 在调用线程上阻塞连接, 连上之后才交给EventLoop, 所以不能在EventLoop 线程上调用
+///
+pub(crate) fn connect(host: &str, port: u16, opts: HashMap<String, ChannelOptions>, event_loop: Arc<EventLoop>, pipeline_fn: PipelineFn) -> Result<SocketChannel, RettyErrorKind> {
+    let timeout_ms = match opts.get("connect_timeout_ms") {
+        Some(ChannelOptions::NUMBER(ms)) => *ms,
+        _ => DEFAULT_CONNECT_TIMEOUT_MS,
+    };
+    let addrs = (host, port).to_socket_addrs()
+        .map_err(|e| RettyErrorKind::new(e.kind(), format!("resolve {}:{} error: {}", host, port, e)))?;
+    let mut last_error = RettyErrorKind::new(ErrorKind::NotFound, format!("no address resolved for {}:{}", host, port));
+    let mut connected = None;
+    for addr in addrs {
+        match std::net::TcpStream::connect_timeout(&addr, Duration::from_millis(timeout_ms as u64)) {
+            Ok(stream) => {
+                connected = Some(stream);
+                break;
+            }
+            Err(e) => last_error = RettyErrorKind::new(e.kind(), format!("connect {} error: {}", addr, e)),
+        }
+    }
+    let stream = connected.ok_or(last_error)?;
+    let local_addr = stream.local_addr()?;
+    let remote_addr = stream.peer_addr()?;
+    let stream = TcpStream::from_stream(stream)?;
+
+    let token = event_loop.allocate_token();
+    let channel = Channel::create(ChannelId::new_instance(), token, opts, event_loop.clone(), stream);
+    let channel = Arc::new(Mutex::new(channel));
+    let (inbound_pipe, outbound_pipe) = (pipeline_fn)();
+    let outbound = Bootstrap::create_channel_outbound_ctx_pipe(outbound_pipe, event_loop.clone(), channel.clone(), Box::new(TailHandler::new()));
+    let outbound = Arc::new(Mutex::new(outbound));
+    let inbound = Bootstrap::create_channel_inbound_ctx_pipe(inbound_pipe, event_loop.clone(), channel.clone(), outbound.clone());
+    log::debug!(target: trace::TARGET_BOOTSTRAP, "connected {} -> {}", local_addr, remote_addr);
+
+    let attach_loop = event_loop.clone();
+    let attach_channel = channel.clone();
+    event_loop.execute(move || attach_loop.attach(token, attach_channel, inbound));
+
+    Ok(SocketChannel {
+        channel,
+        event_loop,
+        outbound_pipe: outbound,
+        local_addr,
+        remote_addr,
+    })
+}
@@ -1,4 +1,5 @@
 use std::any::Any;
+use std::io::ErrorKind;
 use std::sync::{Arc, Mutex};
 
 use bytebuf_rs::bytebuf::ByteBuf;
@@ -6,10 +7,71 @@ use crossbeam::sync::WaitGroup;
 use rayon_core::ThreadPool;
 use uuid::Uuid;
 
+use crate::core::allocator::PooledByteBufAllocator;
 use crate::core::bootstrap::Bootstrap;
+use crate::core::eventloop::{EventLoop, TokenSlab};
+use crate::errors::RettyErrorKind;
+use crate::handler::channel_handler_ctx::{ChannelInboundHandlerCtx, ChannelOutboundHandlerCtx};
+use crate::handler::codec::byte_to_message_decoder::{ByteToMessageDecoder, ByteToMessageDecoderHandler};
+use crate::handler::codec::charset::{Charset, CodingErrorAction};
+use crate::handler::codec::delimiter_based_frame_decoder::DelimiterBasedFrameDecoder;
+use crate::handler::codec::fixed_length_frame_decoder::FixedLengthFrameDecoder;
+use crate::handler::codec::http::http_client_codec::HttpClientCodec;
+use crate::handler::codec::http::http_connection_pool::HttpConnectionPool;
+use crate::handler::codec::http::http_content_compressor::HttpContentCompressor;
+use crate::handler::codec::http::http_content_decompressor::HttpContentDecompressor;
+use crate::handler::codec::http::http_message::{FullHttpRequest, FullHttpResponse, HttpContent, HttpMessage, HttpMethod, HttpRequest, HttpResponse, HttpResponseStatus, HttpVersion, LastHttpContent};
+use crate::handler::codec::http::http_object_aggregator::HttpObjectAggregator;
+use crate::handler::codec::http::http_router::HttpRouter;
+use crate::handler::codec::http::http_server_codec::HttpServerCodec;
+use crate::handler::codec::http::http_server_expect_continue_handler::HttpServerExpectContinueHandler;
+use crate::handler::codec::http::http_static_file_handler::HttpStaticFileHandler;
+use crate::handler::codec::http::query_string_decoder::QueryStringDecoder;
+use crate::handler::codec::http::websocket::websocket_client_protocol_handler::WebSocketClientProtocolHandler;
+use crate::handler::codec::http::websocket::websocket_frame::{encode_frame, WebSocketFrame};
+use crate::handler::codec::http::websocket::websocket_permessage_deflate::PerMessageDeflate;
+use crate::handler::codec::http::websocket::websocket_protocol::WebSocketHandshakeComplete;
+use crate::handler::codec::http::websocket::websocket_server_handshaker::WebSocketServerHandshaker;
+use crate::handler::codec::http::websocket::websocket_server_protocol_handler::WebSocketServerProtocolHandler;
+use crate::handler::codec::length_field_based_frame_decoder::{ByteOrder, LengthFieldBasedFrameDecoder};
+use crate::handler::codec::length_field_prepender::LengthFieldPrepender;
+use crate::handler::codec::line_based_frame_decoder::LineBasedFrameDecoder;
+use crate::handler::codec::message_to_message_decoder::{MessageToMessageDecoder, MessageToMessageDecoderHandler};
+use crate::handler::codec::message_to_message_encoder::{MessageToMessageEncoder, MessageToMessageEncoderHandler};
+use crate::handler::codec::protobuf_varint32_frame_decoder::ProtobufVarint32FrameDecoder;
+use crate::handler::codec::protobuf_varint32_length_field_prepender::ProtobufVarint32LengthFieldPrepender;
+use crate::handler::codec::string_decoder::StringDecoder;
+use crate::handler::codec::string_encoder::StringEncoder;
+use crate::handler::combined_channel_duplex_handler::CombinedChannelDuplexHandler;
+use crate::handler::handler::{ChannelInboundHandler, ChannelOutboundHandler};
+use crate::handler::handler_pipe::{ChannelInboundHandlerPipe, ChannelOutboundHandlerPipe};
+use crate::transport::attribute::AttributeKey;
+use crate::transport::channel::{Channel, InboundChannelCtx};
+use crate::transport::channel_id::ChannelId;
+use crate::transport::embedded::EmbeddedChannel;
+use crate::transport::file_region::FileRegion;
+use crate::transport::message;
+use crate::transport::outbound_buffer::{ChannelOutboundBuffer, PendingWrite};
 
 #[test]
-pub fn test_create_server() {}
+pub fn test_create_server() {
+    // 与示例服务端相同的入站pipeline: 长度字段包含自身, 帧保留长度字段
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(8 * 1024 * 1024, 0, 4, -4, 0))));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    assert!(channel.is_active());
+
+    let mut frame = ByteBuf::new_with_capacity(16);
+    frame.write_u32_be(10);
+    frame.write_string_with_u8_be_len("hello".to_string());
+    channel.write_inbound(frame);
+    let mut frame = channel.read_inbound::<ByteBuf>().unwrap();
+    assert_eq!(frame.read_u32_be(), 10);
+    assert_eq!(frame.read_string_with_u8_be_len(), "hello");
+
+    channel.close();
+    assert!(!channel.is_active());
+}
 
 
 struct A {
@@ -70,3 +132,1743 @@ pub fn test_ref() {
     println!("ch2: {:?}", guard_ch.id);
 }
 
+
+#[test]
+pub fn test_pooled_allocator() {
+    let allocator = Arc::new(PooledByteBufAllocator::new());
+    {
+        let mut buf = allocator.buffer(100);
+        buf.extend_from_slice(&[1u8, 2, 3]);
+        assert!(buf.capacity() >= 256);
+        assert_eq!(allocator.stats().outstanding, 1);
+    }
+    let stats = allocator.stats();
+    assert_eq!(stats.outstanding, 0);
+    assert_eq!(stats.recycled, 1);
+    assert_eq!(stats.misses, 1);
+
+    let buf = allocator.buffer(200);
+    assert_eq!(buf.len(), 0);
+    assert_eq!(allocator.stats().hits, 1);
+    buf.release();
+
+    let mut buf = allocator.buffer_from(&[1u8, 2, 3, 4]);
+    buf.discard_read_bytes(3);
+    assert_eq!(&buf[..], &[4u8]);
+}
+
+
+#[test]
+pub fn test_hex_dump() {
+    let dump = crate::handler::logging_handler::hex_dump(b"retty\x00\x01", 4096);
+    assert!(dump.contains("|00000000| 72 65 74 74 79 00 01"));
+    assert!(dump.contains("|retty..         |"));
+
+    let dump = crate::handler::logging_handler::hex_dump(&[0u8; 40], 16);
+    assert!(dump.ends_with("... 24 more bytes"));
+}
+
+
+struct UpperCaseHandler {}
+
+impl ChannelInboundHandler for UpperCaseHandler {
+    fn id(&self) -> String {
+        "UpperCaseHandler".to_string()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_active();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.fire_channel_inactive();
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        let msg = message.downcast_ref::<String>().unwrap();
+        channel_handler_ctx.fire_channel_read(&mut msg.to_uppercase());
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
+
+#[test]
+pub fn test_embedded_channel() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(UpperCaseHandler {}));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    channel.fire_channel_active();
+
+    channel.write_inbound("retty".to_string());
+    assert_eq!(channel.read_inbound::<String>(), Some("RETTY".to_string()));
+    assert!(channel.read_inbound::<String>().is_none());
+
+    channel.write_outbound(ByteBuf::new_from(&[1u8, 2, 3]));
+    let out = channel.read_outbound::<ByteBuf>().unwrap();
+    assert_eq!(out.available_bytes(), &[1u8, 2, 3][..]);
+
+    channel.set_read_idle_timeout_ms(1000);
+    channel.advance_time_ms(500);
+    assert!(channel.read_exception().is_none());
+    channel.advance_time_ms(1000);
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::TimedOut);
+}
+
+
+#[test]
+pub fn test_channel_attribute() {
+    const USER: AttributeKey<String> = AttributeKey::new("User");
+    const COUNT: AttributeKey<u32> = AttributeKey::new("Count");
+    let channel = EmbeddedChannel::new(ChannelInboundHandlerPipe::new(), ChannelOutboundHandlerPipe::new());
+    let mut ch = channel.channel();
+
+    assert!(ch.get_attribute("User".to_string()).is_none());
+    assert!(ch.attr(&USER).get().is_none());
+
+    // 字符串key 写入, AttributeKey 读取
+    ch.set_attribute("User".to_string(), Box::new("lgphp".to_string()));
+    assert_eq!(ch.attr(&USER).get().as_deref(), Some(&"lgphp".to_string()));
+    // 类型不匹配
+    assert!(ch.attr(&AttributeKey::<u32>::new("User")).get().is_none());
+
+    // AttributeKey 写入, 字符串key 读到 Arc<T>
+    ch.attr(&COUNT).set(1);
+    let count = ch.get_attribute("Count".to_string()).unwrap();
+    assert_eq!(count.lock().unwrap().downcast_ref::<Arc<u32>>().map(|c| **c), Some(1));
+
+    assert_eq!(ch.attr(&COUNT).set_if_absent(2).as_deref(), Some(&1));
+    assert!(ch.attr(&COUNT).compare_and_set(&1, 3));
+    assert!(!ch.attr(&COUNT).compare_and_set(&1, 4));
+    assert_eq!(ch.attr(&COUNT).remove().as_deref(), Some(&3));
+    assert!(ch.attr(&COUNT).get().is_none());
+    assert!(ch.attr(&COUNT).set_if_absent(5).is_none());
+    assert_eq!(ch.attr(&COUNT).get().as_deref(), Some(&5));
+}
+
+
+#[test]
+pub fn test_token_slab() {
+    let mut slab = TokenSlab::new();
+    let (t1, t2, t3) = (slab.allocate(), slab.allocate(), slab.allocate());
+    assert_eq!((t1, t2, t3), (mio::Token(1), mio::Token(2), mio::Token(3)));
+    slab.release(t2);
+    slab.release(t1);
+    // 后回收的先复用
+    assert_eq!(slab.allocate(), t1);
+    assert_eq!(slab.allocate(), t2);
+    assert_eq!(slab.allocate(), mio::Token(4));
+}
+
+#[test]
+pub fn test_channel_id() {
+    let a = ChannelId::new_instance();
+    let b = ChannelId::new_instance();
+    assert_ne!(a, b);
+    assert!(b.sequence() > a.sequence());
+
+    let short = a.as_short_text();
+    assert_eq!(short.len(), 8);
+    assert!(short.chars().all(|c| c.is_ascii_hexdigit()));
+    assert_eq!(a.to_string(), short);
+
+    let long = a.as_long_text();
+    let parts: Vec<&str> = long.split('-').collect();
+    assert_eq!(parts.iter().map(|p| p.len()).collect::<Vec<_>>(), vec![8, 16, 16, 8]);
+    assert_eq!(u32::from_str_radix(parts[0], 16).unwrap(), std::process::id());
+    assert_eq!(u64::from_str_radix(parts[1], 16).unwrap(), a.sequence());
+    assert_eq!(u64::from_str_radix(parts[2], 16).unwrap(), a.timestamp_ms());
+    assert_eq!(parts[3], short);
+
+    let channel = EmbeddedChannel::new(ChannelInboundHandlerPipe::new(), ChannelOutboundHandlerPipe::new());
+    assert_eq!(channel.channel().id_string(), channel.channel().id().as_long_text());
+}
+
+
+struct EchoHandler {}
+
+impl ChannelInboundHandler for EchoHandler {
+    fn id(&self) -> String {
+        "EchoHandler".to_string()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        channel_handler_ctx.write_and_flush(message);
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {}
+}
+
+struct CollectHandler {
+    received: Arc<Mutex<Vec<String>>>,
+}
+
+impl ChannelInboundHandler for CollectHandler {
+    fn id(&self) -> String {
+        "CollectHandler".to_string()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        self.received.lock().unwrap().push("INACTIVE".to_string());
+    }
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        let msg = message.downcast_ref::<String>().unwrap();
+        self.received.lock().unwrap().push(msg.clone());
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {}
+}
+
+#[test]
+pub fn test_local_channel() {
+    let mut server = Bootstrap::new_server_bootstrap();
+    server.worker_group(1)
+        .bind_local("test_local_channel")
+        .initialize_inbound_handler_pipeline(|| {
+            let mut handler_pipe = ChannelInboundHandlerPipe::new();
+            handler_pipe.add_last(Box::new(UpperCaseHandler {}));
+            handler_pipe.add_last(Box::new(EchoHandler {}));
+            handler_pipe
+        })
+        .initialize_outbound_handler_pipeline(|| ChannelOutboundHandlerPipe::new())
+        .start();
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let collected = received.clone();
+    let mut client = Bootstrap::new_client_bootstrap();
+    let channel = client.worker_group(1)
+        .initialize_inbound_handler_pipeline(move || {
+            let mut handler_pipe = ChannelInboundHandlerPipe::new();
+            handler_pipe.add_last(Box::new(CollectHandler { received: collected.clone() }));
+            handler_pipe
+        })
+        .initialize_outbound_handler_pipeline(|| ChannelOutboundHandlerPipe::new())
+        .connect_local("test_local_channel")
+        .unwrap();
+    assert!(client.connect_local("not_bound").is_err());
+
+    channel.write_and_flush("ping".to_string());
+    wait_until(|| received.lock().unwrap().len() == 1);
+    assert_eq!(received.lock().unwrap()[0], "PING");
+
+    channel.close();
+    wait_until(|| received.lock().unwrap().len() == 2);
+    assert_eq!(received.lock().unwrap()[1], "INACTIVE");
+    assert!(!channel.is_active());
+
+    server.terminate();
+    client.terminate();
+}
+
+fn wait_until<F: Fn() -> bool>(f: F) {
+    for _ in 0..200 {
+        if f() {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("condition not reached");
+}
+
+
+///
+/// 连接建立后写出 size 字节然后关闭
+///
+struct WriteThenCloseHandler {
+    size: usize,
+}
+
+impl ChannelInboundHandler for WriteThenCloseHandler {
+    fn id(&self) -> String {
+        "WriteThenCloseHandler".to_string()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        let bytes: Vec<u8> = (0..self.size).map(|i| (i % 251) as u8).collect();
+        channel_handler_ctx.write_and_flush(&mut ByteBuf::new_from(&bytes));
+        channel_handler_ctx.close();
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {}
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {}
+}
+
+#[test]
+pub fn test_close_after_flush() {
+    use std::io::Read;
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let size = 8 * 1024 * 1024;
+    let mut server = Bootstrap::new_server_bootstrap();
+    server.worker_group(1)
+        .bind("127.0.0.1", port)
+        .initialize_pipeline(move |inbound, _outbound| {
+            inbound.add_last(Box::new(WriteThenCloseHandler { size }));
+        })
+        .start();
+    wait_until(|| std::net::TcpStream::connect(("127.0.0.1", port)).is_ok());
+    let metrics = server.metrics();
+    wait_until(|| metrics.closed_connections() == 1);
+
+    // 先不读, 让数据堆在出站缓冲区里
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    let mut received = Vec::new();
+    stream.read_to_end(&mut received).unwrap();
+    assert_eq!(received.len(), size);
+    assert!(received.iter().enumerate().all(|(i, b)| *b == (i % 251) as u8));
+    wait_until(|| metrics.closed_connections() == 2);
+    assert_eq!(metrics.active_connections(), 0);
+    server.terminate();
+}
+
+
+#[test]
+pub fn test_metrics_registry() {
+    use crate::core::metrics::MetricsRegistry;
+    let registry = MetricsRegistry::new();
+    let event_loop = Arc::new(EventLoop::new(0));
+    registry.register_event_loop("eventloop-0".to_string(), event_loop.clone());
+    for _ in 0..3 {
+        registry.record_accepted();
+    }
+    registry.record_accept_error();
+    event_loop.metrics().record_closed();
+    event_loop.metrics().record_exception("InvalidData");
+    event_loop.metrics().record_exception("InvalidData");
+    event_loop.metrics().record_exception("TimedOut");
+    event_loop.stats().record_read(10);
+    event_loop.stats().record_write(20);
+
+    assert_eq!((registry.accepted_connections(), registry.closed_connections(), registry.active_connections()), (3, 1, 2));
+    assert_eq!((registry.bytes_read(), registry.bytes_written()), (10, 20));
+    let text = registry.render_prometheus();
+    for line in [
+        "# TYPE retty_connections_accepted_total counter",
+        "retty_connections_accepted_total 3",
+        "retty_connections_closed_total 1",
+        "# TYPE retty_connections_active gauge",
+        "retty_connections_active 2",
+        "retty_accept_errors_total 1",
+        "retty_bytes_read_total 10",
+        "retty_bytes_written_total 20",
+        "retty_pipeline_exceptions_total{kind=\"InvalidData\"} 2",
+        "retty_pipeline_exceptions_total{kind=\"TimedOut\"} 1",
+        "retty_eventloop_poll_latency_seconds_count{eventloop=\"eventloop-0\"} 0",
+        "retty_eventloop_pending_tasks{eventloop=\"eventloop-0\"} 0",
+    ] {
+        assert!(text.lines().any(|l| l == line), "missing {} in\n{}", line, text);
+    }
+}
+
+
+///
+/// 收到 "close" 时关闭连接, 收到 "boom" 时触发异常
+///
+struct CommandHandler {}
+
+impl ChannelInboundHandler for CommandHandler {
+    fn id(&self) -> String {
+        "CommandHandler".to_string()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        match message::as_bytes(message).unwrap() {
+            b"close" => channel_handler_ctx.close(),
+            b"boom" => channel_handler_ctx.fire_channel_exception(RettyErrorKind::new(ErrorKind::InvalidData, "boom".to_string())),
+            _ => {}
+        }
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {
+        channel_handler_ctx.fire_channel_exception(error);
+    }
+}
+
+#[test]
+pub fn test_connection_and_exception_metrics() {
+    use std::io::{Read, Write};
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let metrics_port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut server = Bootstrap::new_server_bootstrap();
+    server.worker_group(1)
+        .bind("127.0.0.1", port)
+        .opt_metrics_listener("127.0.0.1", metrics_port)
+        .initialize_pipeline(|inbound, _outbound| {
+            inbound.add_last(Box::new(CommandHandler {}));
+            // 往后传的异常不重复计数
+            inbound.add_last(Box::new(UpperCaseHandler {}));
+        })
+        .start();
+    wait_until(|| std::net::TcpStream::connect(("127.0.0.1", port)).is_ok());
+    wait_until(|| std::net::TcpStream::connect(("127.0.0.1", metrics_port)).is_ok());
+    let metrics = server.metrics();
+    wait_until(|| metrics.closed_connections() == 1);
+
+    // handler 主动关闭
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(b"close").unwrap();
+    assert_eq!(stream.read(&mut [0u8; 16]).unwrap(), 0);
+    wait_until(|| metrics.closed_connections() == 2);
+
+    // handler 触发的异常, 然后对端 RST
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.write_all(b"boom").unwrap();
+    wait_until(|| metrics.exceptions().get("InvalidData") == Some(&1));
+    let stream = mio::net::TcpStream::from_stream(stream).unwrap();
+    stream.set_linger(Some(std::time::Duration::from_millis(0))).unwrap();
+    drop(stream);
+    wait_until(|| metrics.closed_connections() == 3);
+    assert_eq!((metrics.accepted_connections(), metrics.active_connections()), (3, 0));
+    assert_eq!(metrics.exceptions().get("InvalidData"), Some(&1));
+
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", metrics_port)).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let (head, body) = response.split_at(response.find("\r\n\r\n").unwrap() + 4);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains(&format!("Content-Length: {}\r\n", body.len())));
+    assert!(body.lines().any(|l| l == "retty_connections_closed_total 3"));
+    assert!(body.lines().any(|l| l == "retty_connections_active 0"));
+    assert!(body.lines().any(|l| l == "retty_pipeline_exceptions_total{kind=\"InvalidData\"} 1"));
+
+    // 请求头过长时直接关闭
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", metrics_port)).unwrap();
+    stream.write_all(&[b'a'; 10000]).unwrap();
+    let mut response = Vec::new();
+    let _ = stream.read_to_end(&mut response);
+    assert!(response.is_empty());
+    server.terminate();
+}
+
+
+///
+/// 连接建立后关闭自动读, 把channel 交给测试线程, 记录每次读到的字节
+///
+struct ManualReadHandler {
+    channel: Arc<Mutex<Option<InboundChannelCtx>>>,
+    reads: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl ChannelInboundHandler for ManualReadHandler {
+    fn id(&self) -> String {
+        "ManualReadHandler".to_string()
+    }
+
+    fn channel_active(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {
+        channel_handler_ctx.channel().set_auto_read(false);
+        let channel = InboundChannelCtx::new(channel_handler_ctx.channel().channel.clone());
+        *self.channel.lock().unwrap() = Some(channel);
+    }
+
+    fn channel_inactive(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx) {}
+
+    fn channel_read(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut dyn Any) {
+        self.reads.lock().unwrap().push(message::as_bytes(message).unwrap().to_vec());
+    }
+
+    fn channel_exception(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, error: RettyErrorKind) {}
+}
+
+#[test]
+pub fn test_auto_read() {
+    use std::io::Write;
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let channel = Arc::new(Mutex::new(None));
+    let reads = Arc::new(Mutex::new(Vec::new()));
+    let mut server = Bootstrap::new_server_bootstrap();
+    let (c, r) = (channel.clone(), reads.clone());
+    server.worker_group(1)
+        .bind("127.0.0.1", port)
+        .initialize_pipeline(move |inbound, _outbound| {
+            inbound.add_last(Box::new(ManualReadHandler { channel: c.clone(), reads: r.clone() }));
+        })
+        .start();
+    // 不用探测连接, 避免它的 channel_active 覆盖掉测试连接的channel
+    let mut stream = (0..200).find_map(|_| {
+        std::net::TcpStream::connect(("127.0.0.1", port)).map_err(|_| std::thread::sleep(std::time::Duration::from_millis(10))).ok()
+    }).unwrap();
+    wait_until(|| channel.lock().unwrap().is_some());
+    let mut ch = channel.lock().unwrap().take().unwrap();
+
+    // 恢复自动读时内核缓冲区是空的, 不会读到空的ByteBuf
+    ch.set_auto_read(true);
+    ch.set_auto_read(false);
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(reads.lock().unwrap().is_empty());
+
+    // 关闭自动读时数据留在内核缓冲区
+    stream.write_all(b"hello").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(reads.lock().unwrap().is_empty());
+
+    // read() 唤醒selector, 不用等到下一次poll 超时
+    let start = std::time::Instant::now();
+    ch.read();
+    wait_until(|| reads.lock().unwrap().len() == 1);
+    assert!(start.elapsed() < std::time::Duration::from_millis(150));
+    assert_eq!(reads.lock().unwrap()[0], b"hello");
+
+    stream.write_all(b"world").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert_eq!(reads.lock().unwrap().len(), 1);
+    ch.set_auto_read(true);
+    wait_until(|| reads.lock().unwrap().len() == 2);
+    assert_eq!(reads.lock().unwrap()[1], b"world");
+    stream.write_all(b"!").unwrap();
+    wait_until(|| reads.lock().unwrap().len() == 3);
+    assert!(reads.lock().unwrap().iter().all(|r| !r.is_empty()));
+
+    // 一次可读事件最多读 256KB, 剩下的数据重新排队读, 不会丢失
+    reads.lock().unwrap().clear();
+    let payload: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+    stream.write_all(&payload).unwrap();
+    wait_until(|| reads.lock().unwrap().iter().map(Vec::len).sum::<usize>() == payload.len());
+    assert!(reads.lock().unwrap().iter().all(|r| r.len() <= 256 * 1024));
+    assert!(reads.lock().unwrap().concat() == payload);
+    server.terminate();
+}
+
+
+#[test]
+pub fn test_outbound_buffer_high_water_mark() {
+    let mut buffer = ChannelOutboundBuffer::new(10);
+    buffer.push_bytes(b"hello");
+    buffer.push_bytes(b" world");
+    assert_eq!(buffer.pending_bytes(), 11);
+    assert_eq!(buffer.update_suspended(), (false, true));
+
+    // 降到高水位的一半以下才恢复读
+    match buffer.pop_front() {
+        Some(PendingWrite::Bytes(mut bytes)) => {
+            assert_eq!(bytes, b"hello world");
+            bytes.drain(..5);
+            buffer.push_front(PendingWrite::Bytes(bytes));
+        }
+        _ => panic!("expected bytes"),
+    }
+    assert_eq!(buffer.update_suspended(), (true, true));
+    buffer.pop_front();
+    assert!(buffer.is_empty());
+    assert_eq!(buffer.update_suspended(), (true, false));
+    assert!(!buffer.is_suspended());
+}
+
+///
+/// 连上本地监听器, 返回 (channel, 对端socket)
+///
+fn connected_channel(event_loop: &Arc<EventLoop>) -> (Channel, std::net::TcpStream) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let stream = mio::net::TcpStream::connect(&listener.local_addr().unwrap()).unwrap();
+    let (peer, _) = listener.accept().unwrap();
+    let channel = Channel::create(ChannelId::new_instance(), event_loop.allocate_token(), std::collections::HashMap::new(), event_loop.clone(), stream);
+    (channel, peer)
+}
+
+#[test]
+pub fn test_channel_stats() {
+    use std::io::{Read, Write};
+    let event_loop = Arc::new(EventLoop::new(0));
+    let (mut channel, mut peer) = connected_channel(&event_loop);
+    assert!(channel.stats().created_time_ms > 0);
+
+    channel.write_bytes(b"hello");
+    channel.write_bytes(b" world");
+    let mut received = [0u8; 11];
+    peer.read_exact(&mut received).unwrap();
+    assert_eq!(&received, b"hello world");
+
+    peer.write_all(b"ping").unwrap();
+    let mut buf = Vec::new();
+    while buf.len() < 4 {
+        let _ = channel.read(&mut buf);
+    }
+    channel.record_message_read();
+
+    let stats = channel.stats();
+    assert_eq!((stats.bytes_written, stats.write_count, stats.messages_written), (11, 2, 2));
+    assert_eq!((stats.bytes_read, stats.messages_read), (4, 1));
+    assert!(stats.read_count >= 1);
+    assert!(stats.last_write_time_ms >= stats.created_time_ms);
+    assert_eq!(stats.outbound_buffer_size, 0);
+
+    // 对端不读时写不下的数据留在出站缓冲区
+    let (mut other, mut other_peer) = connected_channel(&event_loop);
+    let size = 16 * 1024 * 1024;
+    other.write_bytes(&vec![7u8; size]);
+    let buffered = other.stats().outbound_buffer_size;
+    assert!(buffered > 0);
+    assert_eq!(other.stats().bytes_written as usize + buffered, size);
+    let reader = std::thread::spawn(move || {
+        let mut received = vec![0u8; size];
+        other_peer.read_exact(&mut received).unwrap();
+    });
+    while other.stats().outbound_buffer_size > 0 {
+        other.flush_outbound();
+    }
+    reader.join().unwrap();
+    assert_eq!(other.stats().bytes_written as usize, size);
+
+    // EventLoop 汇总这个循环上所有channel 的统计
+    let totals = event_loop.stats();
+    assert_eq!(totals.bytes_written(), 11 + size as u64);
+    assert_eq!(totals.messages_written(), 3);
+    assert_eq!(totals.write_count(), channel.stats().write_count + other.stats().write_count);
+    assert_eq!((totals.bytes_read(), totals.messages_read()), (4, 1));
+    assert_eq!(totals.read_count(), channel.stats().read_count);
+}
+
+
+#[test]
+pub fn test_length_field_based_frame_decoder() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(8, 0, 2, 0, 2))));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+
+    // 半包 + 粘包
+    channel.write_inbound(ByteBuf::new_from(&[0u8, 3, b'a']));
+    assert!(channel.read_inbound::<ByteBuf>().is_none());
+    channel.write_inbound(ByteBuf::new_from(&[b'b', b'c', 0, 1, b'd']));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"abc"[..]);
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"d"[..]);
+
+    // 超长帧立即报错, 丢弃后继续解码
+    channel.write_inbound(ByteBuf::new_from(&[0u8, 10, 1, 2, 3]));
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::InvalidData);
+    channel.write_inbound(ByteBuf::new_from(&[4u8, 5, 6, 7, 8, 9, 10, 0, 1, b'e']));
+    assert!(channel.read_exception().is_none());
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"e"[..]);
+
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(1024, 1, 4, -5, 0).byte_order(ByteOrder::LittleEndian))));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    channel.write_inbound(ByteBuf::new_from(&[0xca, 6, 0, 0, 0, b'x']));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &[0xca, 6, 0, 0, 0, b'x'][..]);
+}
+
+#[test]
+#[allow(deprecated)]
+pub fn test_first_integer_length_field_decoder() {
+    use crate::handler::codec::first_integer_length_field_decoder::FirstIntegerLengthFieldDecoder;
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(FirstIntegerLengthFieldDecoder::new()));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+
+    // 长度包含自身, 帧保留长度字段
+    channel.write_inbound(ByteBuf::new_from(&[0u8, 0, 0, 6, b'a']));
+    assert!(channel.read_inbound::<ByteBuf>().is_none());
+    channel.write_inbound(ByteBuf::new_from(&[b'b', 0, 0, 0, 5, b'c']));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &[0u8, 0, 0, 6, b'a', b'b'][..]);
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &[0u8, 0, 0, 5, b'c'][..]);
+
+    // 长度小于长度字段本身时报错并跳过, 不会死循环
+    channel.write_inbound(ByteBuf::new_from(&[0u8, 0, 0, 0, 0, 0, 0, 5, b'd']));
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::InvalidData);
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &[0u8, 0, 0, 5, b'd'][..]);
+}
+
+
+#[test]
+pub fn test_length_field_prepender() {
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    outbound.add_last(Box::new(LengthFieldPrepender::new(2)));
+    let mut channel = EmbeddedChannel::new(ChannelInboundHandlerPipe::new(), outbound);
+    channel.write_outbound(ByteBuf::new_from(b"abc"));
+    assert_eq!(channel.read_outbound::<ByteBuf>().unwrap().available_bytes(), &[0u8, 3, b'a', b'b', b'c'][..]);
+
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    outbound.add_last(Box::new(LengthFieldPrepender::new(2).byte_order(ByteOrder::LittleEndian).length_includes_length_field_length(true)));
+    let mut channel = EmbeddedChannel::new(ChannelInboundHandlerPipe::new(), outbound);
+    channel.write_outbound(ByteBuf::new_from(b"abc"));
+    assert_eq!(channel.read_outbound::<ByteBuf>().unwrap().available_bytes(), &[5u8, 0, b'a', b'b', b'c'][..]);
+
+    // 超出1 字节长度字段能表示的范围, 丢弃
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    outbound.add_last(Box::new(LengthFieldPrepender::new(1)));
+    let mut channel = EmbeddedChannel::new(ChannelInboundHandlerPipe::new(), outbound);
+    channel.write_outbound(ByteBuf::new_from(&[0u8; 256]));
+    assert!(channel.read_outbound::<ByteBuf>().is_none());
+}
+
+
+#[test]
+pub fn test_line_and_delimiter_based_frame_decoder() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(LineBasedFrameDecoder::new(4))));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    channel.write_inbound(ByteBuf::new_from(b"ab\r\ncd\nef"));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"ab"[..]);
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"cd"[..]);
+    assert!(channel.read_inbound::<ByteBuf>().is_none());
+    // 超长的行丢弃到换行符之后再报错
+    channel.write_inbound(ByteBuf::new_from(b"ghijk\nxy\n"));
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::InvalidData);
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"xy"[..]);
+
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(DelimiterBasedFrameDecoder::new(8, vec![b"$".to_vec(), b"##".to_vec()]).strip_delimiter(false))));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    channel.write_inbound(ByteBuf::new_from(b"a##b$c"));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"a##"[..]);
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"b$"[..]);
+    assert!(channel.read_inbound::<ByteBuf>().is_none());
+    // fail_fast: 超过长度立即报错
+    channel.write_inbound(ByteBuf::new_from(b"cccccccc"));
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::InvalidData);
+    channel.write_inbound(ByteBuf::new_from(b"c$d$"));
+    assert!(channel.read_exception().is_none());
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"d$"[..]);
+}
+
+
+#[test]
+pub fn test_fixed_length_and_varint32_frame_decoder() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(FixedLengthFrameDecoder::new(3))));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    channel.write_inbound(ByteBuf::new_from(b"abcde"));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"abc"[..]);
+    assert!(channel.read_inbound::<ByteBuf>().is_none());
+    channel.write_inbound(ByteBuf::new_from(b"f"));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &b"def"[..]);
+
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    outbound.add_last(Box::new(ProtobufVarint32LengthFieldPrepender::new()));
+    let mut channel = EmbeddedChannel::new(ChannelInboundHandlerPipe::new(), outbound);
+    channel.write_outbound(ByteBuf::new_from(&[7u8; 300]));
+    let encoded = channel.read_outbound::<ByteBuf>().unwrap();
+    assert_eq!(&encoded.available_bytes()[..2], &[0xacu8, 0x02][..]);
+
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(ProtobufVarint32FrameDecoder::new())));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    // varint 被拆在两次读里
+    channel.write_inbound(ByteBuf::new_from(&encoded.available_bytes()[..1]));
+    assert!(channel.read_inbound::<ByteBuf>().is_none());
+    channel.write_inbound(ByteBuf::new_from(&encoded.available_bytes()[1..]));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &[7u8; 300][..]);
+
+    channel.write_inbound(ByteBuf::new_from(&[0xffu8, 0xff, 0xff, 0xff, 0xff, 0x01]));
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::InvalidData);
+}
+
+
+#[test]
+pub fn test_string_codec() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(StringDecoder::new(Charset::Gbk)));
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    outbound.add_last(Box::new(StringEncoder::new(Charset::Gbk)));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.write_inbound(ByteBuf::new_from(&[0xd6u8, 0xd0, 0xce, 0xc4]));
+    assert_eq!(channel.read_inbound::<String>(), Some("中文".to_string()));
+    // 不是字节的消息原样传递
+    channel.register_message_type::<u32>();
+    channel.write_inbound(7u32);
+    assert_eq!(channel.read_inbound::<u32>(), Some(7));
+    assert!(channel.read_exception().is_none());
+    channel.write_outbound("中文".to_string());
+    assert_eq!(channel.read_outbound::<ByteBuf>().unwrap().available_bytes(), &[0xd6u8, 0xd0, 0xce, 0xc4][..]);
+
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(StringDecoder::new(Charset::Utf8).strict()));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    channel.write_inbound(ByteBuf::new_from(&[b'a', 0xff]));
+    assert!(channel.read_inbound::<String>().is_none());
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::InvalidData);
+
+    assert_eq!(Charset::Utf8.decode(&[b'a', 0xff], CodingErrorAction::Replace).unwrap(), "a\u{fffd}");
+    assert_eq!(Charset::Utf16Be.encode("a中", CodingErrorAction::Strict).unwrap(), vec![0u8, 0x61, 0x4e, 0x2d]);
+    assert_eq!(Charset::Latin1.encode("é中", CodingErrorAction::Replace).unwrap(), vec![0xe9u8, b'?']);
+    assert!(Charset::Latin1.encode("中", CodingErrorAction::Strict).is_err());
+}
+
+
+///
+/// 每次解出一个u32, channel 关闭时把不够4 字节的尾巴作为 Vec<u8> 传出去
+///
+struct IntDecoder {}
+
+impl ByteToMessageDecoder for IntDecoder {
+    fn id(&self) -> String {
+        "IntDecoder".to_string()
+    }
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        if buf.readable_bytes() >= 4 {
+            out.push(Box::new(buf.read_u32_be()));
+        }
+    }
+
+    fn decode_last(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, buf: &mut ByteBuf, out: &mut Vec<Box<dyn Any>>) {
+        out.push(Box::new(buf.available_bytes().to_vec()));
+    }
+}
+
+#[test]
+pub fn test_byte_to_message_decoder() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(IntDecoder {})));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    channel.register_message_type::<u32>();
+
+    channel.write_inbound(ByteBuf::new_from(&[0u8, 0, 0, 1, 0, 0]));
+    assert_eq!(channel.read_inbound::<u32>(), Some(1));
+    assert!(channel.read_inbound::<u32>().is_none());
+    channel.write_inbound(ByteBuf::new_from(&[0u8, 2, 0, 0, 0, 3, 9]));
+    assert_eq!(channel.read_inbound::<u32>(), Some(2));
+    assert_eq!(channel.read_inbound::<u32>(), Some(3));
+    // 不是字节的消息原样传递, 不影响累积的半包
+    channel.write_inbound("text".to_string());
+    assert_eq!(channel.read_inbound::<String>(), Some("text".to_string()));
+    assert!(channel.read_exception().is_none());
+
+    channel.close();
+    assert_eq!(channel.read_inbound::<Vec<u8>>(), Some(vec![9u8]));
+}
+
+#[test]
+pub fn test_byte_to_message_decoder_large_frame() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    inbound.add_last(Box::new(ByteToMessageDecoderHandler::new(LengthFieldBasedFrameDecoder::new(4 * 1024 * 1024, 0, 4, 0, 4))));
+    let mut channel = EmbeddedChannel::new(inbound, ChannelOutboundHandlerPipe::new());
+    let payload: Vec<u8> = (0..2 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut bytes = (payload.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(&payload);
+    // 一帧分成很多次读, 后面紧跟下一帧的一部分
+    bytes.extend_from_slice(&[0, 0, 0, 2, 7]);
+    for chunk in bytes.chunks(1000) {
+        channel.write_inbound(ByteBuf::new_from(chunk));
+    }
+    let frame = channel.read_inbound::<ByteBuf>().unwrap();
+    assert!(frame.available_bytes() == &payload[..]);
+    assert!(channel.read_inbound::<ByteBuf>().is_none());
+    assert_eq!(channel.event_loop().allocator().stats().outstanding, 1);
+
+    channel.write_inbound(ByteBuf::new_from(&[8]));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), &[7u8, 8][..]);
+    // 半包全部消费完, 累积缓冲区归还给内存池
+    assert_eq!(channel.event_loop().allocator().stats().outstanding, 0);
+}
+
+#[test]
+pub fn test_encoder_output_pooled() {
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    outbound.add_last(Box::new(LengthFieldPrepender::new(2)));
+    let mut channel = EmbeddedChannel::new(ChannelInboundHandlerPipe::new(), outbound);
+    for _ in 0..3 {
+        channel.write_outbound(ByteBuf::new_from(b"abc"));
+        assert_eq!(channel.read_outbound::<ByteBuf>().unwrap().available_bytes(), &[0u8, 3, b'a', b'b', b'c'][..]);
+    }
+    // 编码器的输出从内存池申请, 写出后归还, 后面的写复用同一块内存
+    let stats = channel.event_loop().allocator().stats();
+    assert_eq!(stats.outstanding, 0);
+    assert_eq!(stats.hits, 2);
+}
+
+
+#[derive(Clone, Debug, PartialEq)]
+struct Command {
+    name: String,
+    args: Vec<String>,
+}
+
+///
+/// "SET a 1" -> Command, 空行不产生消息
+///
+struct CommandDecoder {}
+
+impl MessageToMessageDecoder<String> for CommandDecoder {
+    fn id(&self) -> String {
+        "CommandDecoder".to_string()
+    }
+
+    fn decode(&mut self, channel_handler_ctx: &mut ChannelInboundHandlerCtx, message: &mut String, out: &mut Vec<Box<dyn Any>>) {
+        let mut parts = message.split_whitespace().map(String::from);
+        if let Some(name) = parts.next() {
+            out.push(Box::new(Command { name, args: parts.collect() }));
+        }
+    }
+}
+
+struct CommandEncoder {}
+
+impl MessageToMessageEncoder<Command> for CommandEncoder {
+    fn id(&self) -> String {
+        "CommandEncoder".to_string()
+    }
+
+    fn encode(&mut self, channel_handler_ctx: &mut ChannelOutboundHandlerCtx, message: &mut Command, out: &mut Vec<Box<dyn Any>>) {
+        let mut line = message.name.clone();
+        for arg in &message.args {
+            line.push(' ');
+            line.push_str(arg);
+        }
+        out.push(Box::new(line));
+    }
+}
+
+#[test]
+pub fn test_message_to_message_codec() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    let codec = CombinedChannelDuplexHandler::new("CommandCodec",
+                                                  Box::new(MessageToMessageDecoderHandler::new(CommandDecoder {})),
+                                                  Box::new(MessageToMessageEncoderHandler::new(CommandEncoder {})));
+    assert_eq!(codec.name(), "CommandCodec");
+    codec.add_last(&mut inbound, &mut outbound);
+    assert_eq!(inbound.handlers[0].id(), "CommandCodec");
+    assert_eq!(outbound.handlers[0].id(), "CommandCodec");
+
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<Command>();
+    channel.write_inbound("SET a 1".to_string());
+    channel.write_inbound("  ".to_string());
+    let command = Command { name: "SET".to_string(), args: vec!["a".to_string(), "1".to_string()] };
+    assert_eq!(channel.read_inbound::<Command>(), Some(command.clone()));
+    assert!(channel.read_inbound::<Command>().is_none());
+
+    channel.write_outbound(command);
+    assert_eq!(channel.read_outbound::<String>(), Some("SET a 1".to_string()));
+    // 不是Command 的消息原样传出
+    channel.write_outbound(vec![1u8]);
+    assert_eq!(channel.read_outbound::<Vec<u8>>(), Some(vec![1u8]));
+}
+
+
+fn http_server_channel(codec: HttpServerCodec) -> EmbeddedChannel {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    codec.add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpServerExpectContinueHandler::new()));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<HttpRequest>()
+        .register_message_type::<HttpContent>()
+        .register_message_type::<LastHttpContent>();
+    channel
+}
+
+fn read_outbound_string(channel: &mut EmbeddedChannel) -> String {
+    String::from_utf8(read_outbound_bytes(channel)).unwrap()
+}
+
+fn read_outbound_bytes(channel: &mut EmbeddedChannel) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    while let Some(buf) = channel.read_outbound::<ByteBuf>() {
+        bytes.extend_from_slice(buf.available_bytes());
+    }
+    bytes
+}
+
+#[test]
+pub fn test_http_server_codec() {
+    let mut channel = http_server_channel(HttpServerCodec::new());
+
+    // 流水线请求, 第二个请求的消息体分两次到达
+    channel.write_inbound(ByteBuf::new_from(b"GET /a HTTP/1.1\r\nHost: x\r\n\r\nPOST /b HTTP/1.1\r\nContent-Length: 5\r\n\r\nhel"));
+    let request = channel.read_inbound::<HttpRequest>().unwrap();
+    assert_eq!((request.method(), request.uri()), (&HttpMethod::Get, "/a"));
+    assert_eq!(request.headers().get("host"), Some("x"));
+    assert_eq!(channel.read_inbound::<LastHttpContent>(), Some(LastHttpContent::empty()));
+    let request = channel.read_inbound::<HttpRequest>().unwrap();
+    assert_eq!(request.content_length(), Some(5));
+    assert_eq!(channel.read_inbound::<HttpContent>(), Some(HttpContent::new(b"hel".to_vec())));
+    channel.write_inbound(ByteBuf::new_from(b"lo"));
+    assert_eq!(channel.read_inbound::<LastHttpContent>(), Some(LastHttpContent::new(b"lo".to_vec())));
+
+    // chunked 请求体和trailer
+    channel.write_inbound(ByteBuf::new_from(b"POST /c HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3;ext=1\r\nabc\r\n0\r\nX-Trailer: t\r\n\r\n"));
+    assert!(channel.read_inbound::<HttpRequest>().unwrap().is_transfer_encoding_chunked());
+    assert_eq!(channel.read_inbound::<HttpContent>(), Some(HttpContent::new(b"abc".to_vec())));
+    let last = channel.read_inbound::<LastHttpContent>().unwrap();
+    assert_eq!(last.trailing_headers().get("X-Trailer"), Some("t"));
+
+    // 三个请求依次响应, 保持连接
+    for _ in 0..3 {
+        let mut response = HttpResponse::new(HttpVersion::Http11, HttpResponseStatus::OK);
+        response.set_content_length(2);
+        channel.write_outbound(response);
+        channel.write_outbound(LastHttpContent::new(b"hi".to_vec()));
+    }
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi".repeat(3));
+    assert!(channel.is_active());
+
+    // chunked 响应
+    channel.write_inbound(ByteBuf::new_from(b"GET /d HTTP/1.1\r\n\r\n"));
+    let mut response = HttpResponse::new(HttpVersion::Http11, HttpResponseStatus::OK);
+    response.set_transfer_encoding_chunked(true);
+    channel.write_outbound(response);
+    channel.write_outbound(HttpContent::new(b"abc".to_vec()));
+    channel.write_outbound(LastHttpContent::empty());
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n");
+
+    // Expect: 100-continue
+    channel.write_inbound(ByteBuf::new_from(b"PUT /e HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 1\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 100 Continue\r\n\r\n");
+    assert!(!channel.read_inbound::<HttpRequest>().unwrap().headers().contains("Expect"));
+
+    // HTTP/1.0 请求, 响应没有长度, 写完后关闭连接
+    let mut channel = http_server_channel(HttpServerCodec::new());
+    channel.write_inbound(ByteBuf::new_from(b"GET / HTTP/1.0\r\n\r\n"));
+    channel.write_outbound(HttpResponse::new(HttpVersion::Http10, HttpResponseStatus::OK));
+    channel.write_outbound(LastHttpContent::new(b"bye".to_vec()));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.0 200 OK\r\n\r\nbye");
+    assert!(!channel.is_active());
+
+    // header 数量超过限制
+    let mut channel = http_server_channel(HttpServerCodec::new().max_header_count(1));
+    channel.write_inbound(ByteBuf::new_from(b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n"));
+    assert!(channel.read_inbound::<HttpRequest>().is_none());
+    assert!(channel.read_exception().unwrap().message.starts_with("TooLongHttpHeader"));
+
+    // 请求行超过限制
+    let mut channel = http_server_channel(HttpServerCodec::new().max_initial_line_length(16));
+    channel.write_inbound(ByteBuf::new_from(b"GET /very/long/uri HTTP/1.1\r\n"));
+    assert!(channel.read_exception().unwrap().message.starts_with("TooLongHttpLine"));
+
+    // 请求体没有收完连接就关闭了
+    let mut channel = http_server_channel(HttpServerCodec::new());
+    channel.write_inbound(ByteBuf::new_from(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhe"));
+    assert!(channel.read_inbound::<HttpRequest>().is_some());
+    assert_eq!(channel.read_inbound::<HttpContent>(), Some(HttpContent::new(b"he".to_vec())));
+    channel.close();
+    assert!(channel.read_exception().unwrap().message.starts_with("PrematureChannelClosure"));
+}
+
+
+#[test]
+pub fn test_http_object_aggregator() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpObjectAggregator::new(8)));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<FullHttpRequest>();
+
+    // chunked 请求体聚合后改成 Content-Length
+    channel.write_inbound(ByteBuf::new_from(b"POST /a HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n"));
+    let request = channel.read_inbound::<FullHttpRequest>().unwrap();
+    assert_eq!(request.content(), b"abcde");
+    assert_eq!(request.content_length(), Some(5));
+    assert!(!request.is_transfer_encoding_chunked());
+
+    // 100-continue
+    channel.write_inbound(ByteBuf::new_from(b"PUT /b HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 4\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 100 Continue\r\n\r\n");
+    channel.write_inbound(ByteBuf::new_from(b"1234"));
+    let request = channel.read_inbound::<FullHttpRequest>().unwrap();
+    assert_eq!((request.uri(), request.content()), ("/b", &b"1234"[..]));
+    assert!(!request.headers().contains("Expect"));
+
+    // 100-continue 但长度超过限制, 不发送 100, 丢弃请求并保持连接
+    channel.write_inbound(ByteBuf::new_from(b"PUT /c HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 100\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 413 Request Entity Too Large\r\nContent-Length: 0\r\n\r\n");
+    assert!(channel.is_active());
+
+    // 不支持的期望
+    channel.write_inbound(ByteBuf::new_from(b"GET /d HTTP/1.1\r\nExpect: x\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 417 Expectation Failed\r\nContent-Length: 0\r\n\r\n");
+    assert!(channel.read_inbound::<FullHttpRequest>().is_none());
+
+    // 没有长度的请求体超过限制, 请求不保持连接, 响应后关闭
+    channel.write_inbound(ByteBuf::new_from(b"POST /e HTTP/1.1\r\nConnection: close\r\nTransfer-Encoding: chunked\r\n\r\n9\r\n123456789\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 413 Request Entity Too Large\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+    assert!(channel.read_inbound::<FullHttpRequest>().is_none());
+    assert!(!channel.is_active());
+}
+
+#[test]
+pub fn test_http_router() {
+    let decoder = QueryStringDecoder::new("/a%20b/c?x=1+2&y=%E4%BD%A0&x=3&flag#top");
+    assert_eq!(decoder.path(), "/a b/c");
+    assert_eq!(decoder.parameter_all("x"), vec!["1 2", "3"]);
+    assert_eq!(decoder.parameter("y"), Some("你"));
+    assert_eq!(decoder.parameter("flag"), Some(""));
+
+    let router = HttpRouter::new()
+        .get("/users/:id", |req| {
+            let body = format!("user {} {}", req.param("id").unwrap(), req.query("fields").unwrap_or("-"));
+            FullHttpResponse::new(req.version(), HttpResponseStatus::OK, body.into_bytes())
+        })
+        .get("/users/me", |req| FullHttpResponse::new(req.version(), HttpResponseStatus::OK, b"me".to_vec()))
+        .post("/users", |req| FullHttpResponse::new(req.version(), HttpResponseStatus::CREATED, req.content().to_vec()))
+        .get("/files/*path", |req| FullHttpResponse::new(req.version(), HttpResponseStatus::OK, req.param("path").unwrap().as_bytes().to_vec()));
+
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpObjectAggregator::new(1024)));
+    inbound.add_last(Box::new(router.clone()));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /users/42?fields=name HTTP/1.1\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 200 OK\r\nContent-Length: 12\r\n\r\nuser 42 name");
+
+    // 静态路径优先于参数
+    channel.write_inbound(ByteBuf::new_from(b"GET /users/me HTTP/1.1\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nme");
+
+    channel.write_inbound(ByteBuf::new_from(b"POST /users HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 201 Created\r\nContent-Length: 3\r\n\r\nabc");
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /files/a/b%20c.txt HTTP/1.1\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\na/b c.txt");
+
+    // HEAD 使用 GET 的处理函数, 不写消息体
+    channel.write_inbound(ByteBuf::new_from(b"HEAD /users/me HTTP/1.1\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n");
+
+    channel.write_inbound(ByteBuf::new_from(b"DELETE /users HTTP/1.1\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel),
+               "HTTP/1.1 405 Method Not Allowed\r\nContent-Type: text/plain; charset=utf-8\r\nAllow: POST\r\nContent-Length: 18\r\n\r\nMethod Not Allowed");
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /nothing HTTP/1.1\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel),
+               "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 9\r\n\r\nNot Found");
+
+    // 自定义 not_found
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpObjectAggregator::new(1024)));
+    inbound.add_last(Box::new(router.not_found(|req| {
+        FullHttpResponse::new(req.version(), HttpResponseStatus::NOT_FOUND, req.path().as_bytes().to_vec())
+    })));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.write_inbound(ByteBuf::new_from(b"GET /x%2Fy HTTP/1.0\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.0 404 Not Found\r\nContent-Length: 4\r\n\r\n/x/y");
+    assert!(!channel.is_active());
+}
+
+#[test]
+pub fn test_http_static_file_handler() {
+    let root = std::env::temp_dir().join(format!("retty-static-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(root.join("fw")).unwrap();
+    std::fs::write(root.join("fw").join("image.bin"), b"0123456789").unwrap();
+    std::fs::write(root.join("index.html"), b"<p>").unwrap();
+
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpObjectAggregator::new(1024)));
+    inbound.add_last(Box::new(HttpStaticFileHandler::new(&root).prefix("/static/")));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<FullHttpRequest>();
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/fw/image.bin HTTP/1.1\r\n\r\n"));
+    let response = read_outbound_string(&mut channel);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nAccept-Ranges: bytes\r\nETag: \"a-"));
+    assert!(response.contains("\r\nLast-Modified: ") && response.ends_with(" GMT\r\nContent-Length: 10\r\n\r\n0123456789"));
+    let etag = response.lines().find(|l| l.starts_with("ETag: ")).unwrap()[6..].to_string();
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/index.html HTTP/1.1\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).contains("Content-Type: text/html; charset=utf-8\r\n"));
+
+    channel.write_inbound(ByteBuf::new_from(b"HEAD /static/fw/image.bin HTTP/1.1\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).ends_with("Content-Length: 10\r\n\r\n"));
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/fw/image.bin HTTP/1.1\r\nRange: bytes=2-5\r\n\r\n"));
+    let response = read_outbound_string(&mut channel);
+    assert!(response.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+    assert!(response.ends_with("Content-Range: bytes 2-5/10\r\nContent-Length: 4\r\n\r\n2345"));
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/fw/image.bin HTTP/1.1\r\nRange: bytes=-3\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).ends_with("Content-Range: bytes 7-9/10\r\nContent-Length: 3\r\n\r\n789"));
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/fw/image.bin HTTP/1.1\r\nRange: bytes=10-\r\n\r\n"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 416 Requested Range Not Satisfiable\r\nContent-Range: bytes */10\r\nContent-Length: 0\r\n\r\n");
+
+    // If-Range 不匹配时返回整个文件
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/fw/image.bin HTTP/1.1\r\nRange: bytes=2-5\r\nIf-Range: \"old\"\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).starts_with("HTTP/1.1 200 OK\r\n"));
+
+    channel.write_inbound(ByteBuf::new_from(format!("GET /static/fw/image.bin HTTP/1.1\r\nIf-None-Match: \"x\", {}\r\n\r\n", etag).as_bytes()));
+    let response = read_outbound_string(&mut channel);
+    assert!(response.starts_with(&format!("HTTP/1.1 304 Not Modified\r\nETag: {}\r\n", etag)));
+    assert!(response.ends_with(" GMT\r\n\r\n"));
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/fw/image.bin HTTP/1.1\r\nIf-Modified-Since: Fri, 01 Jan 2100 00:00:00 GMT\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).starts_with("HTTP/1.1 304 Not Modified\r\n"));
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/fw/../../etc/passwd HTTP/1.1\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/%2e%2e%2fsecret HTTP/1.1\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).starts_with("HTTP/1.1 403 Forbidden\r\n"));
+
+    channel.write_inbound(ByteBuf::new_from(b"GET /static/missing.bin HTTP/1.1\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+    channel.write_inbound(ByteBuf::new_from(b"POST /static/fw/image.bin HTTP/1.1\r\nContent-Length: 0\r\n\r\n"));
+    assert!(read_outbound_string(&mut channel).contains("\r\nAllow: GET, HEAD\r\n"));
+
+    // 不在prefix 下的请求传给下一个handler
+    channel.write_inbound(ByteBuf::new_from(b"GET /staticx HTTP/1.1\r\n\r\n"));
+    assert_eq!(channel.read_inbound::<FullHttpRequest>().unwrap().uri(), "/staticx");
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+pub fn test_http_static_file_over_tcp() {
+    use std::io::{Read, Write};
+
+    let root = std::env::temp_dir().join(format!("retty-static-tcp-{}", Uuid::new_v4()));
+    std::fs::create_dir_all(&root).unwrap();
+    let content = (0..5 * 1024 * 1024 + 7).map(|i| (i % 251) as u8).collect::<Vec<u8>>();
+    std::fs::write(root.join("big.bin"), &content).unwrap();
+
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let static_root = root.clone();
+    let mut server = Bootstrap::new_server_bootstrap();
+    server.worker_group(1)
+        .bind("127.0.0.1", port)
+        .initialize_pipeline(move |inbound, outbound| {
+            HttpServerCodec::new().add_last(inbound, outbound);
+            inbound.add_last(Box::new(HttpObjectAggregator::new(1024)));
+            inbound.add_last(Box::new(HttpStaticFileHandler::new(&static_root)));
+        })
+        .start();
+    wait_until(|| std::net::TcpStream::connect(("127.0.0.1", port)).is_ok());
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(10))).unwrap();
+
+    // 同一个连接上连续两次下载
+    for _ in 0..2 {
+        stream.write_all(b"GET /big.bin HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        let mut response = Vec::new();
+        let mut buf = [0u8; 65536];
+        let head_end = loop {
+            if let Some(pos) = response.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0);
+            response.extend_from_slice(&buf[..n]);
+        };
+        let head = String::from_utf8(response[..head_end].to_vec()).unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains(&format!("Content-Length: {}\r\n", content.len())));
+        while response.len() < head_end + content.len() {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0);
+            response.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(response.len(), head_end + content.len());
+        assert!(response[head_end..] == content[..]);
+    }
+
+    // Connection: close, 文件写完之后才关闭连接
+    stream.write_all(b"GET /big.bin HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let (head, body) = split_http_response(&response);
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert_eq!(body.len(), content.len());
+    assert!(body == content);
+
+    // 请求之后单独读到EOF 时, 出站缓冲区里的文件写完才关闭连接; 文件比socket 缓冲区大, EOF 时还没写完
+    let content = (0..32 * 1024 * 1024).map(|i| (i % 241) as u8).collect::<Vec<u8>>();
+    std::fs::write(root.join("huge.bin"), &content).unwrap();
+    let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.set_read_timeout(Some(std::time::Duration::from_secs(10))).unwrap();
+    stream.write_all(b"GET /huge.bin HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    stream.shutdown(std::net::Shutdown::Write).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let (_, body) = split_http_response(&response);
+    assert!(body == content);
+
+    server.terminate();
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+///
+/// 拆出响应头和消息体, chunked 的消息体合并成一段
+///
+fn split_http_response(response: &[u8]) -> (String, Vec<u8>) {
+    let head_end = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap() + 4;
+    let head = String::from_utf8(response[..head_end].to_vec()).unwrap();
+    if !head.contains("Transfer-Encoding: chunked") {
+        return (head, response[head_end..].to_vec());
+    }
+    let mut body = Vec::new();
+    let mut pos = head_end;
+    loop {
+        let line_end = pos + response[pos..].windows(2).position(|w| w == b"\r\n").unwrap();
+        let size = usize::from_str_radix(std::str::from_utf8(&response[pos..line_end]).unwrap(), 16).unwrap();
+        if size == 0 {
+            return (head, body);
+        }
+        body.extend_from_slice(&response[line_end + 2..line_end + 2 + size]);
+        pos = line_end + 2 + size + 2;
+    }
+}
+
+#[test]
+pub fn test_http_content_compression() {
+    use std::io::{Read, Write};
+
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpContentDecompressor::new()));
+    HttpContentCompressor::new().content_size_threshold(16).add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpObjectAggregator::new(1 << 20)));
+    inbound.add_last(Box::new(HttpRouter::new().post("/echo", |req| {
+        FullHttpResponse::new(req.version(), HttpResponseStatus::OK, req.content().to_vec())
+    })));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+
+    // gzip 请求体分两个chunk 到达, 解压后原样返回, 响应按q 值选择 br
+    let body = "retty ".repeat(100);
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(body.as_bytes()).unwrap();
+    let gzipped = gzip.finish().unwrap();
+    let (first, second) = gzipped.split_at(gzipped.len() / 2);
+    let mut request = b"POST /echo HTTP/1.1\r\nContent-Encoding: gzip\r\nAccept-Encoding: gzip;q=0.5, br\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+    for chunk in [first, second].iter() {
+        request.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+        request.extend_from_slice(chunk);
+        request.extend_from_slice(b"\r\n");
+    }
+    request.extend_from_slice(b"0\r\n\r\n");
+    channel.write_inbound(ByteBuf::new_from(&request));
+    let (head, content) = split_http_response(&read_outbound_bytes(&mut channel));
+    assert!(head.contains("\r\nContent-Encoding: br\r\nVary: Accept-Encoding\r\n"));
+    assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", content.len())));
+    let mut decoded = String::new();
+    brotli::Decompressor::new(&content[..], 4096).read_to_string(&mut decoded).unwrap();
+    assert_eq!(decoded, body);
+
+    // 小于阈值不压缩
+    channel.write_inbound(ByteBuf::new_from(b"POST /echo HTTP/1.1\r\nAccept-Encoding: gzip\r\nContent-Length: 5\r\n\r\nhello"));
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello");
+
+    // q=0 的编码不能使用
+    let request = format!("POST /echo HTTP/1.1\r\nAccept-Encoding: br;q=0, gzip;q=0, *\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+    channel.write_inbound(ByteBuf::new_from(request.as_bytes()));
+    let (head, content) = split_http_response(&read_outbound_bytes(&mut channel));
+    assert!(head.contains("Content-Encoding: deflate\r\n"));
+    let mut decoded = String::new();
+    flate2::read::ZlibDecoder::new(&content[..]).read_to_string(&mut decoded).unwrap();
+    assert_eq!(decoded, body);
+
+    // 损坏的请求体
+    channel.write_inbound(ByteBuf::new_from(b"POST /echo HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: 4\r\n\r\nxxxx"));
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::InvalidData);
+
+    // 分段的响应边写边压缩, 改成chunked
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    HttpContentCompressor::new().add_last(&mut inbound, &mut outbound);
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<HttpRequest>().register_message_type::<LastHttpContent>();
+    channel.write_inbound(ByteBuf::new_from(b"GET /stream HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n"));
+    // 长度未知的响应也压缩
+    channel.write_outbound(HttpResponse::new(HttpVersion::Http11, HttpResponseStatus::OK));
+    channel.write_outbound(HttpContent::new(b"hello ".to_vec()));
+    channel.write_outbound(LastHttpContent::new(b"world!".to_vec()));
+    let (head, content) = split_http_response(&read_outbound_bytes(&mut channel));
+    assert_eq!(head, "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nVary: Accept-Encoding\r\nTransfer-Encoding: chunked\r\n\r\n");
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(&content[..]).read_to_string(&mut decoded).unwrap();
+    assert_eq!(decoded, "hello world!");
+
+    // FileRegion 分段读出来压缩
+    let path = std::env::temp_dir().join(format!("retty-compress-{}", Uuid::new_v4()));
+    let file_content = (0..200 * 1024).map(|i| (i % 7) as u8).collect::<Vec<u8>>();
+    std::fs::write(&path, &file_content).unwrap();
+    channel.write_inbound(ByteBuf::new_from(b"GET /file HTTP/1.1\r\nAccept-Encoding: gzip\r\n\r\n"));
+    let mut response = HttpResponse::new(HttpVersion::Http11, HttpResponseStatus::OK);
+    response.set_content_length(file_content.len() as u64);
+    channel.write_outbound(response);
+    channel.write_outbound(FileRegion::open(&path).unwrap());
+    channel.write_outbound(LastHttpContent::empty());
+    let (head, content) = split_http_response(&read_outbound_bytes(&mut channel));
+    assert!(head.contains("Content-Encoding: gzip\r\n") && !head.contains("Content-Length"));
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(&content[..]).read_to_end(&mut decoded).unwrap();
+    assert!(decoded == file_content);
+    std::fs::remove_file(&path).unwrap();
+
+    // 请求体解压后超过限制, 响应413
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(&vec![0u8; 1 << 20]).unwrap();
+    let bomb = gzip.finish().unwrap();
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpContentDecompressor::new().max_content_length(1024)));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<HttpRequest>().register_message_type::<HttpContent>().register_message_type::<LastHttpContent>();
+    let mut request = format!("POST /upload HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n", bomb.len()).into_bytes();
+    request.extend_from_slice(&bomb);
+    channel.write_inbound(ByteBuf::new_from(&request));
+    assert!(channel.read_inbound::<HttpRequest>().is_some());
+    assert!(channel.read_inbound::<HttpContent>().is_none() && channel.read_inbound::<LastHttpContent>().is_none());
+    assert_eq!(read_outbound_string(&mut channel), "HTTP/1.1 413 Request Entity Too Large\r\nContent-Length: 0\r\n\r\n");
+    assert!(channel.is_active());
+
+    // 响应体解压后超过限制
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpClientCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpContentDecompressor::new().max_content_length(1024)));
+    inbound.add_last(Box::new(HttpObjectAggregator::new(1 << 20)));
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.write_outbound(FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/", Vec::new()));
+    read_outbound_bytes(&mut channel);
+    let mut response = format!("HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n", bomb.len()).into_bytes();
+    response.extend_from_slice(&bomb);
+    channel.write_inbound(ByteBuf::new_from(&response));
+    let error = channel.read_exception().unwrap();
+    assert_eq!(error.kind, ErrorKind::InvalidData);
+    assert!(error.message.starts_with("TooLongFrame"));
+}
+
+#[test]
+pub fn test_http_client_codec() {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpClientCodec::new().add_last(&mut inbound, &mut outbound);
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<HttpResponse>()
+        .register_message_type::<HttpContent>()
+        .register_message_type::<LastHttpContent>();
+
+    let mut request = FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/a", Vec::new());
+    request.headers_mut().set("Host", "x");
+    channel.write_outbound(request);
+    channel.write_outbound(FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Head, "/b", Vec::new()));
+    let mut request = HttpRequest::new(HttpVersion::Http11, HttpMethod::Post, "/c");
+    request.set_transfer_encoding_chunked(true);
+    channel.write_outbound(request);
+    channel.write_outbound(HttpContent::new(b"abc".to_vec()));
+    channel.write_outbound(LastHttpContent::empty());
+    assert_eq!(read_outbound_string(&mut channel),
+               "GET /a HTTP/1.1\r\nHost: x\r\n\r\nHEAD /b HTTP/1.1\r\n\r\nPOST /c HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n");
+
+    // 1xx 不对应请求, HEAD 的响应没有消息体, 没有长度的响应读到连接关闭
+    channel.write_inbound(ByteBuf::new_from(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi"));
+    assert_eq!(channel.read_inbound::<HttpResponse>().unwrap().status().code(), 100);
+    assert_eq!(channel.read_inbound::<LastHttpContent>(), Some(LastHttpContent::empty()));
+    assert_eq!(channel.read_inbound::<HttpResponse>().unwrap().status().code(), 200);
+    assert_eq!(channel.read_inbound::<LastHttpContent>(), Some(LastHttpContent::new(b"hi".to_vec())));
+    channel.write_inbound(ByteBuf::new_from(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nHTTP/1.1 404 Not Here\r\n\r\nab"));
+    assert_eq!(channel.read_inbound::<HttpResponse>().unwrap().content_length(), Some(5));
+    assert_eq!(channel.read_inbound::<LastHttpContent>(), Some(LastHttpContent::empty()));
+    assert_eq!(channel.read_inbound::<HttpResponse>().unwrap().status().reason_phrase(), "Not Here");
+    assert_eq!(channel.read_inbound::<HttpContent>(), Some(HttpContent::new(b"ab".to_vec())));
+    channel.write_inbound(ByteBuf::new_from(b"c"));
+    assert_eq!(channel.read_inbound::<HttpContent>(), Some(HttpContent::new(b"c".to_vec())));
+    channel.close();
+    assert_eq!(channel.read_inbound::<LastHttpContent>(), Some(LastHttpContent::empty()));
+    assert!(channel.read_exception().is_none());
+
+    // 101 之后的数据原样传递
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpClientCodec::new().add_last(&mut inbound, &mut outbound);
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<HttpResponse>()
+        .register_message_type::<LastHttpContent>();
+    channel.write_outbound(FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/ws", Vec::new()));
+    read_outbound_bytes(&mut channel);
+    channel.write_inbound(ByteBuf::new_from(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n\x81\x00"));
+    assert_eq!(channel.read_inbound::<HttpResponse>().unwrap().status().code(), 101);
+    assert_eq!(channel.read_inbound::<LastHttpContent>(), Some(LastHttpContent::empty()));
+    assert_eq!(channel.read_inbound::<ByteBuf>().unwrap().available_bytes(), b"\x81\x00");
+
+    // 响应没有收到就关闭连接
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpClientCodec::new().add_last(&mut inbound, &mut outbound);
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.write_outbound(FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/", Vec::new()));
+    channel.write_inbound(ByteBuf::new_from(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nabc"));
+    channel.close();
+    assert_eq!(channel.read_exception().unwrap().kind, ErrorKind::UnexpectedEof);
+}
+
+#[test]
+pub fn test_http_connection_pool() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let router = HttpRouter::new()
+        .get("/hello", |req| FullHttpResponse::new(req.version(), HttpResponseStatus::OK, b"hello".to_vec()))
+        .get("/slow", |req| {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            FullHttpResponse::new(req.version(), HttpResponseStatus::OK, b"slow".to_vec())
+        })
+        .get("/bye", |req| {
+            let mut response = FullHttpResponse::new(req.version(), HttpResponseStatus::OK, b"bye".to_vec());
+            response.set_keep_alive(false);
+            response
+        });
+    let mut server = Bootstrap::new_server_bootstrap();
+    server.worker_group(2)
+        .bind("127.0.0.1", port)
+        .initialize_pipeline(move |inbound, outbound| {
+            HttpServerCodec::new().add_last(inbound, outbound);
+            inbound.add_last(Box::new(HttpObjectAggregator::new(1024)));
+            inbound.add_last(Box::new(router.clone()));
+        })
+        .start();
+    wait_until(|| std::net::TcpStream::connect(("127.0.0.1", port)).is_ok());
+    let metrics = server.metrics();
+    wait_until(|| metrics.accepted_connections() == 1);
+
+    let pool = Arc::new(HttpConnectionPool::new().max_connections_per_host(2).max_idle_per_host(1).request_timeout_ms(5000));
+    let get = |path: &str| FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, path, Vec::new());
+
+    // keep-alive 连接被复用
+    for _ in 0..3 {
+        let response = pool.request("127.0.0.1", port, get("/hello")).unwrap();
+        assert_eq!(response.content(), b"hello");
+    }
+    assert_eq!(metrics.accepted_connections(), 2);
+    assert_eq!((pool.idle_count("127.0.0.1", port), pool.leased_count("127.0.0.1", port)), (1, 0));
+
+    // 最多两个连接, 归还后只保留一个空闲连接
+    let handles = (0..4).map(|_| {
+        let pool = pool.clone();
+        std::thread::spawn(move || pool.request("127.0.0.1", port, FullHttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/slow", Vec::new())).unwrap())
+    }).collect::<Vec<_>>();
+    for handle in handles {
+        assert_eq!(handle.join().unwrap().content(), b"slow");
+    }
+    assert_eq!(metrics.accepted_connections(), 3);
+    assert_eq!((pool.idle_count("127.0.0.1", port), pool.leased_count("127.0.0.1", port)), (1, 0));
+
+    // 响应不保持连接时不放回
+    let response = pool.request("127.0.0.1", port, get("/bye")).unwrap();
+    assert_eq!(response.content(), b"bye");
+    assert_eq!(pool.idle_count("127.0.0.1", port), 0);
+
+    let response = pool.request("127.0.0.1", port, get("/missing")).unwrap();
+    assert_eq!(response.status().code(), 404);
+    assert!(pool.request("127.0.0.1", 1, get("/")).is_err());
+
+    pool.close();
+    server.terminate();
+}
+
+fn websocket_server_channel(handler: WebSocketServerProtocolHandler) -> EmbeddedChannel {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpServerCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpObjectAggregator::new(1024)));
+    handler.add_last(&mut inbound, &mut outbound);
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<FullHttpRequest>()
+        .register_message_type::<WebSocketHandshakeComplete>()
+        .register_message_type::<WebSocketFrame>();
+    channel
+}
+
+fn websocket_handshake_request(version: &str) -> Vec<u8> {
+    format!("GET /ws?room=1 HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nConnection: keep-alive, Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: {}\r\nSec-WebSocket-Protocol: superchat, chat\r\n\r\n", version).into_bytes()
+}
+
+fn masked_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_frame(fin, 0, opcode, payload, Some([0x37, 0xfa, 0x21, 0x3d]), &mut out);
+    out
+}
+
+#[test]
+pub fn test_websocket_server() {
+    let mut channel = websocket_server_channel(WebSocketServerProtocolHandler::new("/ws").subprotocols(&["chat"]));
+
+    // 其它路径的请求原样传递
+    channel.write_inbound(ByteBuf::new_from(b"GET /index HTTP/1.1\r\n\r\n"));
+    assert_eq!(channel.read_inbound::<FullHttpRequest>().unwrap().uri(), "/index");
+
+    // RFC 6455 的示例key, 握手请求后面紧跟着第一帧
+    let mut bytes = websocket_handshake_request("13");
+    bytes.extend(masked_frame(true, 0x1, "你好".as_bytes()));
+    channel.write_inbound(ByteBuf::new_from(&bytes));
+    assert_eq!(read_outbound_string(&mut channel),
+               "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\nSec-WebSocket-Protocol: chat\r\n\r\n");
+    let complete = channel.read_inbound::<WebSocketHandshakeComplete>().unwrap();
+    assert_eq!((complete.uri(), complete.subprotocol()), ("/ws?room=1", Some("chat")));
+    assert_eq!(channel.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Text("你好".to_string())));
+
+    // 分片中间插入 Ping, 自动回复 Pong; 最后一帧分两次到达
+    let mut bytes = masked_frame(false, 0x2, b"ab");
+    bytes.extend(masked_frame(true, 0x9, b"p"));
+    bytes.extend(masked_frame(false, 0x0, b"cd"));
+    let last = masked_frame(true, 0x0, b"e");
+    bytes.extend_from_slice(&last[..3]);
+    channel.write_inbound(ByteBuf::new_from(&bytes));
+    assert_eq!(channel.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Ping(b"p".to_vec())));
+    assert!(channel.read_inbound::<WebSocketFrame>().is_none());
+    channel.write_inbound(ByteBuf::new_from(&last[3..]));
+    assert_eq!(channel.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Binary(b"abcde".to_vec())));
+    assert_eq!(read_outbound_bytes(&mut channel), b"\x8a\x01p");
+    // 半包由 ByteToMessageDecoderHandler 累积, 收齐后归还给内存池
+    assert_eq!(channel.event_loop().allocator().stats().outstanding, 0);
+
+    // 服务端的帧没有掩码
+    channel.write_outbound(WebSocketFrame::Text("hi".to_string()));
+    assert_eq!(read_outbound_bytes(&mut channel), b"\x81\x02hi");
+
+    // 关闭握手: 回复相同状态码的关闭帧并关闭连接
+    let mut close = 1000u16.to_be_bytes().to_vec();
+    close.extend_from_slice(b"bye");
+    channel.write_inbound(ByteBuf::new_from(&masked_frame(true, 0x8, &close)));
+    assert_eq!(channel.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::close(1000, "bye")));
+    assert_eq!(read_outbound_bytes(&mut channel), b"\x88\x02\x03\xe8");
+    assert!(!channel.is_active());
+
+    // 超过 max_frame_size 时以1009 关闭
+    let mut channel = websocket_server_channel(WebSocketServerProtocolHandler::new("/ws").max_frame_size(4));
+    channel.write_inbound(ByteBuf::new_from(&websocket_handshake_request("13")));
+    read_outbound_bytes(&mut channel);
+    channel.write_inbound(ByteBuf::new_from(&masked_frame(true, 0x1, b"hello")));
+    let close = read_outbound_bytes(&mut channel);
+    assert_eq!((close[0], &close[2..4]), (0x88, &b"\x03\xf1"[..]));
+    assert!(channel.read_exception().unwrap().message.starts_with("TooLongFrame"));
+    assert!(!channel.is_active());
+
+    // 客户端的帧必须有掩码
+    let mut channel = websocket_server_channel(WebSocketServerProtocolHandler::new("/ws"));
+    channel.write_inbound(ByteBuf::new_from(&websocket_handshake_request("13")));
+    read_outbound_bytes(&mut channel);
+    channel.write_inbound(ByteBuf::new_from(b"\x81\x02hi"));
+    assert_eq!(&read_outbound_bytes(&mut channel)[2..4], b"\x03\xea");
+    assert!(channel.read_exception().unwrap().message.starts_with("CorruptedWebSocketFrame"));
+    assert!(channel.read_inbound::<WebSocketFrame>().is_none());
+
+    // 不支持的版本
+    let mut channel = websocket_server_channel(WebSocketServerProtocolHandler::new("/ws"));
+    channel.write_inbound(ByteBuf::new_from(&websocket_handshake_request("8")));
+    let (head, _) = split_http_response(&read_outbound_bytes(&mut channel));
+    assert!(head.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+    assert!(head.contains("Sec-WebSocket-Version: 13\r\n"));
+    assert!(!channel.is_active());
+}
+
+fn websocket_client_channel(handler: WebSocketClientProtocolHandler) -> EmbeddedChannel {
+    let mut inbound = ChannelInboundHandlerPipe::new();
+    let mut outbound = ChannelOutboundHandlerPipe::new();
+    HttpClientCodec::new().add_last(&mut inbound, &mut outbound);
+    inbound.add_last(Box::new(HttpObjectAggregator::new(1024)));
+    handler.add_last(&mut inbound, &mut outbound);
+    let mut channel = EmbeddedChannel::new(inbound, outbound);
+    channel.register_message_type::<WebSocketHandshakeComplete>()
+        .register_message_type::<WebSocketFrame>();
+    channel.fire_channel_active();
+    channel
+}
+
+///
+/// 把一端写出的字节交给另一端, 返回字节数
+///
+fn transfer(from: &mut EmbeddedChannel, to: &mut EmbeddedChannel) -> Vec<u8> {
+    let bytes = read_outbound_bytes(from);
+    if !bytes.is_empty() {
+        to.write_inbound(ByteBuf::new_from(&bytes));
+    }
+    bytes
+}
+
+#[test]
+pub fn test_websocket_client_and_permessage_deflate() {
+    // 请求 server_no_context_takeover, client 保留上下文
+    let mut client = websocket_client_channel(WebSocketClientProtocolHandler::new("ws://127.0.0.1:8080/ws?room=1")
+        .subprotocols(&["chat"])
+        .header("Origin", "http://127.0.0.1")
+        .permessage_deflate(PerMessageDeflate::new().server_no_context_takeover(true)));
+    let mut server = websocket_server_channel(WebSocketServerProtocolHandler::new("/ws")
+        .subprotocols(&["chat"])
+        .permessage_deflate(PerMessageDeflate::new()));
+    let request = String::from_utf8(transfer(&mut client, &mut server)).unwrap();
+    assert!(request.starts_with("GET /ws?room=1 HTTP/1.1\r\nOrigin: http://127.0.0.1\r\nHost: 127.0.0.1:8080\r\n"));
+    assert!(request.contains("Sec-WebSocket-Extensions: permessage-deflate; server_no_context_takeover\r\n"));
+    let response = String::from_utf8(transfer(&mut server, &mut client)).unwrap();
+    assert!(response.contains("Sec-WebSocket-Extensions: permessage-deflate; server_no_context_takeover\r\n"));
+    assert_eq!(server.read_inbound::<WebSocketHandshakeComplete>().unwrap().subprotocol(), Some("chat"));
+    let complete = client.read_inbound::<WebSocketHandshakeComplete>().unwrap();
+    assert_eq!((complete.uri(), complete.subprotocol()), ("/ws?room=1", Some("chat")));
+
+    // client 的帧有掩码并设置RSV1, 保留上下文时第二条相同的消息更短
+    let text = "hello websocket, ".repeat(20);
+    client.write_outbound(WebSocketFrame::Text(text.clone()));
+    let first = transfer(&mut client, &mut server);
+    assert_eq!((first[0], first[1] & 0x80), (0xC1, 0x80));
+    assert!(first.len() < text.len() / 4);
+    client.write_outbound(WebSocketFrame::Text(text.clone()));
+    let second = transfer(&mut client, &mut server);
+    assert!(second.len() < first.len());
+    assert_eq!(server.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Text(text.clone())));
+    assert_eq!(server.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Text(text.clone())));
+
+    // server 不保留上下文, 相同的消息压缩结果相同; 控制帧不压缩
+    server.write_outbound(WebSocketFrame::Binary(text.as_bytes().to_vec()));
+    let first = transfer(&mut server, &mut client);
+    server.write_outbound(WebSocketFrame::Binary(text.as_bytes().to_vec()));
+    assert_eq!(transfer(&mut server, &mut client), first);
+    assert_eq!(client.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Binary(text.as_bytes().to_vec())));
+    assert_eq!(client.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Binary(text.as_bytes().to_vec())));
+    server.write_outbound(WebSocketFrame::Ping(b"p".to_vec()));
+    assert_eq!(transfer(&mut server, &mut client), b"\x89\x01p");
+    assert_eq!(client.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Ping(b"p".to_vec())));
+    transfer(&mut client, &mut server);
+    assert_eq!(server.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Pong(b"p".to_vec())));
+
+    // client 发起关闭
+    client.write_outbound(WebSocketFrame::close(1000, "done"));
+    transfer(&mut client, &mut server);
+    assert_eq!(server.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::close(1000, "done")));
+    assert!(!server.is_active());
+    transfer(&mut server, &mut client);
+    assert_eq!(client.read_inbound::<WebSocketFrame>(), Some(WebSocketFrame::Close(1000, String::new())));
+    assert!(!client.is_active());
+
+    // server 不支持压缩时不协商
+    let mut client = websocket_client_channel(WebSocketClientProtocolHandler::new("ws://localhost/")
+        .permessage_deflate(PerMessageDeflate::new()));
+    let mut server = websocket_server_channel(WebSocketServerProtocolHandler::new("/"));
+    assert!(String::from_utf8(transfer(&mut client, &mut server)).unwrap().contains("Host: localhost\r\n"));
+    assert!(!String::from_utf8(transfer(&mut server, &mut client)).unwrap().contains("Sec-WebSocket-Extensions"));
+    client.read_inbound::<WebSocketHandshakeComplete>().unwrap();
+    client.write_outbound(WebSocketFrame::Text("hi".to_string()));
+    assert_eq!(transfer(&mut client, &mut server)[0], 0x81);
+
+    // server 拒绝不能满足的提议, 接受下一个
+    let mut request = FullHttpRequest::from_parts(HttpRequest::new(HttpVersion::Http11, HttpMethod::Get, "/"), Vec::new());
+    request.headers_mut().set("Upgrade", "websocket").set("Connection", "Upgrade")
+        .set("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==").set("Sec-WebSocket-Version", "13")
+        .set("Sec-WebSocket-Extensions", "x-webkit-deflate-frame, permessage-deflate; server_max_window_bits=10, permessage-deflate; client_max_window_bits; client_no_context_takeover");
+    let handshaker = WebSocketServerHandshaker::new().permessage_deflate(PerMessageDeflate::new().server_no_context_takeover(true));
+    let response = handshaker.handshake(&request).unwrap();
+    assert_eq!(response.headers().get("Sec-WebSocket-Extensions"), Some("permessage-deflate; server_no_context_takeover; client_no_context_takeover"));
+
+    // Sec-WebSocket-Accept 不对时握手失败
+    let mut client = websocket_client_channel(WebSocketClientProtocolHandler::new("ws://localhost/ws"));
+    read_outbound_bytes(&mut client);
+    client.write_inbound(ByteBuf::new_from(b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                                            Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n"));
+    assert!(client.read_exception().unwrap().message.starts_with("WebSocketHandshakeException"));
+    assert!(client.read_inbound::<WebSocketHandshakeComplete>().is_none());
+    assert!(!client.is_active());
+}